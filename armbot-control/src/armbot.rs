@@ -0,0 +1,2397 @@
+use core::{cmp::Ordering, ops::Range, time::Duration};
+
+use log::{debug, error, info, warn};
+use servo_driver::{Dir, MotionProfile, ServoDriver, StepResult};
+
+use crate::{
+    error::{Context, Error, Report},
+    gamepad::{Axis, Button, Event, Events, Gamepad, Position, State, AXES, BUTTONS},
+    interpreter::{Interpreter, InterpreterConfig, Motion},
+    kinematics::{ChainAngles, KinematicsConfig, Point, WorkspaceLimits},
+    script::{Script, ScriptRun, Step},
+    trajectory::{Interpolation, Segment, Trajectory},
+    units::Degrees,
+    util::{interp::Easing, slew::SlewLimiter},
+};
+
+/// Number of joints of the arm: shoulder, elbow and gripper.
+pub const JOINTS: usize = 3;
+
+/// How fast the step size range follows a speed mode switch, in scale per second.
+const SPEED_SCALE_RATE: f32 = 2.0;
+
+/// How far off the plane of an arm without a base a point of [`ArmBot::move_to_xyz`] can be,
+/// in millimeters.
+const XYZ_PLANE_TOLERANCE: f32 = 0.5;
+
+/// Arm with `N` servo driven joints and an optional base joint `B`.
+///
+/// All per-joint state lives in fixed-size arrays, so the joint count of the config and of the
+/// servos passed to [`ArmBot::new`] is checked at compile time.
+/// Servos can be of any [`ServoDriver`] backend.
+/// Joints are bound to gamepad axes by [`JointConfig::axis`], an arm with more joints than axes
+/// shares axes or leaves joints to poses and serial commands.
+pub struct ArmBot<G, D, const N: usize = JOINTS, B = NoBase> {
+    config: ArmBotConfig<N>,
+    /// Step size range in hundredths of a degree, gamepad maps sticks onto it.
+    /// Scaled by the speed mode.
+    step_output: Range<u32>,
+    speed_mode: SpeedMode,
+    /// Scale of the step size range, follows the speed mode at [`SPEED_SCALE_RATE`].
+    speed_scale: SlewLimiter,
+    /// Stick steps, ramped up at [`ArmBotConfig::stick_accel`], indexed by [`Axis`].
+    stick_ramps: [SlewLimiter; AXES],
+    teleop_mode: TeleopMode,
+
+    /// Driven by the base rotator axis, unless a servo joint takes it.
+    base: B,
+    joints: [Joint<D>; N],
+
+    gamepad: G,
+    /// Gamepad state of the last step.
+    state: State,
+    /// Steps in a row with the sticks centered and no input changes.
+    idle_steps: u32,
+    /// Idle steps after which every joint is at rest, so the steps can be skipped.
+    settle_steps: u32,
+    /// Set by [`Action::EmergencyStop`], servos are detached and sticks are ignored.
+    stopped: bool,
+    /// Stop switch is tripped, the stop can't be released until it's reset.
+    stop_held: bool,
+    /// Steps in a row that failed to read the gamepad or found it stale.
+    lost_input_steps: u32,
+    /// Steps without gamepad input after which the failsafe engages.
+    failsafe_steps: u32,
+    /// Gamepad input is lost for longer than the failsafe timeout, sticks are centered.
+    failsafe: bool,
+    /// Pose move in progress, moving a stick cancels it.
+    pose_move: Option<PoseMove<N>>,
+    /// Queued moves, played while the sticks are centered.
+    trajectory: Trajectory<N>,
+    /// Script of [`ArmBot::run_script`].
+    script: Option<Script<N>>,
+    /// Progress of the running script, moving a stick cancels it.
+    script_run: Option<ScriptRun>,
+    /// Modal state of the G-code lines, see [`ArmBot::run_gcode`].
+    gcode: Interpreter<N>,
+    /// Last stick step was undone at the workspace edge, so it's reported once.
+    workspace_hit: bool,
+    /// Gripper is kept level, see [`ArmBot::set_level_hold`].
+    level_hold: bool,
+    /// Last pitch of the gripper from the IMU.
+    pitch: Option<Degrees>,
+}
+
+impl<G: Gamepad, D: ServoDriver, const N: usize> ArmBot<G, D, N> {
+    /// Creates the arm, servos go in the same order as joints in the config.
+    /// Angle ranges of the joints become soft limits of their servos,
+    /// motion profiles of the joints are applied to their servos.
+    pub fn new(config: ArmBotConfig<N>, gamepad: G, mut servos: [D; N]) -> Result<Self, Error> {
+        if let Some(kinematics) = &config.kinematics {
+            kinematics.validate()?;
+        }
+        if let Some(workspace) = &config.workspace {
+            if config.kinematics.is_none() {
+                return Err(Error::Other("workspace limits need kinematics"));
+            }
+            workspace.validate()?;
+        }
+        if let Some(level_hold) = &config.level_hold {
+            level_hold.validate(N)?;
+        }
+        for profile in config.joints.iter().filter_map(|joint| joint.profile) {
+            profile.validate().map_err(Error::servo)?;
+        }
+        for rate_limit in config.joints.iter().filter_map(|joint| joint.rate_limit) {
+            rate_limit.validate()?;
+        }
+        for mapping in &config.axis_map {
+            mapping.validate()?;
+        }
+        if !(config.cartesian_step.is_finite() && config.cartesian_step > 0.0) {
+            return Err(Error::OutOfRange("cartesian step must be positive"));
+        }
+        if !(config.stick_accel.is_finite() && config.stick_accel > 0.0) {
+            return Err(Error::OutOfRange("stick acceleration must be positive"));
+        }
+        // fails early on a scale that doesn't fit, so switching modes can't fail later
+        for scale in config.speed_scales {
+            scaled_output(&config, scale)?;
+        }
+        let speed_mode = SpeedMode::default();
+        let scale = config.speed_scales[speed_mode as usize];
+        let step_output = scaled_output(&config, scale)?;
+        let step_secs = crate::CONTROL_PERIOD.as_secs_f32();
+        let speed_scale = SlewLimiter::symmetric(SPEED_SCALE_RATE * step_secs, scale);
+        // steps are hundredths of a degree per step, they grow by the accel every step
+        let stick_accel = config.stick_accel * step_secs * step_secs * 100.0;
+        let stick_ramps = core::array::from_fn(|_| SlewLimiter::symmetric(stick_accel, 0.0));
+        for (servo, joint) in servos.iter_mut().zip(&config.joints) {
+            let range = &joint.angle_range;
+            servo
+                .set_limits(range.start.get() as f64, range.end.get() as f64)
+                .map_err(Error::servo)?;
+            servo.set_profile(joint.profile).map_err(Error::servo)?;
+        }
+
+        // a profiled joint may still be slowing down after the sticks were centered
+        let settle_steps = config
+            .joints
+            .iter()
+            .filter_map(|joint| joint.profile)
+            .map(|profile| {
+                // the cast saturates, a slow accel mustn't overflow the extra step
+                ((profile.max_speed / profile.accel / profile.step_period) as u32).saturating_add(1)
+            })
+            .max()
+            .unwrap_or(0);
+        let period = crate::CONTROL_PERIOD.as_micros();
+        let failsafe_steps = config.failsafe.timeout.as_micros().div_ceil(period).max(1) as u32;
+
+        let trajectory = Trajectory::new(config.interpolation);
+        // the position is taken from the arm at the first line
+        let gcode = Interpreter::new(config.gcode.clone(), [Degrees::ZERO; N]);
+        let mut idx = 0;
+        let joints = servos.map(|servo| {
+            let joint = Joint::new(&config.joints[idx], servo);
+            idx += 1;
+            joint
+        });
+
+        Ok(Self {
+            config,
+            step_output,
+            speed_mode,
+            speed_scale,
+            stick_ramps,
+            teleop_mode: TeleopMode::default(),
+            joints,
+            gamepad,
+            state: State::default(),
+            idle_steps: 0,
+            settle_steps,
+            stopped: false,
+            stop_held: false,
+            lost_input_steps: 0,
+            failsafe_steps,
+            failsafe: false,
+            pose_move: None,
+            trajectory,
+            script: None,
+            script_run: None,
+            gcode,
+            workspace_hit: false,
+            level_hold: false,
+            pitch: None,
+            base: NoBase,
+        })
+    }
+
+    /// Drives the base rotator axis with the base joint, e.g. a stepper.
+    pub fn with_base<B: BaseJoint>(self, base: B) -> ArmBot<G, D, N, B> {
+        ArmBot {
+            config: self.config,
+            step_output: self.step_output,
+            speed_mode: self.speed_mode,
+            speed_scale: self.speed_scale,
+            stick_ramps: self.stick_ramps,
+            teleop_mode: self.teleop_mode,
+            base,
+            joints: self.joints,
+            gamepad: self.gamepad,
+            state: self.state,
+            idle_steps: self.idle_steps,
+            settle_steps: self.settle_steps,
+            stopped: self.stopped,
+            stop_held: self.stop_held,
+            lost_input_steps: self.lost_input_steps,
+            failsafe_steps: self.failsafe_steps,
+            failsafe: self.failsafe,
+            pose_move: self.pose_move,
+            trajectory: self.trajectory,
+            script: self.script,
+            script_run: self.script_run,
+            gcode: self.gcode,
+            workspace_hit: self.workspace_hit,
+            level_hold: self.level_hold,
+            pitch: self.pitch,
+        }
+    }
+}
+
+impl<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint> ArmBot<G, D, N, B> {
+    /// Makes the arm bot do a cycle of its movement. Joints with a [`RateLimit`] are held to
+    /// it whatever moves them: sticks, poses, queued moves, scripts or commands.
+    ///
+    /// A step without gamepad input, a failed read or a stale wireless gamepad, holds the arm.
+    /// Once the input is lost for the failsafe timeout the sticks are taken as centered, so
+    /// the arm comes to rest and plays back as with the sticks released, see [`FailsafeConfig`].
+    pub fn do_step(&mut self) -> Result<(), Report> {
+        self.ramp_speed_scale();
+        // a stale read is kept out, it must not drive the arm once the failsafe centered it
+        let mut state = self.state.clone();
+        let polled = self
+            .gamepad
+            .poll_events(&self.step_output, &mut state)
+            .context("reading gamepad");
+        let events = match polled {
+            Ok(events) if !self.gamepad.is_stale() => {
+                self.state = state;
+                self.input_restored();
+                events
+            }
+            Ok(_) => self.input_lost(Error::Other("gamepad sends no input").into())?,
+            Err(err) => self.input_lost(err)?,
+        };
+        #[cfg(feature = "defmt")]
+        defmt::trace!("gamepad: {}", self.state);
+        self.handle_events(&events)?;
+        let sticks = self.ramp_sticks();
+        if self.stopped {
+            return Ok(());
+        }
+        let result = self.step_joints(&sticks, events.is_empty());
+        result
+            .and(self.hold_level().context("level hold"))
+            .and(self.limit_rates())
+    }
+
+    /// Counts a step without gamepad input, engages the failsafe after the timeout.
+    /// Returns the error while the arm is held, no events once the sticks are centered.
+    fn input_lost(&mut self, err: Report) -> Result<Events, Report> {
+        if !self.failsafe {
+            self.lost_input_steps += 1;
+            if self.lost_input_steps < self.failsafe_steps {
+                return Err(err);
+            }
+            error!("gamepad input lost: {err}, sticks are centered");
+            self.failsafe = true;
+            self.state = State::default();
+            if self.config.failsafe.detach && !self.stopped {
+                // detaching is the point, a failure was already logged for every joint
+                let _ = self.run_action(Action::EmergencyStop);
+            }
+        }
+        Ok(Events::default())
+    }
+
+    fn input_restored(&mut self) {
+        self.lost_input_steps = 0;
+        if self.failsafe {
+            info!("gamepad input is back");
+            self.failsafe = false;
+        }
+    }
+
+    /// Advances the script, the pose move or the queued moves, or moves the joints with
+    /// the sticks.
+    fn step_joints(&mut self, sticks: &[Position; AXES], no_events: bool) -> Result<(), Report> {
+        if self.script_run.is_some() {
+            if !self.state.is_center() {
+                info!("sticks moved, script cancelled");
+                self.script_run = None;
+            } else if self.pose_move.is_none()
+                && self.trajectory.is_idle()
+                && !self.joints.iter().any(|joint| joint.goal.is_some())
+            {
+                self.step_script().context("script")?;
+            }
+        }
+        if let Some(pose_move) = &self.pose_move {
+            if self.state.is_center() {
+                let base_result = self.base.make_step(&Position::Center).context("base");
+                return self.step_pose_move().and(base_result);
+            }
+            info!(
+                "sticks moved, {} pose move cancelled",
+                pose_move.pose.name()
+            );
+            self.pose_move = None;
+        }
+        if !self.trajectory.is_idle() {
+            if self.state.is_center() {
+                let base_result = self.base.make_step(&Position::Center).context("base");
+                return self.step_trajectory().and(base_result);
+            }
+            info!("sticks moved, trajectory cancelled");
+            self.trajectory.clear();
+        }
+
+        // held sticks keep moving the arm, only the rest is skipped
+        if no_events && self.state.is_center() && !self.base.is_moving() {
+            if self.idle_steps >= self.settle_steps {
+                return Ok(());
+            }
+            self.idle_steps += 1;
+        } else {
+            self.idle_steps = 0;
+        }
+
+        if self.teleop_mode == TeleopMode::Cartesian {
+            return self.step_cartesian(sticks);
+        }
+
+        // a failed joint must not prevent the rest of the arm from moving
+        let mut result = Ok(());
+        let before = self.joint_angles();
+        for joint in self.joints.iter_mut() {
+            let cmd = &sticks[joint.axis as usize];
+            let joint_result = joint
+                .make_step(cmd, self.config.max_joint_errors)
+                .context(joint.name);
+            result = result.and(joint_result);
+        }
+        result = result.and(self.keep_in_workspace(&before));
+        let base_result = self
+            .base
+            .make_step(&sticks[Axis::BaseRotator as usize])
+            .context("base");
+
+        result.and(base_result)
+    }
+
+    /// Undoes the stick step if it took the gripper out of the workspace, which also stops
+    /// profiled joints like a joint limit does. An arm already outside can move anywhere.
+    fn keep_in_workspace(&mut self, before: &[Degrees; N]) -> Result<(), Report> {
+        let Err(err) = self.config.check_workspace(&self.joint_angles()) else {
+            self.workspace_hit = false;
+            return Ok(());
+        };
+        if self.config.check_workspace(before).is_err() {
+            return Ok(());
+        }
+        if !self.workspace_hit {
+            info!("arm reached the workspace edge: {err}");
+        }
+        self.workspace_hit = true;
+
+        let mut result = Ok(());
+        for (joint, angle) in self.joints.iter_mut().zip(before) {
+            // undone right away, the step back is as fast as the step was
+            let joint_result = joint.servo.set_angle(angle.get() as f64);
+            result = result.and(joint_result.map_err(Error::servo).context(joint.name));
+        }
+        result
+    }
+
+    /// Runs the next step of the script once the previous move is done.
+    /// The script is cancelled when a step fails.
+    fn step_script(&mut self) -> Result<(), Report> {
+        let (Some(script), Some(run)) = (&self.script, &mut self.script_run) else {
+            return Ok(());
+        };
+        if run.waiting() {
+            return Ok(());
+        }
+        let step = match run.next(script) {
+            Some(Step::Wait(duration)) => {
+                run.wait(duration);
+                return Ok(());
+            }
+            Some(step) => step,
+            None => {
+                info!("script finished");
+                self.script_run = None;
+                return Ok(());
+            }
+        };
+        let result = match step {
+            Step::Pose(pose) => self.goto_pose(pose),
+            Step::Move { targets, duration } => self.queue_move(targets, duration),
+            Step::Action(action) => self.run_action(action),
+            // run by the script run
+            Step::Wait(_) | Step::Repeat(_) | Step::End => Ok(()),
+        };
+        if result.is_err() {
+            warn!("script cancelled");
+            self.script_run = None;
+        }
+        result
+    }
+
+    /// Turns the level joint against the pitch of the gripper, see [`ArmBot::set_pitch`].
+    /// Pauses during pose and queued moves and while the stick of the joint is moved.
+    fn hold_level(&mut self) -> Result<(), Error> {
+        let (Some(config), true, Some(pitch)) =
+            (&self.config.level_hold, self.level_hold, self.pitch)
+        else {
+            return Ok(());
+        };
+        if self.pose_move.is_some() || !self.trajectory.is_idle() {
+            return Ok(());
+        }
+        let range = &self.config.joints[config.joint].angle_range;
+        let stick = self.stick(self.joints[config.joint].axis);
+        let joint = &mut self.joints[config.joint];
+        if stick != Position::Center || pitch.get().abs() < config.tolerance.get() {
+            return Ok(());
+        }
+        let max_step = config.max_step.get();
+        let step = (-pitch.get() * config.gain).clamp(-max_step, max_step);
+        let angle = (joint.angle().get() + step).clamp(range.start.get(), range.end.get());
+        joint.follow(Degrees::new(angle))
+    }
+
+    /// Moves the joints towards their goals and holds back steps faster than their rate limits.
+    fn limit_rates(&mut self) -> Result<(), Report> {
+        let dt = crate::CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0;
+        let mut result = Ok(());
+        for joint in self.joints.iter_mut() {
+            result = result.and(joint.limit_rate(dt).context(joint.name));
+        }
+        result
+    }
+
+    /// Runs the actions of the pressed buttons.
+    fn handle_events(&mut self, events: &Events) -> Result<(), Report> {
+        let mut result = Ok(());
+        for event in events.iter() {
+            debug!("gamepad event: {event:?}");
+            if let Event::Pressed(button) = event {
+                if let Some(action) = self.config.buttons[*button as usize] {
+                    info!("{button:?} pressed: {action:?}");
+                    result = result.and(self.run_action(action));
+                }
+            }
+        }
+        result
+    }
+
+    /// Runs the action right away, joints jump to their targets without a ramp,
+    /// except [`Action::Home`] that starts a pose move. Queued moves are dropped.
+    /// Only [`Action::EmergencyStop`] runs while the arm is stopped.
+    pub fn run_action(&mut self, action: Action) -> Result<(), Report> {
+        if action == Action::CycleSpeed {
+            self.set_speed_mode(self.speed_mode.next());
+            return Ok(());
+        }
+        if action == Action::ToggleTeleop {
+            let mode = match self.teleop_mode {
+                TeleopMode::Joint => TeleopMode::Cartesian,
+                TeleopMode::Cartesian => TeleopMode::Joint,
+            };
+            return self.set_teleop_mode(mode).map_err(Into::into);
+        }
+        if self.stopped && action != Action::EmergencyStop {
+            warn!("arm is stopped, ignoring {action:?}");
+            return Ok(());
+        }
+        if action == Action::Home {
+            return self.goto_pose(PoseName::Home);
+        }
+        self.pose_move = None;
+        self.trajectory.clear();
+
+        let mut result = Ok(());
+        for (joint, config) in self.joints.iter_mut().zip(&self.config.joints) {
+            let target = match action {
+                Action::JointToMin(axis) if axis == joint.axis => config.angle_range.start,
+                Action::JointToMax(axis) if axis == joint.axis => config.angle_range.end,
+                Action::EmergencyStop => {
+                    if let Err(err) = joint.servo.disable().map_err(Error::servo) {
+                        error!("{} joint can't be disabled: {err}", joint.name);
+                    }
+                    joint.hold();
+                    continue;
+                }
+                _ => continue,
+            };
+            result = result.and(joint.move_to(target).context(joint.name));
+        }
+        if action == Action::EmergencyStop {
+            self.script_run = None;
+            self.base.disable();
+            error!("emergency stop, servos are detached");
+            self.stopped = true;
+        }
+        result
+    }
+
+    /// Starts moving all joints to the pose, they arrive together after the slowest one
+    /// moved at [`ArmBotConfig::pose_speed`]. Following steps advance the move until a stick
+    /// is moved. Nothing moves if the pose has no angles, any of them is out of its range or
+    /// the pose is out of the workspace. Queued moves are dropped.
+    pub fn goto_pose(&mut self, pose: PoseName) -> Result<(), Report> {
+        if self.stopped {
+            warn!("arm is stopped, ignoring {} pose", pose.name());
+            return Ok(());
+        }
+        let target = match (&self.config.poses[pose as usize], pose) {
+            (Some(angles), _) => angles.0,
+            (None, PoseName::Home) => core::array::from_fn(|idx| self.config.joints[idx].home),
+            (None, _) => return Err(Error::Other("pose has no angles").into()),
+        };
+        for (angle, config) in target.iter().zip(&self.config.joints) {
+            let range = &config.angle_range;
+            if *angle < range.start || *angle > range.end {
+                warn!("{} joint can't reach {angle}", config.name);
+                return Err(Error::OutOfRange("pose angle is out of a joint range").into());
+            }
+        }
+        self.config.check_workspace(&target)?;
+        self.trajectory.clear();
+
+        let start = self.joint_angles();
+        let distance = start
+            .iter()
+            .zip(&target)
+            .map(|(start, target)| (*target - *start).get().abs())
+            .fold(0.0, f32::max);
+        let step_period = crate::CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0;
+        let speed = self.config.pose_speed.max(f32::EPSILON);
+        let steps = libm::ceilf(distance / speed / step_period).max(1.0) as u32;
+        info!("moving to {} pose in {steps} steps", pose.name());
+        self.pose_move = Some(PoseMove {
+            pose,
+            start,
+            target,
+            steps,
+            done: 0,
+        });
+        Ok(())
+    }
+
+    /// Advances the pose move by a step.
+    /// The move is cancelled if the path between the poses leaves the workspace.
+    fn step_pose_move(&mut self) -> Result<(), Report> {
+        let Some(pose_move) = self.pose_move.as_mut() else {
+            return Ok(());
+        };
+        pose_move.done += 1;
+        let t = pose_move.done as f32 / pose_move.steps as f32;
+        let angles: [Degrees; N] = core::array::from_fn(|idx| {
+            let (start, target) = (pose_move.start[idx].get(), pose_move.target[idx].get());
+            Degrees::new(self.config.pose_easing.interpolate(start, target, t))
+        });
+        if let Err(err) = self.config.check_workspace(&angles) {
+            warn!(
+                "{} pose move cancelled at the workspace edge",
+                pose_move.pose.name()
+            );
+            self.pose_move = None;
+            return Err(err.into());
+        }
+
+        let mut result = Ok(());
+        for (joint, angle) in self.joints.iter_mut().zip(angles) {
+            result = result.and(joint.follow(angle).context(joint.name));
+        }
+        if pose_move.done >= pose_move.steps {
+            info!("reached {} pose", pose_move.pose.name());
+            self.pose_move = None;
+        }
+        result
+    }
+
+    /// Queues a move of all joints to the targets in the duration, it starts after the queued
+    /// ones and plays while the sticks are centered, see [`crate::trajectory`].
+    /// Fails if the queue is full or any target is out of its joint range or the workspace.
+    pub fn queue_move(&mut self, targets: [Degrees; N], duration: Duration) -> Result<(), Report> {
+        if self.stopped {
+            warn!("arm is stopped, ignoring queued move");
+            return Ok(());
+        }
+        for (angle, config) in targets.iter().zip(&self.config.joints) {
+            let range = &config.angle_range;
+            if *angle < range.start || *angle > range.end {
+                warn!("{} joint can't reach {angle}", config.name);
+                return Err(Error::OutOfRange("target is out of a joint range").into());
+            }
+        }
+        self.config.check_workspace(&targets)?;
+        self.pose_move = None;
+        self.trajectory.push(Segment::new(targets, duration))?;
+        Ok(())
+    }
+
+    /// Runs a G-code line, see [`crate::interpreter`]. Moves and dwells are queued like
+    /// [`ArmBot::queue_move`], M codes run right away. The modal state is kept between lines.
+    pub fn run_gcode(&mut self, line: &str) -> Result<(), Report> {
+        // moves start where the queued ones end, or where the sticks left the arm
+        if self.trajectory.is_idle() {
+            self.gcode.set_position(self.joint_angles());
+        }
+        let before = self.gcode.clone();
+        let result = match self.gcode.execute(line)? {
+            None => Ok(()),
+            Some(Motion::Move { targets, duration }) => self.queue_move(targets, duration),
+            Some(Motion::Dwell(duration)) => self.queue_move(*self.gcode.position(), duration),
+            Some(Motion::Action(action)) => self.run_action(action),
+        };
+        // a rejected move doesn't count as the end of the last one
+        if result.is_err() {
+            self.gcode = before;
+        }
+        result
+    }
+
+    /// Advances the trajectory by a step, it's dropped if it leaves the workspace.
+    fn step_trajectory(&mut self) -> Result<(), Report> {
+        let Some(angles) = self.trajectory.next_angles(&self.joint_angles()) else {
+            return Ok(());
+        };
+        if let Err(err) = self.config.check_workspace(&angles) {
+            warn!("trajectory cancelled at the workspace edge");
+            self.trajectory.clear();
+            return Err(err.into());
+        }
+
+        let mut result = Ok(());
+        for ((joint, angle), config) in self.joints.iter_mut().zip(angles).zip(&self.config.joints)
+        {
+            // a smooth path may overshoot a target at the end of a range a bit
+            let range = &config.angle_range;
+            let angle = Degrees::new(angle.get().clamp(range.start.get(), range.end.get()));
+            result = result.and(joint.follow(angle).context(joint.name));
+        }
+        result
+    }
+
+    /// Moves the joint to the angle right away, the index is in the joint order of the config.
+    /// Nothing moves if the gripper would leave the workspace.
+    pub fn move_joint(&mut self, joint: usize, angle: Degrees) -> Result<(), Report> {
+        if self.stopped {
+            warn!("arm is stopped, ignoring move of joint {joint}");
+            return Ok(());
+        }
+        self.pose_move = None;
+        self.trajectory.clear();
+        let mut angles = self.joint_angles();
+        *angles
+            .get_mut(joint)
+            .ok_or(Error::OutOfRange("no such joint"))? = angle;
+        self.config.check_workspace(&angles)?;
+        let joint = self
+            .joints
+            .get_mut(joint)
+            .ok_or(Error::OutOfRange("no such joint"))?;
+        joint.move_to(angle).context(joint.name)
+    }
+
+    /// Moves the gripper to the point in millimeters from the shoulder axis right away,
+    /// see [`crate::kinematics`]. Nothing moves if any joint can't reach its angle or the point
+    /// is out of the workspace.
+    /// Without a base joint the point must lie in front of the arm, with `y` of zero.
+    pub fn move_to_xyz(&mut self, x: f32, y: f32, z: f32) -> Result<(), Report> {
+        if self.stopped {
+            warn!("arm is stopped, ignoring move to ({x}, {y}, {z})");
+            return Ok(());
+        }
+        self.pose_move = None;
+        self.trajectory.clear();
+        let angles = self.chain_angles(Point::new(x, y, z))?;
+        self.move_chain(&angles, true)
+    }
+
+    /// Angles of the chain joints that put the gripper at the point, fails if any joint can't
+    /// reach its angle or the point is out of the workspace.
+    fn chain_angles(&self, point: Point) -> Result<ChainAngles, Report> {
+        let kinematics = self
+            .config
+            .kinematics
+            .as_ref()
+            .ok_or(Error::Other("kinematics aren't configured"))?;
+        if let Some(workspace) = &self.config.workspace {
+            workspace.check(point)?;
+        }
+        let angles = kinematics.inverse(point).context("inverse kinematics")?;
+
+        let has_joint = |axis| self.config.joint_of(axis).is_some();
+        if !has_joint(Axis::Shoulder) || !has_joint(Axis::Elbow) {
+            return Err(Error::Other("kinematics need shoulder and elbow joints").into());
+        }
+        if !has_joint(Axis::BaseRotator) && (point.y.abs() > XYZ_PLANE_TOLERANCE || point.x < 0.0) {
+            return Err(
+                Error::OutOfRange("point is off the plane of an arm without a base").into(),
+            );
+        }
+
+        for config in &self.config.joints {
+            let Some(angle) = chain_target(&angles, config.axis) else {
+                continue;
+            };
+            let range = &config.angle_range;
+            if angle < range.start || angle > range.end {
+                warn!("{} joint can't reach {angle}", config.name);
+                return Err(Error::OutOfRange("point needs an angle out of a joint range").into());
+            }
+        }
+        Ok(angles)
+    }
+
+    /// Moves the chain joints to the angles right away, `jump` if the angles are far away
+    /// rather than the next step of a path.
+    fn move_chain(&mut self, angles: &ChainAngles, jump: bool) -> Result<(), Report> {
+        let mut result = Ok(());
+        for joint in self.joints.iter_mut() {
+            if let Some(angle) = chain_target(angles, joint.axis) {
+                let joint_result = if jump {
+                    joint.move_to(angle)
+                } else {
+                    joint.follow(angle)
+                };
+                result = result.and(joint_result.context(joint.name));
+            }
+        }
+        result
+    }
+
+    /// Moves the gripper in straight lines by [`ArmBotConfig::cartesian_step`]: the shoulder
+    /// stick forward and back, the elbow stick up and down and the base stick sideways.
+    /// Other joints, e.g. the gripper, and a base that isn't a servo follow their sticks.
+    fn step_cartesian(&mut self, sticks: &[Position; AXES]) -> Result<(), Report> {
+        let kinematics = self
+            .config
+            .kinematics
+            .as_ref()
+            .ok_or(Error::Other("kinematics aren't configured"))?;
+        let (Some(shoulder), Some(elbow)) = (
+            self.config.joint_of(Axis::Shoulder),
+            self.config.joint_of(Axis::Elbow),
+        ) else {
+            return Err(Error::Other("kinematics need shoulder and elbow joints").into());
+        };
+        let base = self.config.joint_of(Axis::BaseRotator);
+        let angles = self.joint_angles();
+        let point = kinematics.forward(&ChainAngles {
+            base: base.map_or(kinematics.base.zero, |idx| angles[idx]),
+            shoulder: angles[shoulder],
+            elbow: angles[elbow],
+        });
+        // steps are in hundredths of a degree
+        let delta = |axis: Axis| {
+            let step = match &sticks[axis as usize] {
+                Position::Low(step) => -(*step as f32),
+                Position::Center => 0.0,
+                Position::High(step) => *step as f32,
+            };
+            step / 100.0 * self.config.cartesian_step
+        };
+        let sideways = if base.is_some() {
+            delta(Axis::BaseRotator)
+        } else {
+            0.0
+        };
+        let target = Point::new(
+            point.x + delta(Axis::Shoulder),
+            point.y + sideways,
+            point.z + delta(Axis::Elbow),
+        );
+
+        let mut result = Ok(());
+        if target != point {
+            match self.chain_angles(target) {
+                Ok(angles) => {
+                    self.workspace_hit = false;
+                    result = self.move_chain(&angles, false);
+                }
+                Err(err) => {
+                    if !self.workspace_hit {
+                        info!("gripper can't move further: {err}");
+                    }
+                    self.workspace_hit = true;
+                }
+            }
+        }
+        for joint in self.joints.iter_mut() {
+            if matches!(joint.axis, Axis::BaseRotator | Axis::Shoulder | Axis::Elbow) {
+                continue;
+            }
+            let joint_result = joint
+                .make_step(&sticks[joint.axis as usize], self.config.max_joint_errors)
+                .context(joint.name);
+            result = result.and(joint_result);
+        }
+        let base_cmd = match base {
+            Some(_) => Position::Center,
+            None => sticks[Axis::BaseRotator as usize].clone(),
+        };
+        let base_result = self.base.make_step(&base_cmd).context("base");
+
+        result.and(base_result)
+    }
+
+    /// Scales the step size range of the sticks, see [`ArmBotConfig::speed_scales`].
+    /// The range gets there over a few steps, so the arm doesn't jerk on a switch.
+    pub fn set_speed_mode(&mut self, mode: SpeedMode) {
+        self.speed_mode = mode;
+        info!("{} speed", mode.name());
+    }
+
+    /// Moves the step size range toward the scale of the speed mode.
+    fn ramp_speed_scale(&mut self) {
+        let target = self.config.speed_scales[self.speed_mode as usize];
+        if self.speed_scale.is_settled(target) {
+            return;
+        }
+        let scale = self.speed_scale.update(target);
+        // a scale between two checked ones fits as well
+        if let Ok(output) = scaled_output(&self.config, scale) {
+            self.step_output = output;
+        }
+    }
+
+    /// Mapped sticks with their steps ramped up, easing or releasing a stick takes effect
+    /// right away.
+    fn ramp_sticks(&mut self) -> [Position; AXES] {
+        let mut sticks = Axis::ALL.map(|axis| self.stick(axis));
+        for (stick, ramp) in sticks.iter_mut().zip(&mut self.stick_ramps) {
+            let target = match stick {
+                Position::Low(step) => -(*step as f32),
+                Position::Center => 0.0,
+                Position::High(step) => *step as f32,
+            };
+            let current = ramp.value();
+            if target * current < 0.0 {
+                ramp.reset(0.0);
+            } else if target.abs() < current.abs() {
+                ramp.reset(target);
+            }
+            let step = ramp.update(target);
+            *stick = if step >= 1.0 {
+                Position::High(step as u32)
+            } else if step <= -1.0 {
+                Position::Low(-step as u32)
+            } else {
+                Position::Center
+            };
+        }
+        sticks
+    }
+
+    pub fn speed_mode(&self) -> SpeedMode {
+        self.speed_mode
+    }
+
+    /// Switches what the sticks move, see [`TeleopMode`]. Cartesian teleop fails without
+    /// kinematics or without shoulder and elbow joints.
+    pub fn set_teleop_mode(&mut self, mode: TeleopMode) -> Result<(), Error> {
+        if mode == TeleopMode::Cartesian {
+            if self.config.kinematics.is_none() {
+                return Err(Error::Other("kinematics aren't configured"));
+            }
+            if self.config.joint_of(Axis::Shoulder).is_none()
+                || self.config.joint_of(Axis::Elbow).is_none()
+            {
+                return Err(Error::Other("kinematics need shoulder and elbow joints"));
+            }
+        }
+        if mode != self.teleop_mode {
+            info!("{} teleop", mode.name());
+        }
+        self.teleop_mode = mode;
+        Ok(())
+    }
+
+    pub fn teleop_mode(&self) -> TeleopMode {
+        self.teleop_mode
+    }
+
+    /// Feeds the pitch of the gripper measured by an IMU on the forearm. Zero is level,
+    /// positive points the gripper up. `None` pauses the level hold, e.g. when the IMU fails.
+    pub fn set_pitch(&mut self, pitch: Option<Degrees>) {
+        self.pitch = pitch;
+    }
+
+    /// Keeps the gripper level with the joint of [`ArmBotConfig::level_hold`] while the
+    /// shoulder and the elbow move. Fails if the config has no level joint.
+    pub fn set_level_hold(&mut self, on: bool) -> Result<(), Error> {
+        if on && self.config.level_hold.is_none() {
+            return Err(Error::Other("level hold isn't configured"));
+        }
+        if on != self.level_hold {
+            info!("level hold {}", if on { "on" } else { "off" });
+        }
+        self.level_hold = on;
+        Ok(())
+    }
+
+    pub fn level_hold(&self) -> bool {
+        self.level_hold
+    }
+
+    pub fn config(&self) -> &ArmBotConfig<N> {
+        &self.config
+    }
+
+    /// Returns the commanded angles of the joints, in the joint order of the config.
+    /// The angles are kept by the servos only, so they're never stale, e.g. for telemetry,
+    /// kinematics and recording.
+    pub fn joint_angles(&self) -> [Degrees; N] {
+        core::array::from_fn(|idx| self.joints[idx].angle())
+    }
+
+    /// Returns the commanded pulse widths of the servos in microseconds, if they have pulses.
+    pub fn pulse_widths(&self) -> [Option<u32>; N] {
+        core::array::from_fn(|idx| self.joints[idx].servo.pulse_width_us())
+    }
+
+    /// Gamepad input of the last step.
+    pub fn gamepad_state(&self) -> &State {
+        &self.state
+    }
+
+    /// Stick command of the joints of the axis in the last step, through the axis map.
+    pub fn stick(&self, axis: Axis) -> Position {
+        let mapping = &self.config.axis_map[axis as usize];
+        let Some(input) = mapping.input else {
+            return Position::Center;
+        };
+        let cmd = self.state.axis(input).scaled(mapping.scale);
+        if mapping.invert {
+            cmd.reversed()
+        } else {
+            cmd
+        }
+    }
+
+    /// Feeds the state of the stop switch, should be called before every step.
+    /// Tripping the switch stops the arm, the stop stays latched after the switch is reset.
+    pub fn update_stop_switch(&mut self, tripped: bool) {
+        if tripped == self.stop_held {
+            return;
+        }
+        self.stop_held = tripped;
+        if !tripped {
+            info!("stop switch reset, release the stop to resume");
+            return;
+        }
+        error!("stop switch tripped");
+        if !self.stopped {
+            // detaching is the point, a failure was already logged for every joint
+            let _ = self.run_action(Action::EmergencyStop);
+        }
+    }
+
+    /// Re-attaches the servos after an emergency stop and moves the arm home, so it resumes
+    /// from a known pose. Fails while the stop switch is tripped.
+    pub fn release_stop(&mut self) -> Result<(), Report> {
+        if self.stop_held {
+            return Err(Error::Other("stop switch is still tripped").into());
+        }
+        if !self.stopped {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for joint in self.joints.iter_mut() {
+            joint.hold();
+            let joint_result = joint.servo.enable().map_err(Error::servo);
+            result = result.and(joint_result.context(joint.name));
+        }
+        self.base.enable();
+        self.stopped = false;
+        info!("emergency stop released, moving home");
+        result.and(self.goto_pose(PoseName::Home))
+    }
+
+    /// Returns true while the sticks move the arm, a pose move or queued moves run or
+    /// profiled joints are still slowing down.
+    pub fn is_moving(&self) -> bool {
+        !self.state.is_center()
+            || self.is_playing_back()
+            || self.base.is_moving()
+            || self.idle_steps < self.settle_steps
+            || self.joints.iter().any(|joint| joint.goal.is_some())
+    }
+
+    /// Returns true while a stick pushes a joint against its limit or the arm against the
+    /// workspace edge.
+    pub fn at_limit(&self) -> bool {
+        !self.state.is_center()
+            && (self.workspace_hit || self.joints.iter().any(|joint| joint.last_step.is_clamped()))
+    }
+
+    /// Returns true while a script, a pose move or queued moves run.
+    pub fn is_playing_back(&self) -> bool {
+        self.script_run.is_some() || self.pose_move.is_some() || !self.trajectory.is_idle()
+    }
+
+    /// Sets the script started by [`ArmBot::run_script`], a running script is stopped.
+    pub fn load_script(&mut self, script: Script<N>) {
+        self.script_run = None;
+        self.script = Some(script);
+    }
+
+    /// Starts the script from its first step, see [`crate::script`]. It runs while the sticks
+    /// are centered, moving a stick or the emergency stop cancels it.
+    pub fn run_script(&mut self) -> Result<(), Report> {
+        if self.stopped {
+            return Err(Error::Other("arm is stopped").into());
+        }
+        if self.script.is_none() {
+            return Err(Error::Other("no script loaded").into());
+        }
+        info!("running script");
+        self.script_run = Some(ScriptRun::default());
+        Ok(())
+    }
+
+    /// Stops the script and its moves, the arm stays where it is.
+    pub fn stop_script(&mut self) {
+        if self.script_run.take().is_some() {
+            info!("script stopped");
+            self.pose_move = None;
+            self.trajectory.clear();
+        }
+    }
+
+    /// Returns true after an emergency stop.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Returns true while the gamepad input is lost and the sticks are taken as centered.
+    pub fn is_failsafe(&self) -> bool {
+        self.failsafe
+    }
+
+    /// Returns true if any joint is faulted and doesn't move anymore.
+    pub fn has_faults(&self) -> bool {
+        self.joints.iter().any(|joint| joint.health.faulted)
+    }
+
+    /// Clears faults and stalls of all joints, so they will be commanded again.
+    pub fn reset_faults(&mut self) {
+        for joint in self.joints.iter_mut() {
+            joint.health.reset();
+        }
+    }
+
+    /// Stops the joint from moving further the way it moved last, e.g. when its servo draws
+    /// too much current. Moving the joint back clears the stall.
+    /// Pose and queued moves are cancelled, `back_off` moves the joint back by that much to
+    /// release the load.
+    pub fn report_stall(&mut self, joint: usize, back_off: Option<Degrees>) -> Result<(), Report> {
+        self.pose_move = None;
+        self.trajectory.clear();
+        let config = self
+            .config
+            .joints
+            .get(joint)
+            .ok_or(Error::OutOfRange("no such joint"))?;
+        let joint = &mut self.joints[joint];
+        let Some(dir) = joint.last_dir else {
+            return Err(Error::Stalled(joint.name).into());
+        };
+        if joint.health.stalled.is_none() {
+            error!("{} joint stalled moving {dir:?}", joint.name);
+            joint.health.counters.stalls += 1;
+        }
+        joint.health.stalled = Some(dir);
+        if let Some(back_off) = back_off {
+            // straight to the servo, moving back through move_to would clear the stall
+            let angle = joint.angle().get();
+            let angle = match dir {
+                Dir::CW => angle + back_off.get(),
+                Dir::CCW => angle - back_off.get(),
+            };
+            let range = &config.angle_range;
+            let angle = angle.clamp(range.start.get(), range.end.get());
+            let result = joint.servo.set_angle(angle as f64).map_err(Error::servo);
+            result.context(joint.name)?;
+        }
+        Err(Error::Stalled(joint.name).into())
+    }
+
+    /// Feeds the end stop switches at the min and max ends of the joint. A pressed switch
+    /// keeps the joint from moving towards it, pose and queued moves are cancelled when it's hit.
+    pub fn update_end_stops(&mut self, joint: usize, min: bool, max: bool) -> Result<(), Error> {
+        let joint = self
+            .joints
+            .get_mut(joint)
+            .ok_or(Error::OutOfRange("no such joint"))?;
+        let [was_min, was_max] = joint.end_stops;
+        if (min && !was_min) || (max && !was_max) {
+            info!("{} joint hit its end stop", joint.name);
+            self.pose_move = None;
+            self.trajectory.clear();
+        }
+        joint.end_stops = [min, max];
+        Ok(())
+    }
+
+    /// Gives access to the gamepad, e.g. to calibrate it while the arm holds still.
+    pub fn gamepad_mut(&mut self) -> &mut G {
+        &mut self.gamepad
+    }
+
+    /// Returns runtime counters of all joints.
+    pub fn counters(&self) -> [JointCounters; N] {
+        core::array::from_fn(|idx| self.joints[idx].health.counters)
+    }
+}
+
+impl<const N: usize> ArmBotConfig<N> {
+    /// Index of the first joint of the axis.
+    fn joint_of(&self, axis: Axis) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.axis == axis)
+    }
+
+    /// Fails if the gripper is out of the workspace at the joint angles.
+    /// Passes without workspace limits or without shoulder and elbow joints.
+    pub fn check_workspace(&self, angles: &[Degrees; N]) -> Result<(), Error> {
+        let (Some(kinematics), Some(workspace)) = (&self.kinematics, &self.workspace) else {
+            return Ok(());
+        };
+        let angle = |axis| Some(angles[self.joint_of(axis)?]);
+        let (Some(shoulder), Some(elbow)) = (angle(Axis::Shoulder), angle(Axis::Elbow)) else {
+            return Ok(());
+        };
+        // the limits are round around the base axis, so the base angle doesn't matter
+        let point = kinematics.forward(&ChainAngles {
+            base: kinematics.base.zero,
+            shoulder,
+            elbow,
+        });
+        workspace.check(point)
+    }
+}
+
+/// Angle of the joints of the axis in the chain, `None` for joints outside of it.
+fn chain_target(angles: &ChainAngles, axis: Axis) -> Option<Degrees> {
+    match axis {
+        Axis::BaseRotator => Some(angles.base),
+        Axis::Shoulder => Some(angles.shoulder),
+        Axis::Elbow => Some(angles.elbow),
+        Axis::Gripper => None,
+    }
+}
+
+/// Step size range at the scale in hundredths of a degree.
+fn scaled_output<const N: usize>(
+    config: &ArmBotConfig<N>,
+    scale: f32,
+) -> Result<Range<u32>, Error> {
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(Error::OutOfRange("speed scale must be positive"));
+    }
+    let scaled = |step: Degrees| Degrees::new(step.get() * scale).to_hundredths();
+    Ok(scaled(config.step_size.start)? as u32..scaled(config.step_size.end)? as u32)
+}
+
+/// Joint on the base rotator axis that isn't a servo, e.g. a stepper or a DC motor with an
+/// encoder.
+pub trait BaseJoint {
+    /// Makes a step of the control loop with the command of the base rotator axis.
+    fn make_step(&mut self, cmd: &Position) -> Result<(), Error>;
+
+    /// Returns true while the joint is still moving, e.g. slowing down after the stick
+    /// was centered.
+    fn is_moving(&self) -> bool;
+
+    /// Angle of the joint, `None` without a base.
+    fn angle(&self) -> Option<Degrees>;
+
+    /// Stops holding the position, for the emergency stop.
+    fn disable(&mut self);
+
+    fn enable(&mut self);
+}
+
+/// Arm without a base joint, or with a servo on the base rotator axis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoBase;
+
+impl BaseJoint for NoBase {
+    fn make_step(&mut self, _cmd: &Position) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn is_moving(&self) -> bool {
+        false
+    }
+
+    fn angle(&self) -> Option<Degrees> {
+        None
+    }
+
+    fn disable(&mut self) {}
+
+    fn enable(&mut self) {}
+}
+
+/// Single servo driven joint of the arm.
+struct Joint<D> {
+    name: &'static str,
+    servo: D,
+    /// Axis of the joint, its stick is mapped by [`ArmBotConfig::axis_map`].
+    axis: Axis,
+    health: JointHealth,
+    /// Direction of the last move, a stall is reported against it.
+    last_dir: Option<Dir>,
+    /// End stops at the min and max ends of the range are pressed.
+    end_stops: [bool; 2],
+    /// Result of the last step, so reaching a limit is reported once.
+    last_step: StepResult,
+    rate_limit: Option<RateLimit>,
+    /// Angle the rate limiter moves the joint to, set by moves that jump the servo without it.
+    goal: Option<Degrees>,
+    /// Goal is the end of a move rather than the next step of a path.
+    stop_at_goal: bool,
+    /// Angle and speed in °/s the rate limiter left the joint at.
+    limited_angle: f32,
+    speed: f32,
+}
+
+impl<D: ServoDriver> Joint<D> {
+    fn new(config: &JointConfig, servo: D) -> Self {
+        let limited_angle = servo.get_angle() as f32;
+        Self {
+            name: config.name,
+            servo,
+            axis: config.axis,
+            health: JointHealth::default(),
+            last_dir: None,
+            end_stops: [false; 2],
+            last_step: StepResult::Stepped,
+            rate_limit: config.rate_limit,
+            goal: None,
+            stop_at_goal: false,
+            limited_angle,
+            speed: 0.0,
+        }
+    }
+
+    /// Makes a step unless the joint is faulted or the step goes the way it stalled or towards
+    /// a pressed end stop.
+    /// Joint becomes faulted after `max_errors` consecutive errors and is held in place since then.
+    fn make_step(&mut self, cmd: &Position, max_errors: u32) -> Result<(), Error> {
+        if self.health.faulted {
+            return Ok(());
+        }
+        if *cmd != Position::Center {
+            // the stick takes over
+            self.goal = None;
+        }
+        if *cmd == Position::Center {
+            // lets a profiled servo slow down, others don't move
+            return self.servo.step_deg(0.0).map(|_| ()).map_err(Error::servo);
+        }
+        let dir = match cmd {
+            Position::Low(_) => Dir::CW,
+            _ => Dir::CCW,
+        };
+        if self.health.stalled == Some(dir) || self.at_end_stop(dir) {
+            return Ok(());
+        }
+
+        let health = &mut self.health;
+        match Self::step_servo(cmd, &mut self.servo) {
+            Ok(result) => {
+                health.consecutive_errors = 0;
+                health.counters.steps += 1;
+                if result.is_clamped() {
+                    health.counters.limit_hits += 1;
+                    if result != self.last_step {
+                        info!("{} joint reached its limit: {result:?}", self.name);
+                    }
+                }
+                #[cfg(feature = "defmt")]
+                defmt::trace!("{=str} step: {}", self.name, cmd);
+                if health.stalled.take().is_some() {
+                    info!("{} joint moved back, stall cleared", self.name);
+                }
+                self.last_dir = Some(dir);
+                self.last_step = result;
+                Ok(())
+            }
+            Err(err) => {
+                health.counters.errors += 1;
+                health.consecutive_errors += 1;
+                if health.consecutive_errors >= max_errors {
+                    error!(
+                        "{} joint faulted after {max_errors} errors, last: {err:?}",
+                        self.name
+                    );
+                    health.faulted = true;
+                    return Err(Error::JointFaulted(self.name));
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Sets the angle unless the joint is faulted, fails if the joint stalled that way or
+    /// the end stop that way is pressed. The rate limiter slows down to stop at the angle.
+    fn move_to(&mut self, angle: Degrees) -> Result<(), Error> {
+        self.command(angle, true)
+    }
+
+    /// Sets the next angle of a path, e.g. of a pose move, which slows down by itself.
+    fn follow(&mut self, angle: Degrees) -> Result<(), Error> {
+        self.command(angle, false)
+    }
+
+    fn command(&mut self, angle: Degrees, stop_at_goal: bool) -> Result<(), Error> {
+        if self.health.faulted {
+            return Ok(());
+        }
+        let current = self.servo.get_angle();
+        let dir = match (angle.get() as f64).partial_cmp(&current) {
+            Some(Ordering::Less) => Dir::CW,
+            Some(Ordering::Greater) => Dir::CCW,
+            _ => return Ok(()),
+        };
+        if self.health.stalled == Some(dir) {
+            return Err(Error::Stalled(self.name));
+        }
+        if self.at_end_stop(dir) {
+            return Err(Error::OutOfRange("joint is at its end stop"));
+        }
+        if self.rate_limit.is_some() {
+            // the rate limiter moves the servo there in steps
+            self.goal = Some(angle);
+            self.stop_at_goal = stop_at_goal;
+        } else {
+            self.servo
+                .set_angle(angle.get() as f64)
+                .map_err(Error::servo)?;
+        }
+        if self.health.stalled.take().is_some() {
+            info!("{} joint moved back, stall cleared", self.name);
+        }
+        self.last_dir = Some(dir);
+        Ok(())
+    }
+
+    /// Makes a step towards the goal, or back from a step of the servo faster than the rate
+    /// limit, e.g. a stick flick. Drops the goal if the joint stalled or hit an end stop.
+    fn limit_rate(&mut self, dt: f32) -> Result<(), Error> {
+        let Some(limit) = self.rate_limit else {
+            return Ok(());
+        };
+        let current = self.angle().get();
+        if self.health.faulted {
+            self.hold();
+            return Ok(());
+        }
+        let target = self.goal.map_or(current, Degrees::get);
+        let stop = self.goal.is_some() && self.stop_at_goal;
+        let (angle, speed) = limit.step(self.limited_angle, self.speed, target, dt, stop);
+        if self.goal.is_some() && angle != current {
+            let dir = if angle < current { Dir::CW } else { Dir::CCW };
+            if self.health.stalled == Some(dir) || self.at_end_stop(dir) {
+                self.hold();
+                return Ok(());
+            }
+        }
+        if angle != current {
+            self.servo.set_angle(angle as f64).map_err(Error::servo)?;
+        }
+        self.limited_angle = angle;
+        self.speed = speed;
+        if self.goal.is_some_and(|goal| goal.get() == angle) {
+            self.goal = None;
+        }
+        Ok(())
+    }
+
+    /// Stops the rate limiter where the servo is, e.g. after it was detached.
+    fn hold(&mut self) {
+        self.goal = None;
+        self.speed = 0.0;
+        self.limited_angle = self.angle().get();
+    }
+
+    /// Commanded angle, read from the servo.
+    fn angle(&self) -> Degrees {
+        Degrees::new(self.servo.get_angle() as f32)
+    }
+
+    /// Returns true if the end stop the joint moves to in the direction is pressed.
+    fn at_end_stop(&self, dir: Dir) -> bool {
+        match dir {
+            Dir::CW => self.end_stops[0],
+            Dir::CCW => self.end_stops[1],
+        }
+    }
+
+    /// Moves the servo according to the command, steps are in hundredths of a degree.
+    /// Tells if the step was cut by a limit of the servo.
+    fn step_servo(cmd: &Position, servo: &mut D) -> Result<StepResult, Error> {
+        let result = match cmd {
+            Position::Center => {
+                // do nothing
+                StepResult::Stepped
+            }
+            Position::Low(step) => {
+                servo.set_dir(Dir::CW);
+                servo.step_deg(*step as f32 / 100.0).map_err(Error::servo)?
+            }
+            Position::High(step) => {
+                servo.set_dir(Dir::CCW);
+                servo.step_deg(*step as f32 / 100.0).map_err(Error::servo)?
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArmBotConfig<const N: usize = JOINTS> {
+    /// Joints of the arm.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_array"))]
+    pub joints: [JointConfig; N],
+
+    /// Min possible step, for slowest motion.
+    /// Max possible step, for fastest motion.
+    /// Steps are made every control period.
+    pub step_size: Range<Degrees>,
+
+    /// Number of consecutive errors after which a joint is considered faulted.
+    pub max_joint_errors: u32,
+
+    /// Actions of the buttons, indexed by [`Button`].
+    pub buttons: [Option<Action>; BUTTONS],
+    /// Gamepad input of the joints of every axis, indexed by [`JointConfig::axis`].
+    /// Swapped sticks or a mirrored servo are fixed here instead of in the wiring.
+    pub axis_map: [AxisMapping; AXES],
+
+    /// Link lengths for Cartesian moves, `None` if the arm is only driven joint by joint.
+    pub kinematics: Option<KinematicsConfig>,
+    /// Keeps the gripper off the table and the base, needs the kinematics.
+    pub workspace: Option<WorkspaceLimits>,
+
+    /// Angles of the named poses, indexed by [`PoseName`].
+    /// Home without angles takes the home angles of the joints, other poses can't be reached.
+    pub poses: [Option<PoseAngles<N>>; POSES],
+    /// Speed of the joint that moves the most in a pose move, in °/s.
+    pub pose_speed: f32,
+    /// Speed curve of pose moves.
+    pub pose_easing: Easing,
+    /// Path of queued moves, see [`ArmBot::queue_move`].
+    pub interpolation: Interpolation,
+    /// Speeds of G-code moves, see [`ArmBot::run_gcode`].
+    pub gcode: InterpreterConfig,
+
+    /// Scales of the step size range, indexed by [`SpeedMode`].
+    /// Motion profiles of the joints still cap their speed.
+    pub speed_scales: [f32; SPEED_MODES],
+    /// Millimeters the gripper moves in Cartesian teleop per degree of stick step, so the
+    /// speed modes scale it too.
+    pub cartesian_step: f32,
+    /// How fast a pushed stick speeds up its joint, in °/s². Profiled joints keep to their
+    /// own acceleration as well.
+    pub stick_accel: f32,
+
+    /// Joint that keeps the gripper level, `None` on an arm without a wrist.
+    pub level_hold: Option<LevelHoldConfig>,
+
+    /// What happens when the gamepad input is lost.
+    pub failsafe: FailsafeConfig,
+}
+
+/// Number of speed modes.
+pub const SPEED_MODES: usize = 3;
+
+/// Sensitivity of the sticks, switched by [`Action::CycleSpeed`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpeedMode {
+    /// Fine positioning, e.g. for assembly work.
+    Precision = 0,
+    #[default]
+    Normal = 1,
+    /// Repositioning across the workspace.
+    Fast = 2,
+}
+
+impl SpeedMode {
+    /// All modes from the slowest to the fastest.
+    pub const ALL: [SpeedMode; SPEED_MODES] =
+        [SpeedMode::Precision, SpeedMode::Normal, SpeedMode::Fast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpeedMode::Precision => "precision",
+            SpeedMode::Normal => "normal",
+            SpeedMode::Fast => "fast",
+        }
+    }
+
+    /// Parses a mode name, case insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Next faster mode, the fastest one wraps to the slowest.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % SPEED_MODES]
+    }
+}
+
+/// What the sticks move, switched by [`Action::ToggleTeleop`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TeleopMode {
+    /// Every stick moves its joints.
+    #[default]
+    Joint,
+    /// One stick moves the gripper forward and sideways, the other up and down and the
+    /// gripper, see [`crate::kinematics`].
+    Cartesian,
+}
+
+impl TeleopMode {
+    pub const ALL: [TeleopMode; 2] = [TeleopMode::Joint, TeleopMode::Cartesian];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TeleopMode::Joint => "joint",
+            TeleopMode::Cartesian => "cartesian",
+        }
+    }
+
+    /// Parses a mode name, case insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Number of named poses.
+pub const POSES: usize = 4;
+
+/// Named pose of [`ArmBot::goto_pose`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PoseName {
+    Home = 0,
+    /// Folded for transport or power off.
+    Park = 1,
+    Pick = 2,
+    Place = 3,
+}
+
+impl PoseName {
+    /// All poses in the order of their indices.
+    pub const ALL: [PoseName; POSES] = [
+        PoseName::Home,
+        PoseName::Park,
+        PoseName::Pick,
+        PoseName::Place,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PoseName::Home => "home",
+            PoseName::Park => "park",
+            PoseName::Pick => "pick",
+            PoseName::Place => "place",
+        }
+    }
+
+    /// Parses a pose name, case insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|pose| pose.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Joint angles of a pose, in the joint order of the arm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseAngles<const N: usize>(
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_array"))] pub [Degrees; N],
+);
+
+/// Coordinated move of all joints to a pose, advanced every step.
+struct PoseMove<const N: usize> {
+    pose: PoseName,
+    start: [Degrees; N],
+    target: [Degrees; N],
+    /// Steps the move takes.
+    steps: u32,
+    /// Steps made so far.
+    done: u32,
+}
+
+/// What a gamepad button does when pressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Moves the joints driven by the axis to the start of their ranges.
+    JointToMin(Axis),
+    /// Moves the joints driven by the axis to the end of their ranges,
+    /// e.g. opens the gripper fully.
+    JointToMax(Axis),
+    /// Moves every joint to the home pose, see [`ArmBot::goto_pose`].
+    Home,
+    /// Detaches all servos and ignores the sticks until [`ArmBot::release_stop`],
+    /// the stop switch does the same.
+    EmergencyStop,
+    /// Switches to the next [`SpeedMode`].
+    CycleSpeed,
+    /// Switches between the joint and the Cartesian [`TeleopMode`].
+    ToggleTeleop,
+}
+
+impl Default for ArmBotConfig {
+    fn default() -> Self {
+        let step_period = crate::CONTROL_PERIOD.as_micros() as f64 / 1_000_000.0;
+        // the fastest step is 100°/s, full speed is reached in 0.25s
+        let profile = MotionProfile::new(100.0, 400.0, step_period);
+        // above the profile, so it only holds back jumps and the fast speed mode
+        let rate_limit = RateLimit::new(120.0, 600.0);
+        Self {
+            joints: [
+                JointConfig::new("shoulder", Axis::Shoulder, 30..150)
+                    .with_profile(profile)
+                    .with_rate_limit(rate_limit),
+                JointConfig::new("elbow", Axis::Elbow, 30..150)
+                    .with_profile(profile)
+                    .with_rate_limit(rate_limit),
+                JointConfig::new("gripper", Axis::Gripper, 20..70),
+            ],
+            step_size: Degrees::new(0.1)..Degrees::new(1.0),
+            max_joint_errors: 5,
+            buttons: {
+                let mut buttons = [None; BUTTONS];
+                buttons[Button::Stick1 as usize] = Some(Action::JointToMax(Axis::Gripper));
+                buttons[Button::Stick2 as usize] = Some(Action::Home);
+                buttons[Button::Aux1 as usize] = Some(Action::EmergencyStop);
+                buttons[Button::Aux2 as usize] = Some(Action::CycleSpeed);
+                buttons
+            },
+            axis_map: Axis::ALL.map(AxisMapping::new),
+            kinematics: Some(KinematicsConfig::default()),
+            workspace: Some(WorkspaceLimits::default()),
+            poses: [None; POSES],
+            pose_speed: 45.0,
+            pose_easing: Easing::CubicInOut,
+            interpolation: Interpolation::Cubic,
+            gcode: InterpreterConfig::default(),
+            speed_scales: [0.25, 1.0, 1.5],
+            // 100 mm/s at the fastest normal step
+            cartesian_step: 1.0,
+            // full normal speed in 0.25s, like the joint profiles
+            stick_accel: 400.0,
+            level_hold: None,
+            failsafe: FailsafeConfig::default(),
+        }
+    }
+}
+
+/// Wrist joint that keeps the gripper level, see [`ArmBot::set_level_hold`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelHoldConfig {
+    /// Index of the wrist joint.
+    pub joint: usize,
+    /// Degrees the joint turns per degree of pitch in a step. Positive if greater angles of
+    /// the joint point the gripper up.
+    pub gain: f32,
+    /// Largest correction of a step.
+    pub max_step: Degrees,
+    /// Pitch that is left uncorrected, so IMU noise doesn't jiggle the joint.
+    pub tolerance: Degrees,
+}
+
+impl LevelHoldConfig {
+    fn validate(&self, joints: usize) -> Result<(), Error> {
+        if self.joint >= joints {
+            return Err(Error::OutOfRange("no such level hold joint"));
+        }
+        if !(self.gain.is_finite() && self.gain != 0.0) || self.max_step.get() <= 0.0 {
+            return Err(Error::OutOfRange("level hold needs a gain and a max step"));
+        }
+        Ok(())
+    }
+}
+
+/// Gamepad axis driving the joints of an axis, see [`ArmBotConfig::axis_map`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisMapping {
+    /// Gamepad axis read, `None` leaves the joints to poses and commands.
+    pub input: Option<Axis>,
+    /// Stick moves the joints the other way, e.g. for a mirrored servo.
+    pub invert: bool,
+    /// Factor of the stick steps, e.g. 0.5 for a joint with a long lever.
+    pub scale: f32,
+}
+
+impl AxisMapping {
+    pub const fn new(input: Axis) -> Self {
+        Self {
+            input: Some(input),
+            invert: false,
+            scale: 1.0,
+        }
+    }
+
+    pub const fn inverted(mut self) -> Self {
+        self.invert = !self.invert;
+        self
+    }
+
+    pub const fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(Error::OutOfRange("axis scale must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Failsafe for lost gamepad input, e.g. a failing ADC or a wireless gamepad out of range.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FailsafeConfig {
+    /// How long the input may be lost before the sticks are centered.
+    pub timeout: Duration,
+    /// Also detaches the servos, latched like the emergency stop.
+    pub detach: bool,
+}
+
+impl Default for FailsafeConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(250),
+            detach: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(into = "JointConfigRepr")
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JointConfig {
+    /// Name of the joint, used in logs.
+    pub name: &'static str,
+    /// Axis of the joint: its stick through [`ArmBotConfig::axis_map`], its angle in Cartesian
+    /// moves and its joint actions. Several joints can share an axis.
+    pub axis: Axis,
+    /// Allowed range of the joint angle, enforced by the servo.
+    pub angle_range: Range<Degrees>,
+    /// Ramps the speed of the joint, `None` moves it with the stick right away.
+    pub profile: Option<MotionProfile>,
+    /// Caps the speed and acceleration of every move of the joint, `None` lets moves jump.
+    pub rate_limit: Option<RateLimit>,
+    /// Angle of [`Action::Home`], the middle of the angle range by default.
+    pub home: Degrees,
+}
+
+impl JointConfig {
+    /// Creates the config, angle range is in whole degrees.
+    pub const fn new(name: &'static str, axis: Axis, angle_range: Range<u16>) -> Self {
+        Self {
+            name,
+            axis,
+            angle_range: Degrees::from_whole(angle_range.start)
+                ..Degrees::from_whole(angle_range.end),
+            profile: None,
+            rate_limit: None,
+            home: Degrees::from_whole((angle_range.start + angle_range.end) / 2),
+        }
+    }
+
+    pub fn with_home(mut self, home: Degrees) -> Self {
+        self.home = home;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: MotionProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+}
+
+/// Max speed and acceleration of a joint, so a stick flick or a far jump doesn't whip the arm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RateLimit {
+    /// In °/s.
+    pub max_speed: f32,
+    /// In °/s².
+    pub max_accel: f32,
+}
+
+impl RateLimit {
+    pub const fn new(max_speed: f32, max_accel: f32) -> Self {
+        Self {
+            max_speed,
+            max_accel,
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let valid = |val: f32| val.is_finite() && val > 0.0;
+        if !valid(self.max_speed) || !valid(self.max_accel) {
+            return Err(Error::OutOfRange("rate limits must be positive"));
+        }
+        Ok(())
+    }
+
+    /// Angle and speed after a step of `dt` seconds from the angle at the speed towards the
+    /// target, slowing down in time to stop at it if `stop`. A joint following a path or its
+    /// stick stops with them, slowing it down is left to the path or the motion profile.
+    fn step(&self, angle: f32, speed: f32, target: f32, dt: f32, stop: bool) -> (f32, f32) {
+        let distance = target - angle;
+        if distance == 0.0 {
+            return (target, 0.0);
+        }
+        let mut wanted = (distance / dt).clamp(-self.max_speed, self.max_speed);
+        if stop {
+            let stopping = libm::sqrtf(2.0 * self.max_accel * distance.abs());
+            wanted = wanted.clamp(-stopping, stopping);
+        }
+        let change = self.max_accel * dt;
+        let speed = wanted.clamp(speed - change, speed + change);
+        let next = angle + speed * dt;
+        if (next - target) * distance.signum() >= 0.0 {
+            // a path keeps its speed into the next step
+            return (target, distance / dt);
+        }
+        (next, speed)
+    }
+}
+
+/// [`JointConfig`] as stored, the name is taken from the axis.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JointConfigRepr {
+    axis: Axis,
+    angle_range: Range<Degrees>,
+    profile: Option<MotionProfile>,
+    #[serde(default)]
+    rate_limit: Option<RateLimit>,
+    home: Degrees,
+}
+
+// derived from the repr it would only deserialize from `'static` data, as the name is `'static`
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for JointConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JointConfigRepr::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<JointConfigRepr> for JointConfig {
+    fn from(repr: JointConfigRepr) -> Self {
+        Self {
+            name: repr.axis.name(),
+            axis: repr.axis,
+            angle_range: repr.angle_range,
+            profile: repr.profile,
+            rate_limit: repr.rate_limit,
+            home: repr.home,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<JointConfig> for JointConfigRepr {
+    fn from(config: JointConfig) -> Self {
+        Self {
+            axis: config.axis,
+            angle_range: config.angle_range,
+            profile: config.profile,
+            rate_limit: config.rate_limit,
+            home: config.home,
+        }
+    }
+}
+
+/// Health of a single joint.
+#[derive(Debug, Default)]
+struct JointHealth {
+    /// Number of errors in a row, reset by a successful step.
+    consecutive_errors: u32,
+    /// Faulted joint isn't commanded until faults are reset.
+    faulted: bool,
+    /// Direction the joint stalled in, it isn't moved that way until it moves back.
+    stalled: Option<Dir>,
+    counters: JointCounters,
+}
+
+impl JointHealth {
+    /// Clears the fault and the stall, counters are kept.
+    fn reset(&mut self) {
+        self.consecutive_errors = 0;
+        self.faulted = false;
+        self.stalled = None;
+    }
+}
+
+/// Counters of a single joint since boot.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointCounters {
+    /// Number of steps commanded to the servo.
+    pub steps: u32,
+    /// Number of steps cut at a limit of the servo.
+    pub limit_hits: u32,
+    /// Number of failed steps.
+    pub errors: u32,
+    /// Number of stalls reported, see [`ArmBot::report_stall`].
+    pub stalls: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use servo_driver::ServoError;
+
+    use super::*;
+    use crate::{
+        clock::{Clock, MockClock},
+        sim::{SimGamepad, SimServo, AXIS_KEYS},
+    };
+
+    type SimBot = ArmBot<SimGamepad, SimServo>;
+
+    fn bot(angles: [f64; JOINTS]) -> SimBot {
+        ArmBot::new(
+            ArmBotConfig::default(),
+            SimGamepad::new(),
+            angles.map(SimServo::new),
+        )
+        .unwrap()
+    }
+
+    /// Steps until nothing moves, fails if the arm doesn't settle in the steps.
+    fn settle<const N: usize>(bot: &mut ArmBot<SimGamepad, SimServo, N>, max_steps: u32) {
+        for _ in 0..max_steps {
+            bot.do_step().unwrap();
+            if !bot.is_moving() {
+                return;
+            }
+        }
+        panic!("arm is still moving after {max_steps} steps");
+    }
+
+    fn assert_angles(bot: &SimBot, expected: [f32; JOINTS]) {
+        for (angle, expected) in bot.joint_angles().iter().zip(expected) {
+            assert!(
+                (angle.get() - expected).abs() < 0.01,
+                "{:?} != {expected:?}",
+                bot.joint_angles()
+            );
+        }
+    }
+
+    fn key(axis: Axis, high: bool) -> u8 {
+        AXIS_KEYS
+            .iter()
+            .find(|(_, idx, key_high)| *idx == axis as usize && *key_high == high)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn stick_moves_its_joint_only() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        bot.gamepad_mut().press(key(Axis::Shoulder, true));
+        for _ in 0..20 {
+            bot.do_step().unwrap();
+        }
+        let [shoulder, elbow, gripper] = bot.joint_angles();
+        assert!(shoulder.get() > 90.5, "shoulder at {shoulder}");
+        assert_eq!(elbow.get(), 90.0);
+        assert_eq!(gripper.get(), 45.0);
+    }
+
+    #[test]
+    fn stick_eases_in_and_stops_at_once() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        bot.gamepad_mut().press(key(Axis::Shoulder, true));
+        let mut last = 90.0;
+        let mut steps = Vec::new();
+        for _ in 0..5 {
+            bot.do_step().unwrap();
+            let shoulder = bot.joint_angles()[0].get();
+            steps.push(shoulder - last);
+            last = shoulder;
+        }
+        assert!(steps.windows(2).all(|pair| pair[1] >= pair[0]), "{steps:?}");
+        assert!(steps[0] < steps[4], "{steps:?}");
+        bot.gamepad_mut().release_all();
+        bot.do_step().unwrap();
+        assert_eq!(bot.stick_ramps[Axis::Shoulder as usize].value(), 0.0);
+    }
+
+    #[test]
+    fn speed_mode_switch_ramps_the_step_sizes() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let normal = bot.step_output.clone();
+        bot.set_speed_mode(SpeedMode::Fast);
+        bot.do_step().unwrap();
+        assert!(bot.step_output.end > normal.end);
+        let fast = scaled_output(
+            &bot.config,
+            bot.config.speed_scales[SpeedMode::Fast as usize],
+        );
+        assert_ne!(bot.step_output, fast.clone().unwrap());
+        for _ in 0..100 {
+            bot.do_step().unwrap();
+        }
+        assert_eq!(bot.step_output, fast.unwrap());
+    }
+
+    #[test]
+    fn stick_stops_at_the_joint_range() {
+        let mut bot = bot([148.0, 90.0, 45.0]);
+        bot.gamepad_mut().press(key(Axis::Shoulder, true));
+        for _ in 0..100 {
+            bot.do_step().unwrap();
+        }
+        assert_eq!(bot.joint_angles()[0].get(), 150.0);
+        assert!(bot.at_limit());
+    }
+
+    #[test]
+    fn home_pose_reaches_home_angles() {
+        let mut bot = bot([100.0, 80.0, 30.0]);
+        bot.goto_pose(PoseName::Home).unwrap();
+        settle(&mut bot, 200);
+        assert_angles(&bot, [90.0, 90.0, 45.0]);
+    }
+
+    #[test]
+    fn queued_moves_play_in_order() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let first = [100.0, 90.0, 45.0].map(Degrees::new);
+        let second = [100.0, 100.0, 60.0].map(Degrees::new);
+        bot.queue_move(first, Duration::from_millis(200)).unwrap();
+        bot.queue_move(second, Duration::from_millis(200)).unwrap();
+        for _ in 0..20 {
+            bot.do_step().unwrap();
+        }
+        assert_angles(&bot, [100.0, 90.0, 45.0]);
+        settle(&mut bot, 100);
+        assert_angles(&bot, [100.0, 100.0, 60.0]);
+    }
+
+    #[test]
+    fn target_out_of_range_is_rejected() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let targets = [10.0, 90.0, 45.0].map(Degrees::new);
+        assert!(bot.queue_move(targets, Duration::from_millis(200)).is_err());
+        assert!(bot.move_joint(2, Degrees::new(80.0)).is_err());
+        for _ in 0..20 {
+            bot.do_step().unwrap();
+        }
+        assert_angles(&bot, [90.0, 90.0, 45.0]);
+    }
+
+    #[test]
+    fn emergency_stop_ignores_sticks() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        bot.run_action(Action::EmergencyStop).unwrap();
+        assert!(bot.is_stopped());
+        bot.gamepad_mut().press(key(Axis::Elbow, false));
+        for _ in 0..20 {
+            bot.do_step().unwrap();
+        }
+        assert_angles(&bot, [90.0, 90.0, 45.0]);
+    }
+
+    #[test]
+    fn invalid_profile_is_rejected() {
+        let with_profile = |profile| {
+            let mut config = ArmBotConfig::default();
+            config.joints[0].profile = Some(profile);
+            let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+            ArmBot::new(config, SimGamepad::new(), servos)
+        };
+        assert!(matches!(
+            with_profile(MotionProfile::new(100.0, 0.0, 0.02)),
+            Err(Error::Servo(ServoError::InvalidProfile))
+        ));
+        assert!(with_profile(MotionProfile::new(100.0, f64::NAN, 0.02)).is_err());
+        assert!(with_profile(MotionProfile::new(-100.0, 400.0, 0.02)).is_err());
+
+        // a tiny accel saturates the settle steps instead of overflowing
+        let bot: SimBot = with_profile(MotionProfile::new(100.0, 1e-30, 0.02)).unwrap();
+        assert_eq!(bot.settle_steps, u32::MAX);
+    }
+
+    #[test]
+    fn joint_faults_after_errors_in_a_row() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let max_errors = bot.config.max_joint_errors;
+        bot.gamepad_mut().press(key(Axis::Shoulder, true));
+
+        // a success in between starts the count again
+        bot.joints[0].servo.set_failing(true);
+        for _ in 0..max_errors - 1 {
+            assert!(bot.do_step().is_err());
+        }
+        bot.joints[0].servo.set_failing(false);
+        bot.do_step().unwrap();
+        assert_eq!(bot.joints[0].health.consecutive_errors, 0);
+
+        bot.joints[0].servo.set_failing(true);
+        for _ in 0..max_errors - 1 {
+            assert!(bot.do_step().is_err());
+            assert!(!bot.joints[0].health.faulted);
+        }
+        assert!(bot.do_step().is_err());
+        assert!(bot.joints[0].health.faulted);
+
+        // a faulted joint is held, the others keep moving
+        bot.joints[0].servo.set_failing(false);
+        let shoulder = bot.joint_angles()[0];
+        bot.gamepad_mut().press(key(Axis::Elbow, true));
+        for _ in 0..10 {
+            bot.do_step().unwrap();
+        }
+        assert_eq!(bot.joint_angles()[0], shoulder);
+        assert!(bot.joint_angles()[1].get() > 90.0);
+    }
+
+    /// Steps once per control period until the failsafe engages, returns the time it took.
+    fn time_to_failsafe(bot: &mut SimBot, clock: &MockClock) -> Duration {
+        let lost_at = clock.now();
+        for _ in 0..1000 {
+            clock.advance(crate::CONTROL_PERIOD);
+            let result = bot.do_step();
+            if bot.is_failsafe() {
+                result.unwrap();
+                return clock.now().duration_since(lost_at);
+            }
+            // the arm is held until then
+            assert!(result.is_err());
+        }
+        panic!("failsafe didn't engage");
+    }
+
+    #[test]
+    fn lost_input_centers_the_sticks_after_the_timeout() {
+        let clock = MockClock::default();
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let timeout = bot.config.failsafe.timeout;
+        bot.gamepad_mut().press(key(Axis::Shoulder, true));
+        bot.do_step().unwrap();
+
+        // a stale wireless gamepad keeps reporting the held stick
+        bot.gamepad_mut().set_stale(true);
+        let held = bot.joint_angles();
+        let lost_for = time_to_failsafe(&mut bot, &clock);
+        assert!(lost_for >= timeout && lost_for < timeout + crate::CONTROL_PERIOD);
+        assert_eq!(bot.joint_angles(), held);
+        for _ in 0..10 {
+            clock.advance(crate::CONTROL_PERIOD);
+            bot.do_step().unwrap();
+        }
+        assert!(bot.stick(Axis::Shoulder) == Position::Center);
+        assert_eq!(bot.joint_angles(), held);
+        assert!(!bot.is_stopped());
+
+        bot.gamepad_mut().set_stale(false);
+        bot.do_step().unwrap();
+        assert!(!bot.is_failsafe());
+        assert!(bot.joint_angles()[0] > held[0]);
+    }
+
+    #[test]
+    fn failsafe_can_detach_the_servos() {
+        let clock = MockClock::default();
+        let mut config = ArmBotConfig::default();
+        config.failsafe.detach = true;
+        let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+        let mut bot: SimBot = ArmBot::new(config, SimGamepad::new(), servos).unwrap();
+        bot.do_step().unwrap();
+
+        bot.gamepad_mut().set_failing(true);
+        let lost_for = time_to_failsafe(&mut bot, &clock);
+        assert!(lost_for >= bot.config.failsafe.timeout);
+        assert!(bot.is_stopped());
+        assert!(bot.joints.iter().all(|joint| !joint.servo.is_enabled()));
+
+        // the stop is latched, input coming back doesn't release it
+        bot.gamepad_mut().set_failing(false);
+        bot.do_step().unwrap();
+        assert!(bot.is_stopped());
+    }
+
+    /// Degrees the shoulder moves in a step once its stick ramp has settled.
+    fn shoulder_speed(bot: &mut SimBot, high: bool) -> f32 {
+        bot.gamepad_mut().press(key(Axis::Shoulder, high));
+        for _ in 0..50 {
+            bot.do_step().unwrap();
+        }
+        let before = bot.joint_angles()[0].get();
+        bot.do_step().unwrap();
+        bot.gamepad_mut().release_all();
+        bot.joint_angles()[0].get() - before
+    }
+
+    #[test]
+    fn axis_map_inverts_and_scales_the_stick() {
+        let with_mapping = |mapping: AxisMapping| {
+            let mut config = ArmBotConfig::default();
+            config.axis_map[Axis::Shoulder as usize] = mapping;
+            let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+            ArmBot::new(config, SimGamepad::new(), servos).unwrap()
+        };
+        let plain = shoulder_speed(&mut bot([90.0, 90.0, 45.0]), true);
+        assert!(plain > 0.0);
+
+        let mut inverted = with_mapping(AxisMapping::new(Axis::Shoulder).inverted());
+        assert_eq!(shoulder_speed(&mut inverted, true), -plain);
+        assert!(inverted.joint_angles()[0].get() < 90.0);
+
+        let mut halved = with_mapping(AxisMapping::new(Axis::Shoulder).with_scale(0.5));
+        let speed = shoulder_speed(&mut halved, true);
+        assert!((speed - plain / 2.0).abs() < 0.02, "{speed} vs {plain}");
+
+        // a step scaled below half a hundredth of a degree is no step at all
+        let mut tiny = with_mapping(AxisMapping::new(Axis::Shoulder).with_scale(1e-4));
+        tiny.gamepad_mut().press(key(Axis::Shoulder, true));
+        tiny.do_step().unwrap();
+        assert_eq!(tiny.stick(Axis::Shoulder), Position::Center);
+        assert_eq!(tiny.joint_angles()[0].get(), 90.0);
+    }
+
+    #[test]
+    fn unmapped_axis_leaves_the_joint_alone() {
+        let mut config = ArmBotConfig::default();
+        config.axis_map[Axis::Shoulder as usize].input = None;
+        let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+        let mut bot: SimBot = ArmBot::new(config, SimGamepad::new(), servos).unwrap();
+        assert_eq!(shoulder_speed(&mut bot, true), 0.0);
+    }
+
+    /// Default arm with a servo on the base, so Cartesian teleop can move sideways.
+    fn bot_with_base() -> ArmBot<SimGamepad, SimServo, 4> {
+        let default = ArmBotConfig::default();
+        let [shoulder, elbow, gripper] = default.joints;
+        let config = ArmBotConfig {
+            joints: [
+                JointConfig::new("base", Axis::BaseRotator, 0..180),
+                shoulder,
+                elbow,
+                gripper,
+            ],
+            step_size: default.step_size,
+            max_joint_errors: default.max_joint_errors,
+            buttons: default.buttons,
+            axis_map: default.axis_map,
+            kinematics: default.kinematics,
+            workspace: default.workspace,
+            poses: [None; POSES],
+            pose_speed: default.pose_speed,
+            pose_easing: default.pose_easing,
+            interpolation: default.interpolation,
+            gcode: default.gcode,
+            speed_scales: default.speed_scales,
+            cartesian_step: default.cartesian_step,
+            stick_accel: default.stick_accel,
+            level_hold: None,
+            failsafe: default.failsafe,
+        };
+        let servos = [90.0, 90.0, 90.0, 45.0].map(SimServo::new);
+        ArmBot::new(config, SimGamepad::new(), servos).unwrap()
+    }
+
+    fn gripper_point(bot: &ArmBot<SimGamepad, SimServo, 4>) -> Point {
+        let [base, shoulder, elbow, _] = bot.joint_angles();
+        bot.config
+            .kinematics
+            .as_ref()
+            .unwrap()
+            .forward(&ChainAngles {
+                base,
+                shoulder,
+                elbow,
+            })
+    }
+
+    #[test]
+    fn cartesian_sticks_move_the_gripper_along_the_axes() {
+        let mut bot = bot_with_base();
+        bot.set_teleop_mode(TeleopMode::Cartesian).unwrap();
+        let start = gripper_point(&bot);
+        assert!(
+            (start.x - 80.0).abs() < 0.01 && (start.z - 80.0).abs() < 0.01,
+            "{start:?}"
+        );
+
+        // shoulder stick forward along x, base stick sideways along y, elbow stick up along z
+        for (component, axis) in [Axis::Shoulder, Axis::BaseRotator, Axis::Elbow]
+            .into_iter()
+            .enumerate()
+        {
+            let before = gripper_point(&bot);
+            bot.gamepad_mut().press(key(axis, true));
+            for _ in 0..20 {
+                bot.do_step().unwrap();
+            }
+            bot.gamepad_mut().release_all();
+            settle(&mut bot, 100);
+            let after = gripper_point(&bot);
+            let moved = [after.x - before.x, after.y - before.y, after.z - before.z];
+            assert!(moved[component] > 1.0, "{axis:?} moved by {moved:?}");
+            for (idx, off_axis) in moved.iter().enumerate() {
+                if idx != component {
+                    assert!(off_axis.abs() < 0.1, "{axis:?} moved by {moved:?}");
+                }
+            }
+        }
+        // joints out of the chain stay where they are
+        assert_eq!(bot.joint_angles()[3].get(), 45.0);
+    }
+
+    #[test]
+    fn large_jump_is_spread_over_steps_at_the_rate_limit() {
+        let mut bot = bot([90.0, 90.0, 45.0]);
+        let limit = bot.config.joints[0].rate_limit.unwrap();
+        let dt = crate::CONTROL_PERIOD.as_secs_f32();
+        let (max_step, max_change) = (limit.max_speed * dt, limit.max_accel * dt * dt);
+
+        bot.move_joint(0, Degrees::new(140.0)).unwrap();
+        let mut angles = vec![90.0];
+        while angles.len() < 1000 {
+            bot.do_step().unwrap();
+            angles.push(bot.joint_angles()[0].get());
+            if !bot.is_moving() {
+                break;
+            }
+        }
+        assert_eq!(angles.last(), Some(&140.0));
+        assert!(
+            angles.len() as f32 > 50.0 / max_step,
+            "{} steps",
+            angles.len()
+        );
+        let steps: Vec<f32> = angles.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(
+            steps
+                .iter()
+                .all(|step| (0.0..=max_step + 1e-3).contains(step)),
+            "{steps:?}"
+        );
+        // speeds up and slows down at the accel limit, the last step lands on the target
+        let ramp = &steps[..steps.len() - 1];
+        assert!(
+            ramp.windows(2)
+                .all(|pair| (pair[1] - pair[0]).abs() <= max_change + 1e-3),
+            "{steps:?}"
+        );
+
+        // a joint without a rate limit jumps at once
+        bot.move_joint(2, Degrees::new(60.0)).unwrap();
+        assert_eq!(bot.joint_angles()[2].get(), 60.0);
+    }
+}
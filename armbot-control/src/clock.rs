@@ -0,0 +1,87 @@
+use core::{cell::Cell, ops::Add, time::Duration};
+
+/// Point in time, microseconds since an arbitrary epoch (boot for the system clock).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub const fn from_micros(micros: u64) -> Self {
+        Self(micros)
+    }
+
+    pub const fn as_micros(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns time passed since `earlier`, zero if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_micros(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Instant(self.0 + rhs.as_micros() as u64)
+    }
+}
+
+/// Source of time for time-dependent logic.
+///
+/// Logic takes a clock instead of reading hardware timers directly,
+/// so it can be driven by [`MockClock`] or by a replay.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// Clock that only moves when it's told to.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Cell<u64>,
+}
+
+impl MockClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            now: Cell::new(now.as_micros()),
+        }
+    }
+
+    pub fn set(&self, now: Instant) {
+        self.now.set(now.as_micros());
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by.as_micros() as u64);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.now.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_moves_only_when_told() {
+        let clock = MockClock::new(Instant::from_micros(1_000));
+        assert_eq!(clock.now(), Instant::from_micros(1_000));
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(clock.now(), Instant::from_micros(6_000));
+        clock.set(Instant::from_micros(42));
+        assert_eq!(clock.now(), Instant::from_micros(42));
+    }
+
+    #[test]
+    fn duration_since_saturates() {
+        let earlier = Instant::from_micros(1_000);
+        let later = earlier + Duration::from_millis(2);
+        assert_eq!(later.duration_since(earlier), Duration::from_millis(2));
+        assert_eq!(earlier.duration_since(later), Duration::ZERO);
+    }
+}
@@ -0,0 +1,170 @@
+use core::fmt;
+
+use armbot_core::storage::StorageError;
+use servo_driver::ServoError;
+
+use crate::settings::SettingsError;
+
+/// Simple error type for no_std environment
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    Adc,
+    /// Transfer to an I2C device failed.
+    I2c,
+    Servo(ServoError),
+    /// Joint with the specified name stopped responding and was disabled.
+    JointFaulted(&'static str),
+    /// Joint with the specified name is overloaded, e.g. the gripper closed on an object.
+    Stalled(&'static str),
+    Settings(SettingsError),
+    /// Flash with the stored files can't be accessed.
+    Storage,
+    /// Pin can't be used for the part connected to it.
+    InvalidPin {
+        name: &'static str,
+        gpio: u8,
+        reason: &'static str,
+    },
+    /// Script has a bad line, lines are numbered from 1.
+    Script {
+        line: usize,
+        reason: &'static str,
+    },
+    /// Range with equal bounds where a non empty one is required.
+    DegenerateRange,
+    /// Value can't be converted, e.g. a pulse longer than the PWM period.
+    OutOfRange(&'static str),
+    Other(&'static str),
+}
+
+impl From<ServoError> for Error {
+    fn from(err: ServoError) -> Self {
+        Error::Servo(err)
+    }
+}
+
+impl From<SettingsError> for Error {
+    fn from(err: SettingsError) -> Self {
+        Error::Settings(err)
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::Flash => Error::Storage,
+            StorageError::NoSuchFile => Error::OutOfRange("no such file"),
+            StorageError::TooLarge => Error::OutOfRange("file is too large"),
+            StorageError::Corrupt => Error::Other("stored file is corrupt"),
+            StorageError::Wire(_) => Error::Other("stored file doesn't match its type"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Adc => write!(f, "ADC read failed"),
+            Error::I2c => write!(f, "I2C transfer failed"),
+            Error::Servo(err) => write!(f, "servo error: {err:?}"),
+            Error::JointFaulted(name) => write!(f, "{name} joint is faulted"),
+            Error::Stalled(name) => write!(f, "{name} joint is stalled"),
+            Error::Settings(err) => write!(f, "bad settings: {err:?}"),
+            Error::Storage => write!(f, "flash access failed"),
+            Error::InvalidPin { name, gpio, reason } => {
+                write!(f, "{name} can't use GPIO{gpio}: {reason}")
+            }
+            Error::Script { line, reason } => write!(f, "script line {line}: {reason}"),
+            Error::DegenerateRange => write!(f, "range is empty"),
+            Error::OutOfRange(msg) => write!(f, "out of range: {msg}"),
+            Error::Other(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl Error {
+    /// Wraps an error of a servo backend, see [`servo_driver::ServoDriver::Error`].
+    pub fn servo(err: impl Into<ServoError>) -> Self {
+        Error::Servo(err.into())
+    }
+
+    /// Kind of the error shared with the libraries and host tools.
+    pub fn kind(&self) -> armbot_core::Error {
+        use armbot_core::Error as Kind;
+
+        match self {
+            Error::Adc => Kind::Adc,
+            Error::I2c => Kind::Comms,
+            Error::Servo(ServoError::LimitReached { .. }) => Kind::Limit,
+            Error::Servo(_) | Error::JointFaulted(_) | Error::Stalled(_) => Kind::Servo,
+            Error::Settings(_)
+            | Error::Storage
+            | Error::InvalidPin { .. }
+            | Error::Script { .. } => Kind::Config,
+            Error::DegenerateRange | Error::OutOfRange(_) => Kind::Limit,
+            Error::Other(_) => Kind::Other,
+        }
+    }
+}
+
+/// Max number of context messages kept by [`Report`].
+const MAX_CONTEXT: usize = 4;
+
+/// Error with a short chain of static context messages.
+///
+/// Lightweight no_std counterpart of `eyre::Report`: displayed as `outer: inner: error`.
+/// Context added beyond [`MAX_CONTEXT`] levels is dropped.
+#[derive(Debug, Clone)]
+pub struct Report {
+    error: Error,
+    /// Context from the innermost to the outermost.
+    context: [&'static str; MAX_CONTEXT],
+    depth: usize,
+}
+
+impl Report {
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    fn push(mut self, context: &'static str) -> Self {
+        if self.depth < MAX_CONTEXT {
+            self.context[self.depth] = context;
+            self.depth += 1;
+        }
+        self
+    }
+}
+
+impl<E: Into<Error>> From<E> for Report {
+    fn from(err: E) -> Self {
+        Report {
+            error: err.into(),
+            context: [""; MAX_CONTEXT],
+            depth: 0,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for context in self.context[..self.depth].iter().rev() {
+            write!(f, "{context}: ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Attaches context to errors, implemented by whatever error reporting the build uses.
+pub trait Context<T> {
+    fn context(self, context: &'static str) -> Result<T, Report>;
+}
+
+impl<T, E: Into<Report>> Context<T> for Result<T, E> {
+    fn context(self, context: &'static str) -> Result<T, Report> {
+        self.map_err(|err| err.into().push(context))
+    }
+}
@@ -0,0 +1,729 @@
+use core::{ops::Range, time::Duration};
+
+use log::{info, warn};
+
+use crate::{
+    clock::Clock,
+    error::Error,
+    util::{
+        self,
+        debounce::DebounceMode,
+        filter::{Ema, MedianFilter},
+        Scalar,
+    },
+};
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadConfig {
+    /// Ranges of the axes, indexed by [`Axis`].
+    pub axes: [AxisConfig; AXES],
+
+    /// If set to true, then real center position will be read from the joystick at the start.
+    pub use_real_center: bool,
+
+    /// ADC samples taken per read of an axis.
+    pub oversampling: Oversampling,
+    /// Weight of a new read in the exponential moving average of an axis, in `0.0..=1.0`.
+    /// Lower is smoother but lags more, no averaging if unset.
+    pub ema_alpha: Option<f32>,
+
+    /// When a button press or release is accepted, reads happen every control period.
+    pub button_debounce: DebounceMode,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            axes: [AxisConfig::default(); AXES],
+            use_real_center: true,
+            oversampling: Oversampling::Off,
+            ema_alpha: None,
+            button_debounce: DebounceMode::Count(3),
+        }
+    }
+}
+
+impl GamepadConfig {
+    /// Checks the values that can't be fixed by calibration.
+    pub fn validate(&self) -> Result<(), Error> {
+        for axis in &self.axes {
+            axis.validate()?;
+        }
+        let samples = self.oversampling.samples();
+        if samples == 0 || samples > MAX_OVERSAMPLING {
+            return Err(Error::OutOfRange("oversampling must take 1..=16 samples"));
+        }
+        if let Some(alpha) = self.ema_alpha {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(Error::OutOfRange("EMA alpha must be within 0..=1"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Max samples of [`Oversampling`].
+pub const MAX_OVERSAMPLING: usize = 16;
+
+/// How several ADC samples of a read are reduced to one value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Oversampling {
+    /// Single sample per read.
+    #[default]
+    Off,
+    /// Mean of the samples, lowers noise.
+    Mean(u8),
+    /// Median of the samples, also drops spikes.
+    Median(u8),
+}
+
+impl Oversampling {
+    fn samples(self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::Mean(n) | Oversampling::Median(n) => n as usize,
+        }
+    }
+
+    /// Reduces samples to one value, reorders them.
+    fn reduce(self, samples: &mut [u32]) -> u32 {
+        match self {
+            Oversampling::Off => samples[0],
+            Oversampling::Mean(_) => {
+                let sum: u64 = samples.iter().map(|val| *val as u64).sum();
+                (sum / samples.len() as u64) as u32
+            }
+            Oversampling::Median(_) => {
+                samples.sort_unstable();
+                samples[samples.len() / 2]
+            }
+        }
+    }
+}
+
+/// Range and deadzone of a gamepad axis, in raw values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisConfig {
+    /// Min value of the axis.
+    pub min_value: u32,
+    /// Max value of the axis.
+    pub max_value: u32,
+
+    /// Defines center of the axis as offsets from its rest position.
+    /// `min value = center - center_offset`
+    /// `max value = center + center_offset`
+    pub center_offset: u32,
+
+    /// Rest position, the middle of `0..=max_value` if unset.
+    /// Measured by [`Gamepad::calibrate`].
+    pub center: Option<u32>,
+
+    /// Maps deflection from the center range to the output.
+    pub curve: ResponseCurve,
+}
+
+impl Default for AxisConfig {
+    fn default() -> Self {
+        Self {
+            min_value: 10,
+            max_value: 2757,
+            center_offset: 50,
+            center: None,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+
+impl AxisConfig {
+    /// Checks that the center range fits into the axis range, at the set center or at
+    /// the middle of `0..=max_value`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.min_value >= self.max_value {
+            return Err(Error::OutOfRange(
+                "axis min value must be less than max value",
+            ));
+        }
+        if self.center_offset >= (self.max_value - self.min_value) / 2 {
+            return Err(Error::OutOfRange("center offset is wider than axis range"));
+        }
+        let center = self.center.unwrap_or(self.max_value / 2);
+        if center < self.min_value + self.center_offset
+            || center + self.center_offset > self.max_value
+        {
+            return Err(Error::OutOfRange(
+                "axis center range must be within the axis range",
+            ));
+        }
+        self.curve.validate()
+    }
+
+    /// Sets offset `[center-offset, center+offset]` that will be considered as center.
+    /// A measured center near the end of the axis gets a narrower range on that side.
+    pub(crate) fn center_range(&self, center: u32) -> Range<u32> {
+        center.saturating_sub(self.center_offset)..center.saturating_add(self.center_offset)
+    }
+
+    /// Center range before the real center is read.
+    pub(crate) fn default_center_range(&self) -> Range<u32> {
+        self.center_range(self.center.unwrap_or(self.max_value / 2))
+    }
+}
+
+/// Shape of the stick response: how deflection (0 at the center range, 1 at the end)
+/// maps to the output range. Curves other than [`ResponseCurve::Linear`] give fine control
+/// near the center and full speed at the ends.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// `(e^(k*x) - 1) / (e^k - 1)` with the rate `k`, higher is flatter near the center.
+    Exponential(f32),
+    /// `x^3`.
+    Cubic,
+    /// `x^n` with a custom exponent, above 1 is flatter near the center.
+    Power(f32),
+}
+
+impl ResponseCurve {
+    /// Checks that the curve maps `0..=1` onto itself.
+    pub fn validate(&self) -> Result<(), Error> {
+        match *self {
+            ResponseCurve::Exponential(k) | ResponseCurve::Power(k)
+                if !(k.is_finite() && k > 0.0) =>
+            {
+                Err(Error::Other("response curve parameter must be positive"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Maps the deflection `0..=1` to `0..=1`.
+    fn apply(self, x: f64) -> f64 {
+        match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::Exponential(k) => {
+                let k = k as f64;
+                libm::expm1(k * x) / libm::expm1(k)
+            }
+            ResponseCurve::Cubic => x * x * x,
+            ResponseCurve::Power(n) => libm::pow(x, n as f64),
+        }
+    }
+
+    /// Maps the deflection `0..=1` to the output range, rounded.
+    fn output(self, deflection: f64, output: &Range<u32>) -> u32 {
+        let span = output.end as f64 - output.start as f64;
+        u32::from_f64_rounded(output.start as f64 + self.apply(deflection) * span)
+    }
+}
+
+pub trait Gamepad {
+    /// Returns raw values of joystick.
+    fn read_raw_state(&mut self) -> Result<RawState, Error>;
+
+    /// Returns state of joystick mapped to the specified output range.
+    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error>;
+
+    /// Measures the range of every axis and uses it from now on, returns the new axis configs.
+    ///
+    /// Guides through the steps in the log: sticks are left at rest for `hold`,
+    /// then moved around to their ends for `hold`. Axes that weren't moved
+    /// keep their min and max values.
+    fn calibrate(
+        &mut self,
+        clock: &impl Clock,
+        hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error>;
+
+    /// Reads the state and returns what changed since `last`, which is updated to the new state.
+    ///
+    /// Held sticks produce no events, the current positions are in `last`.
+    fn poll_events(&mut self, output: &Range<u32>, last: &mut State) -> Result<Events, Error> {
+        let state = self.read_state(output)?;
+        let mut events = Events::default();
+        for (idx, position) in state.axes.iter().enumerate() {
+            if *position != last.axes[idx] {
+                events.push(Event::AxisMoved(Axis::ALL[idx], position.clone()));
+            }
+        }
+        for (idx, pressed) in state.buttons.into_iter().enumerate() {
+            match (last.buttons[idx], pressed) {
+                (false, true) => events.push(Event::Pressed(Button::ALL[idx])),
+                (true, false) => events.push(Event::Released(Button::ALL[idx])),
+                _ => {}
+            }
+        }
+        *last = state;
+        Ok(events)
+    }
+
+    /// Returns true while a wireless gamepad sends no input, e.g. out of range or
+    /// disconnected. The last state it read is kept, so it must not drive the arm.
+    fn is_stale(&self) -> bool {
+        false
+    }
+}
+
+/// Change of the gamepad input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Pressed(Button),
+    Released(Button),
+    /// Axis got to a new position.
+    AxisMoved(Axis, Position),
+}
+
+/// Events of a single poll, at most one per axis and button.
+#[derive(Debug, Clone, Default)]
+pub struct Events {
+    events: [Option<Event>; AXES + BUTTONS],
+    len: usize,
+}
+
+impl Events {
+    fn push(&mut self, event: Event) {
+        self.events[self.len] = Some(event);
+        self.len += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events[..self.len].iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Number of gamepad axes.
+pub const AXES: usize = 4;
+
+/// Gamepad axis, its value is an index in the state arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Axis {
+    BaseRotator = 0,
+    Shoulder = 1,
+    Elbow = 2,
+    Gripper = 3,
+}
+
+impl Axis {
+    /// All axes in the order of their indices.
+    pub const ALL: [Axis; AXES] = [
+        Axis::BaseRotator,
+        Axis::Shoulder,
+        Axis::Elbow,
+        Axis::Gripper,
+    ];
+
+    /// Name of the joint the axis drives by default.
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::BaseRotator => "base",
+            Axis::Shoulder => "shoulder",
+            Axis::Elbow => "elbow",
+            Axis::Gripper => "gripper",
+        }
+    }
+}
+
+/// Number of gamepad buttons.
+pub const BUTTONS: usize = 4;
+
+/// Gamepad button, its value is an index in the state arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Button {
+    /// Push switch of the first joystick.
+    Stick1 = 0,
+    /// Push switch of the second joystick.
+    Stick2 = 1,
+    /// Extra button wired to a GPIO.
+    Aux1 = 2,
+    /// Extra button wired to a GPIO.
+    Aux2 = 3,
+}
+
+impl Button {
+    /// All buttons in the order of their indices.
+    pub const ALL: [Button; BUTTONS] = [Button::Stick1, Button::Stick2, Button::Aux1, Button::Aux2];
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawState {
+    /// Raw values indexed by [`Axis`].
+    pub axes: [u32; AXES],
+    /// Debounced buttons indexed by [`Button`], true while pressed.
+    pub buttons: [bool; BUTTONS],
+}
+
+impl RawState {
+    pub fn axis(&self, axis: Axis) -> u32 {
+        self.axes[axis as usize]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct State {
+    /// Positions indexed by [`Axis`].
+    pub axes: [Position; AXES],
+    /// Buttons indexed by [`Button`], true while pressed.
+    pub buttons: [bool; BUTTONS],
+}
+
+impl State {
+    /// Maps raw values to positions within the output range.
+    pub(crate) fn from_raw(
+        raw: &RawState,
+        config: &GamepadConfig,
+        centers: &[Range<u32>; AXES],
+        output: &Range<u32>,
+    ) -> Result<Self, Error> {
+        let mut state = Self {
+            buttons: raw.buttons,
+            ..Self::default()
+        };
+        for (idx, position) in state.axes.iter_mut().enumerate() {
+            *position = Position::new(raw.axes[idx], &config.axes[idx], &centers[idx], output)?;
+        }
+        Ok(state)
+    }
+
+    pub fn axis(&self, axis: Axis) -> &Position {
+        &self.axes[axis as usize]
+    }
+
+    pub fn is_center(&self) -> bool {
+        self.axes.iter().all(|pos| *pos == Position::Center)
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.buttons[button as usize]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Position {
+    Low(u32),
+    #[default]
+    Center,
+    High(u32),
+}
+
+impl Position {
+    /// Same deflection in the other direction.
+    pub fn reversed(&self) -> Position {
+        match self {
+            Position::Low(step) => Position::High(*step),
+            Position::Center => Position::Center,
+            Position::High(step) => Position::Low(*step),
+        }
+    }
+
+    /// Deflection with the step scaled, a step that rounds to zero is centered.
+    pub fn scaled(&self, scale: f32) -> Position {
+        let scale = |step: u32| (step as f32 * scale + 0.5) as u32;
+        match self {
+            Position::Low(step) if scale(*step) > 0 => Position::Low(scale(*step)),
+            Position::High(step) if scale(*step) > 0 => Position::High(scale(*step)),
+            _ => Position::Center,
+        }
+    }
+
+    fn new(
+        val: u32,
+        config: &AxisConfig,
+        center_range: &Range<u32>,
+        output: &Range<u32>,
+    ) -> Result<Self, Error> {
+        let position = if center_range.contains(&val) {
+            Position::Center
+        } else if val < center_range.start {
+            // the further from the center, the bigger the step
+            let deflection = util::rescale(
+                val as f64,
+                center_range.start as f64,
+                config.min_value as f64,
+                0.0,
+                1.0,
+            )?;
+            Position::Low(config.curve.output(deflection, output))
+        } else {
+            let deflection = util::rescale(
+                val as f64,
+                center_range.end as f64,
+                config.max_value as f64,
+                0.0,
+                1.0,
+            )?;
+            Position::High(config.curve.output(deflection, output))
+        };
+        Ok(position)
+    }
+}
+
+/// Filters, limits and centers raw readings of the axes, shared by the analog gamepads.
+pub struct AxisReader {
+    config: GamepadConfig,
+    /// Center ranges indexed by [`Axis`].
+    centers: [Range<u32>; AXES],
+    /// Drop single ADC spikes, indexed by [`Axis`].
+    spike_filters: [MedianFilter<3>; AXES],
+    /// Smooth the noise, indexed by [`Axis`].
+    emas: [Option<Ema>; AXES],
+}
+
+impl AxisReader {
+    pub fn new(config: GamepadConfig) -> Result<Self, Error> {
+        config.validate()?;
+        let centers = config.axes.map(|axis| axis.default_center_range());
+        let emas = core::array::from_fn(|_| config.ema_alpha.map(Ema::new));
+        Ok(Self {
+            config,
+            centers,
+            spike_filters: Default::default(),
+            emas,
+        })
+    }
+
+    pub fn config(&self) -> &GamepadConfig {
+        &self.config
+    }
+
+    /// Takes the current position as the center if the config asks for it.
+    /// `sample` takes a single reading of every axis.
+    pub fn init_centers(
+        &mut self,
+        sample: impl FnMut() -> Result<[u32; AXES], Error>,
+    ) -> Result<(), Error> {
+        if self.config.use_real_center {
+            // read and store center position
+            let real_positions = self.read(sample)?;
+            for (idx, real) in real_positions.into_iter().enumerate() {
+                self.centers[idx] = self.config.axes[idx].center_range(real);
+            }
+        }
+        info!("centers={:?}", self.centers);
+        Ok(())
+    }
+
+    /// Reads all axes through the filters, limited to their ranges.
+    pub fn read(
+        &mut self,
+        sample: impl FnMut() -> Result<[u32; AXES], Error>,
+    ) -> Result<[u32; AXES], Error> {
+        let raw = self.read_filtered(sample)?;
+        Ok(core::array::from_fn(|idx| {
+            let axis = &self.config.axes[idx];
+            raw[idx].clamp(axis.min_value, axis.max_value)
+        }))
+    }
+
+    /// Maps the raw state to positions within the output range.
+    pub fn state(&self, raw: &RawState, output: &Range<u32>) -> Result<State, Error> {
+        State::from_raw(raw, &self.config, &self.centers, output)
+    }
+
+    /// See [`Gamepad::calibrate`]. `sample` paces the calibration, it should wait a bit
+    /// before reading, as reads are much faster than sticks move.
+    pub fn calibrate(
+        &mut self,
+        clock: &impl Clock,
+        hold: Duration,
+        mut sample: impl FnMut() -> Result<[u32; AXES], Error>,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        let axes = self.config.axes;
+        let axes = calibrate_axes(clock, hold, axes, || self.read_filtered(&mut sample))?;
+
+        self.config.axes = axes;
+        self.centers = axes.map(|axis| axis.default_center_range());
+        info!("calibration done: {:?}", axes);
+        Ok(axes)
+    }
+
+    /// Reads all axes through the filters, values aren't limited to the configured range.
+    fn read_filtered(
+        &mut self,
+        mut sample: impl FnMut() -> Result<[u32; AXES], Error>,
+    ) -> Result<[u32; AXES], Error> {
+        let oversampling = self.config.oversampling;
+        let samples = oversampling.samples();
+        let mut buf = [[0; MAX_OVERSAMPLING]; AXES];
+        for idx in 0..samples {
+            for (axis, val) in buf.iter_mut().zip(sample()?) {
+                axis[idx] = val;
+            }
+        }
+
+        Ok(core::array::from_fn(|idx| {
+            let val = oversampling.reduce(&mut buf[idx][..samples]);
+            let val = self.spike_filters[idx].push(val);
+            match &mut self.emas[idx] {
+                Some(ema) => ema.push(val),
+                None => val,
+            }
+        }))
+    }
+}
+
+/// Runs the calibration steps on unlimited raw values returned by `read`,
+/// returns `axes` with the measured values.
+fn calibrate_axes(
+    clock: &impl Clock,
+    hold: Duration,
+    mut axes: [AxisConfig; AXES],
+    mut read: impl FnMut() -> Result<[u32; AXES], Error>,
+) -> Result<[AxisConfig; AXES], Error> {
+    info!("calibration: leave the sticks at rest");
+    let mut sums = [0u64; AXES];
+    let mut samples = 0;
+    let start = clock.now();
+    while clock.now().duration_since(start) < hold {
+        for (sum, val) in sums.iter_mut().zip(read()?) {
+            *sum += val as u64;
+        }
+        samples += 1;
+    }
+    if samples == 0 {
+        return Err(Error::Other("calibration took no samples"));
+    }
+    let centers = sums.map(|sum| (sum / samples) as u32);
+
+    info!("calibration: move the sticks around to their ends");
+    let (mut mins, mut maxs) = (centers, centers);
+    let start = clock.now();
+    while clock.now().duration_since(start) < hold {
+        for (idx, val) in read()?.into_iter().enumerate() {
+            mins[idx] = mins[idx].min(val);
+            maxs[idx] = maxs[idx].max(val);
+        }
+    }
+
+    for (idx, axis) in axes.iter_mut().enumerate() {
+        let center = centers[idx];
+        // an axis must go well past its center range both ways to count as moved
+        let margin = 2 * axis.center_offset;
+        if center - mins[idx] > margin && maxs[idx] - center > margin {
+            axis.min_value = mins[idx];
+            axis.max_value = maxs[idx];
+        } else {
+            warn!("calibration: axis {idx} wasn't moved, keeping its range");
+        }
+        if center < axis.min_value + axis.center_offset
+            || center + axis.center_offset > axis.max_value
+        {
+            return Err(Error::Other("joystick rests too close to its end"));
+        }
+        axis.center = Some(center);
+    }
+    Ok(axes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    const HOLD: Duration = Duration::from_secs(1);
+    const SAMPLE_PERIOD: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn axis_config_is_checked() {
+        assert!(AxisConfig::default().validate().is_ok());
+        let bad = [
+            AxisConfig {
+                min_value: 2000,
+                max_value: 100,
+                ..AxisConfig::default()
+            },
+            AxisConfig {
+                center_offset: 2000,
+                ..AxisConfig::default()
+            },
+            AxisConfig {
+                center: Some(20),
+                ..AxisConfig::default()
+            },
+            AxisConfig {
+                min_value: 2000,
+                max_value: 2700,
+                ..AxisConfig::default()
+            },
+            AxisConfig {
+                curve: ResponseCurve::Power(0.0),
+                ..AxisConfig::default()
+            },
+        ];
+        for axis in bad {
+            assert!(axis.validate().is_err(), "{axis:?}");
+            let mut config = GamepadConfig::default();
+            config.axes[1] = axis;
+            assert!(AxisReader::new(config).is_err(), "{axis:?}");
+        }
+        // a stick resting near its end doesn't underflow the center range
+        assert_eq!(AxisConfig::default().center_range(20), 0..70);
+    }
+
+    #[test]
+    fn scaled_steps_round_and_center() {
+        assert_eq!(Position::High(10).scaled(0.25), Position::High(3));
+        assert_eq!(Position::Low(10).scaled(2.0), Position::Low(20));
+        assert_eq!(Position::High(1).scaled(0.5), Position::High(1));
+        assert_eq!(Position::Low(1).scaled(0.4), Position::Center);
+        assert_eq!(Position::Center.scaled(10.0), Position::Center);
+    }
+
+    #[test]
+    fn calibration_takes_its_time_from_the_clock() {
+        let clock = MockClock::default();
+        let mut reader = AxisReader::new(GamepadConfig::default()).unwrap();
+        let mut samples = 0u32;
+        let axes = reader
+            .calibrate(&clock, HOLD, || {
+                clock.advance(SAMPLE_PERIOD);
+                samples += 1;
+                // at rest for the first hold, then the base stick is held at both ends
+                let base = match samples {
+                    101..=120 => 200,
+                    121..=140 => 2600,
+                    _ => 1400,
+                };
+                Ok([base, 1300, 1400, 1500])
+            })
+            .unwrap();
+
+        // a sample per period over both steps
+        assert_eq!(samples, 200);
+        assert_eq!(axes[0].center, Some(1400));
+        assert_eq!(axes[0].min_value, 200);
+        assert_eq!(axes[0].max_value, 2600);
+        // axes that weren't moved keep their range
+        assert_eq!(axes[1].center, Some(1300));
+        assert_eq!(axes[1].min_value, AxisConfig::default().min_value);
+        assert_eq!(axes[1].max_value, AxisConfig::default().max_value);
+    }
+
+    #[test]
+    fn calibration_fails_on_a_stick_resting_at_its_end() {
+        let clock = MockClock::default();
+        let mut reader = AxisReader::new(GamepadConfig::default()).unwrap();
+        let result = reader.calibrate(&clock, HOLD, || {
+            clock.advance(SAMPLE_PERIOD);
+            Ok([1400, 20, 1400, 1400])
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Minimal G-code dialect for scripted arm motion.
+//!
+//! | Code            | What it does                                                   |
+//! |-----------------|----------------------------------------------------------------|
+//! | `G0 A90 B45`    | Moves joints at the rapid speed                                |
+//! | `G1 A90 F600`   | Moves joints at the feed rate in °/min, the rate is kept       |
+//! | `G4 P500`       | Waits 500 ms, `S` gives seconds                                |
+//! | `G90` / `G91`   | Joint words are absolute / relative to the last move           |
+//! | `M3` / `M5`     | Closes / opens the gripper                                     |
+//! | `M112`          | Emergency stop                                                 |
+//!
+//! Joint words are `A`, `B`, `C`, `U`, `V`, `W` in the joint order of the arm.
+//! `N` line numbers and comments in `;` or `( )` are ignored.
+//! Cartesian `X`, `Y`, `Z` moves need kinematics of the arm and are rejected.
+
+use core::time::Duration;
+
+use crate::{armbot::Action, error::Error, gamepad::Axis, units::Degrees};
+
+/// Words of the joints, in the joint order of the arm.
+pub const JOINT_WORDS: [u8; 6] = *b"ABCUVW";
+
+/// Speeds of the moves.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterpreterConfig {
+    /// Speed of `G0` in °/s.
+    pub rapid_speed: f32,
+    /// Speed of `G1` until a program sets `F`, in °/s.
+    pub default_feed: f32,
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        Self {
+            rapid_speed: 90.0,
+            default_feed: 30.0,
+        }
+    }
+}
+
+/// What a line asks the arm to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion<const N: usize> {
+    /// Moves the joints to the angles in `duration`, all joints arrive together.
+    Move {
+        targets: [Degrees; N],
+        duration: Duration,
+    },
+    /// Holds the arm still.
+    Dwell(Duration),
+    /// Runs the action, e.g. moves the gripper.
+    Action(Action),
+}
+
+/// Executes G-code line by line, keeping the modal state between lines.
+#[derive(Debug, Clone)]
+pub struct Interpreter<const N: usize> {
+    config: InterpreterConfig,
+    /// Joint angles at the end of the last move.
+    position: [Degrees; N],
+    /// Set by `G91`.
+    relative: bool,
+    /// Speed of `G1` in °/s.
+    feed: f32,
+}
+
+impl<const N: usize> Interpreter<N> {
+    /// Starts at the joint angles of the arm, moves are computed from them.
+    pub fn new(config: InterpreterConfig, position: [Degrees; N]) -> Self {
+        let feed = config.default_feed;
+        Self {
+            config,
+            position,
+            relative: false,
+            feed,
+        }
+    }
+
+    /// Joint angles at the end of the last move.
+    pub fn position(&self) -> &[Degrees; N] {
+        &self.position
+    }
+
+    /// Computes the next moves from the angles, e.g. after the arm was moved by the sticks.
+    pub fn set_position(&mut self, position: [Degrees; N]) {
+        self.position = position;
+    }
+
+    /// Executes a line, returns the motion it asks for.
+    /// Lines with only modal codes or comments return `None`.
+    pub fn execute(&mut self, line: &str) -> Result<Option<Motion<N>>, Error> {
+        let words = Words::parse(line)?;
+        if words.any(b"XYZ") {
+            return Err(Error::Other("cartesian moves aren't supported"));
+        }
+        if let Some(feed) = words.get(b'F') {
+            if feed <= 0.0 {
+                return Err(Error::OutOfRange("feed rate must be positive"));
+            }
+            self.feed = feed / 60.0;
+        }
+
+        match (words.code(b'G')?, words.code(b'M')?) {
+            (Some(_), Some(_)) => Err(Error::Other("G and M codes in the same line")),
+            (Some(0), None) => self.move_joints(&words, self.config.rapid_speed),
+            (Some(1), None) => self.move_joints(&words, self.feed),
+            (Some(4), None) => {
+                let secs = match (words.get(b'P'), words.get(b'S')) {
+                    (Some(ms), None) => ms / 1000.0,
+                    (None, Some(secs)) => secs,
+                    _ => return Err(Error::Other("dwell needs either P or S")),
+                };
+                let time = Duration::try_from_secs_f32(secs)
+                    .map_err(|_| Error::OutOfRange("dwell time"))?;
+                Ok(Some(Motion::Dwell(time)))
+            }
+            (Some(90), None) => {
+                self.relative = false;
+                Ok(None)
+            }
+            (Some(91), None) => {
+                self.relative = true;
+                Ok(None)
+            }
+            (None, Some(3)) => Ok(Some(Motion::Action(Action::JointToMin(Axis::Gripper)))),
+            (None, Some(5)) => Ok(Some(Motion::Action(Action::JointToMax(Axis::Gripper)))),
+            (None, Some(112)) => Ok(Some(Motion::Action(Action::EmergencyStop))),
+            (None, None) if words.any(&JOINT_WORDS) => {
+                Err(Error::Other("joint words without a move code"))
+            }
+            (None, None) => Ok(None),
+            _ => Err(Error::Other("unsupported G or M code")),
+        }
+    }
+
+    /// Moves the joints with words in the line, the slowest joint moves at `speed` in °/s.
+    fn move_joints(&mut self, words: &Words, speed: f32) -> Result<Option<Motion<N>>, Error> {
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(Error::OutOfRange("move speed must be positive"));
+        }
+        let mut targets = self.position;
+        for (idx, &word) in JOINT_WORDS.iter().enumerate() {
+            let Some(val) = words.get(word) else {
+                continue;
+            };
+            let target = targets
+                .get_mut(idx)
+                .ok_or(Error::Other("joint word beyond the joints of the arm"))?;
+            *target = if self.relative {
+                *target + Degrees::new(val)
+            } else {
+                Degrees::new(val)
+            };
+        }
+
+        let distance = targets
+            .iter()
+            .zip(&self.position)
+            .map(|(target, start)| (*target - *start).get().abs())
+            .fold(0.0, f32::max);
+        let duration = Duration::try_from_secs_f32(distance / speed)
+            .map_err(|_| Error::OutOfRange("move duration"))?;
+        self.position = targets;
+        Ok(Some(Motion::Move { targets, duration }))
+    }
+}
+
+/// Values of the words in a line, indexed by the letter.
+struct Words([Option<f32>; 26]);
+
+impl Words {
+    fn parse(line: &str) -> Result<Self, Error> {
+        let mut words = [None; 26];
+        let mut rest = line.as_bytes();
+        loop {
+            rest = skip_comments(rest)?;
+            let Some((&letter, tail)) = rest.split_first() else {
+                return Ok(Self(words));
+            };
+            let letter = letter.to_ascii_uppercase();
+            if !letter.is_ascii_uppercase() {
+                return Err(Error::Other("expected a word letter"));
+            }
+
+            let len = tail
+                .iter()
+                .position(|ch| !matches!(ch, b'0'..=b'9' | b'.' | b'+' | b'-'))
+                .unwrap_or(tail.len());
+            let (number, tail) = tail.split_at(len);
+            // only ASCII digits and signs were taken
+            let number = core::str::from_utf8(number).unwrap_or_default();
+            let val: f32 = number
+                .parse()
+                .map_err(|_| Error::Other("word without a valid number"))?;
+            if !val.is_finite() {
+                return Err(Error::OutOfRange("word value"));
+            }
+
+            let slot = &mut words[(letter - b'A') as usize];
+            if slot.is_some() {
+                return Err(Error::Other("word repeated in a line"));
+            }
+            // line numbers don't change anything
+            if letter != b'N' {
+                *slot = Some(val);
+            }
+            rest = tail;
+        }
+    }
+
+    fn get(&self, letter: u8) -> Option<f32> {
+        self.0[(letter - b'A') as usize]
+    }
+
+    fn any(&self, letters: &[u8]) -> bool {
+        letters.iter().any(|&letter| self.get(letter).is_some())
+    }
+
+    /// Number of a G or M code, fractional codes aren't supported.
+    fn code(&self, letter: u8) -> Result<Option<u32>, Error> {
+        match self.get(letter) {
+            None => Ok(None),
+            Some(val) if val >= 0.0 && val == (val as u32) as f32 => Ok(Some(val as u32)),
+            Some(_) => Err(Error::Other("unsupported G or M code")),
+        }
+    }
+}
+
+/// Skips whitespace and comments, a `;` comment runs to the end of the line.
+fn skip_comments(mut rest: &[u8]) -> Result<&[u8], Error> {
+    loop {
+        match rest.first() {
+            Some(ch) if ch.is_ascii_whitespace() => rest = &rest[1..],
+            Some(b';') => return Ok(&[]),
+            Some(b'(') => {
+                let end = rest
+                    .iter()
+                    .position(|&ch| ch == b')')
+                    .ok_or(Error::Other("unclosed comment"))?;
+                rest = &rest[end + 1..];
+            }
+            _ => return Ok(rest),
+        }
+    }
+}
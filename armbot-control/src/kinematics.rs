@@ -0,0 +1,253 @@
+//! Kinematics of the base, shoulder and elbow chain, for moving the gripper in straight lines.
+//!
+//! Points are in millimeters from the shoulder axis: `x` points forward at base angle zero,
+//! `y` to the left and `z` up. Joint angles are geometric: the shoulder angle is taken from the
+//! horizontal, the elbow angle from the straight continuation of the upper arm, both grow
+//! upwards. [`JointFrame`] maps them to servo angles.
+
+use libm::{acosf, atan2f, cosf, hypotf, sinf};
+
+use crate::{error::Error, units::Degrees, util::angle};
+
+/// Point of the gripper in millimeters.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+/// How a geometric joint angle maps to the servo angle.
+///
+/// Servo angles are normalized into `0..360`, geometric angles into `-180..=180`,
+/// so a point behind the base maps to the same servo angle from either side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointFrame {
+    /// Servo angle at the geometric angle of zero.
+    pub zero: Degrees,
+    /// Servo angle decreases when the geometric one increases.
+    pub inverted: bool,
+}
+
+impl JointFrame {
+    pub const fn new(zero: Degrees, inverted: bool) -> Self {
+        Self { zero, inverted }
+    }
+
+    fn to_servo(self, angle: f32) -> Degrees {
+        let angle = if self.inverted { -angle } else { angle };
+        Degrees::new(angle::normalize(self.zero.get() + angle))
+    }
+
+    fn joint_angle(self, servo: Degrees) -> f32 {
+        let angle = angle::shortest_diff(self.zero.get(), servo.get());
+        if self.inverted {
+            -angle
+        } else {
+            angle
+        }
+    }
+}
+
+/// Link lengths and servo mounting of the arm.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KinematicsConfig {
+    /// From the shoulder axis to the elbow axis, in millimeters.
+    pub upper_arm: f32,
+    /// From the elbow axis to the gripper tip, in millimeters.
+    pub forearm: f32,
+    pub base: JointFrame,
+    pub shoulder: JointFrame,
+    pub elbow: JointFrame,
+}
+
+impl Default for KinematicsConfig {
+    /// MeArm-like arm: the upper arm is vertical and the forearm horizontal with servos at 90°.
+    fn default() -> Self {
+        Self {
+            upper_arm: 80.0,
+            forearm: 80.0,
+            base: JointFrame::new(Degrees::from_whole(90), false),
+            shoulder: JointFrame::new(Degrees::ZERO, false),
+            elbow: JointFrame::new(Degrees::from_whole(180), false),
+        }
+    }
+}
+
+/// Servo angles of the chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainAngles {
+    pub base: Degrees,
+    pub shoulder: Degrees,
+    pub elbow: Degrees,
+}
+
+impl KinematicsConfig {
+    /// Checks that the links have a length.
+    pub fn validate(&self) -> Result<(), Error> {
+        let valid = |len: f32| len.is_finite() && len > 0.0;
+        if !valid(self.upper_arm) || !valid(self.forearm) {
+            return Err(Error::OutOfRange("link lengths must be positive"));
+        }
+        Ok(())
+    }
+
+    /// Servo angles that put the gripper at the point, with the elbow above the line from
+    /// the shoulder to the point.
+    pub fn inverse(&self, point: Point) -> Result<ChainAngles, Error> {
+        let (l1, l2) = (self.upper_arm, self.forearm);
+        let yaw = atan2f(point.y, point.x);
+        let reach = hypotf(point.x, point.y);
+        let dist_sq = reach * reach + point.z * point.z;
+
+        let cos_elbow = (dist_sq - l1 * l1 - l2 * l2) / (2.0 * l1 * l2);
+        if !(-1.0..=1.0).contains(&cos_elbow) {
+            return Err(Error::OutOfRange("point is out of reach"));
+        }
+        // bending down keeps the elbow up
+        let elbow = -acosf(cos_elbow);
+        let shoulder = atan2f(point.z, reach) - atan2f(l2 * sinf(elbow), l1 + l2 * cosf(elbow));
+
+        Ok(ChainAngles {
+            base: self.base.to_servo(yaw.to_degrees()),
+            shoulder: self.shoulder.to_servo(shoulder.to_degrees()),
+            elbow: self.elbow.to_servo(elbow.to_degrees()),
+        })
+    }
+
+    /// Point of the gripper at the servo angles.
+    pub fn forward(&self, angles: &ChainAngles) -> Point {
+        let yaw = self.base.joint_angle(angles.base).to_radians();
+        let shoulder = self.shoulder.joint_angle(angles.shoulder).to_radians();
+        let elbow = self.elbow.joint_angle(angles.elbow).to_radians();
+
+        let reach = self.upper_arm * cosf(shoulder) + self.forearm * cosf(shoulder + elbow);
+        let z = self.upper_arm * sinf(shoulder) + self.forearm * sinf(shoulder + elbow);
+        Point::new(reach * cosf(yaw), reach * sinf(yaw), z)
+    }
+}
+
+/// Space the gripper must stay in, on top of the angle ranges of the joints.
+///
+/// The base rotates around the `z` axis, so the base is a cylinder around it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkspaceLimits {
+    /// Height of the table plane, the gripper stays above it.
+    pub floor: f32,
+    /// Radius of the base around the `z` axis, the gripper stays out of it.
+    pub base_radius: f32,
+    /// Height of the top of the base, the gripper can pass over it.
+    pub base_top: f32,
+}
+
+impl Default for WorkspaceLimits {
+    /// Shoulder axis 40 mm above the table, on top of a base of 30 mm radius.
+    fn default() -> Self {
+        Self {
+            floor: -40.0,
+            base_radius: 30.0,
+            base_top: 0.0,
+        }
+    }
+}
+
+impl WorkspaceLimits {
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.floor.is_finite() || !self.base_top.is_finite() {
+            return Err(Error::OutOfRange("workspace heights must be finite"));
+        }
+        if !self.base_radius.is_finite() || self.base_radius < 0.0 {
+            return Err(Error::OutOfRange("base radius can't be negative"));
+        }
+        Ok(())
+    }
+
+    /// Fails if the point is below the table or inside the base.
+    pub fn check(&self, point: Point) -> Result<(), Error> {
+        if point.z < self.floor {
+            return Err(Error::OutOfRange("gripper would go below the table"));
+        }
+        if hypotf(point.x, point.y) < self.base_radius && point.z < self.base_top {
+            return Err(Error::OutOfRange("gripper would hit the base"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(point: Point, expected: Point) {
+        let dist = hypotf(
+            hypotf(point.x - expected.x, point.y - expected.y),
+            point.z - expected.z,
+        );
+        assert!(dist < 0.01, "{point:?} != {expected:?}");
+    }
+
+    #[test]
+    fn servos_at_90_put_the_gripper_in_front() {
+        let config = KinematicsConfig::default();
+        let angles = ChainAngles {
+            base: Degrees::from_whole(90),
+            shoulder: Degrees::from_whole(90),
+            elbow: Degrees::from_whole(90),
+        };
+        assert_near(config.forward(&angles), Point::new(80.0, 0.0, 80.0));
+    }
+
+    #[test]
+    fn inverse_is_undone_by_forward() {
+        let config = KinematicsConfig::default();
+        for point in [
+            Point::new(80.0, 0.0, 80.0),
+            Point::new(100.0, 30.0, 20.0),
+            Point::new(60.0, -50.0, -20.0),
+        ] {
+            let angles = config.inverse(point).unwrap();
+            assert_near(config.forward(&angles), point);
+        }
+    }
+
+    #[test]
+    fn base_angle_wraps_behind_the_arm() {
+        let config = KinematicsConfig {
+            base: JointFrame::new(Degrees::ZERO, false),
+            ..KinematicsConfig::default()
+        };
+        // just left and just right of straight back, atan2 gives about +180 and -180
+        let left = config.inverse(Point::new(-80.0, 0.01, 80.0)).unwrap();
+        let right = config.inverse(Point::new(-80.0, -0.01, 80.0)).unwrap();
+        assert!((left.base.get() - 180.0).abs() < 0.1, "{left:?}");
+        assert!((right.base.get() - 180.0).abs() < 0.1, "{right:?}");
+        assert_near(config.forward(&left), Point::new(-80.0, 0.01, 80.0));
+        assert_near(config.forward(&right), Point::new(-80.0, -0.01, 80.0));
+    }
+
+    #[test]
+    fn point_out_of_reach_fails() {
+        let config = KinematicsConfig::default();
+        assert!(config.inverse(Point::new(200.0, 0.0, 0.0)).is_err());
+        assert!(config.inverse(Point::new(0.0, 120.0, 120.0)).is_err());
+    }
+
+    #[test]
+    fn workspace_keeps_the_gripper_off_the_table_and_the_base() {
+        let limits = WorkspaceLimits::default();
+        assert!(limits.check(Point::new(80.0, 0.0, 0.0)).is_ok());
+        assert!(limits.check(Point::new(80.0, 0.0, -50.0)).is_err());
+        assert!(limits.check(Point::new(10.0, 10.0, -10.0)).is_err());
+        assert!(limits.check(Point::new(10.0, 10.0, 10.0)).is_ok());
+    }
+}
@@ -0,0 +1,26 @@
+//! Logic of the arm that doesn't touch the chip: joints, poses, queued moves, scripts, the
+//! gamepad mapping, settings and the command protocol.
+//!
+//! The firmware in `rust-armbot` drives it with real servos and sticks. [`sim`] and [`replay`]
+//! drive it without hardware, so it builds and is tested on the host as well.
+#![cfg_attr(not(test), no_std)]
+
+use core::time::Duration;
+
+pub mod armbot;
+pub mod clock;
+pub mod error;
+pub mod gamepad;
+pub mod interpreter;
+pub mod kinematics;
+pub mod protocol;
+pub mod replay;
+pub mod script;
+pub mod settings;
+pub mod sim;
+pub mod trajectory;
+pub mod units;
+pub mod util;
+
+/// Period of the control loop, joint speeds and motion profiles are scaled to it.
+pub const CONTROL_PERIOD: Duration = Duration::from_millis(10);
@@ -0,0 +1,709 @@
+//! Line based command protocol, for driving the arm from a PC over the console UART.
+//!
+//! Every line is a command, every command gets a single line reply: `OK` with optional values
+//! or `ERR <code> <message>`. Keywords are case insensitive, joints are numbered from 1.
+//!
+//! | Command                   | Reply                    | What it does                             |
+//! |---------------------------|--------------------------|------------------------------------------|
+//! | `J1 90`                   | `OK`                     | Moves joint 1 to 90°                     |
+//! | `J1 +5`                   | `OK`                     | Moves joint 1 by 5°, `-5` moves it back  |
+//! | `POSE home`               | `OK`                     | Starts moving to the pose, see below     |
+//! | `ANGLES?`                 | `OK 90.00 45.00 20.00`   | Commanded angles of the joints           |
+//! | `STATUS?`                 | `OK stopped=0 faults=0`  | Emergency stop and joint faults          |
+//! | `STOP`                    | `OK`                     | Emergency stop                           |
+//! | `RELEASE`                 | `OK`                     | Releases the emergency stop              |
+//! | `RESET`                   | `OK`                     | Clears joint faults                      |
+//! | `SPEED fast`              | `OK`                     | Sets the speed mode of the sticks        |
+//! | `SPEED?`                  | `OK normal`              | Speed mode of the sticks                 |
+//! | `TELEOP joint`            | `OK`                     | Sets what the sticks move, see below     |
+//! | `TELEOP?`                 | `OK cartesian`           | Teleop mode of the sticks                |
+//! | `RUN`                     | `OK`                     | Runs the script, see [`crate::script`]   |
+//! | `HALT`                    | `OK`                     | Stops the script, the arm stays put      |
+//! | `LOG servo debug`         | `OK`                     | Sets the log level of a module           |
+//! | `LOG?`                    | `OK servo=INFO ...`      | Log levels of the modules                |
+//! | `CALIBRATE`               | `OK`                     | Calibrates the gamepad and stores it     |
+//! | `DEFAULTS`                | `OK`                     | Stores the default settings              |
+//! | `GRIP 300`                | `OK`                     | Closes the gripper up to 300 mA          |
+//! | `GRIP off`                | `OK`                     | Stops gripping, the gripper stays put    |
+//! | `HOME 1`                  | `OK`                     | Drives joint 1 to its end stop           |
+//! | `G1 A90 F600`             | `OK`                     | Queues a G-code move, see below          |
+//! | `OTA BEGIN 1024 1a2b3c4d` | `OK`                     | Starts a firmware update, see below      |
+//! | `OTA 0 e9...`             | `OK`                     | Writes a chunk of the firmware image     |
+//! | `OTA END`                 | `OK`                     | Boots the new image after a reset        |
+//! | `OTA?`                    | `OK 512`                 | Bytes of the image received so far       |
+//!
+//! Poses are `home`, `park`, `pick` and `place`, see [`PoseName`].
+//! Speed modes are `precision`, `normal` and `fast`, see [`SpeedMode`].
+//! Teleop modes are `joint` and `cartesian`, see [`TeleopMode`].
+//! Log levels are `off`, `error`, `warn`, `info`, `debug` and `trace`.
+//! Lines starting with a `G`, `M` or `N` code run on the arm as G-code, see
+//! [`crate::interpreter`] and [`ArmBot::run_gcode`].
+//!
+//! A firmware update starts with the size of the image and its CRC-32 (IEEE) in hex, then the
+//! image follows in hex chunks of up to [`MAX_CHUNK_LEN`] bytes, each with its offset. A chunk
+//! sent again is skipped, so after a lost reply the host asks `OTA?` and goes on from there.
+//!
+//! The logger, the gamepad calibration, the flash, the current sensors and the end stops belong
+//! to the firmware, so its console runs `LOG`, `CALIBRATE`, `DEFAULTS`, `GRIP`, `HOME` and `OTA`.
+//! In the safe mode it runs only the first three.
+
+use core::fmt;
+
+use log::LevelFilter;
+use servo_driver::ServoDriver;
+
+use crate::{
+    armbot::{Action, ArmBot, BaseJoint, PoseName, SpeedMode, TeleopMode},
+    error::Report,
+    gamepad::Gamepad,
+    units::Degrees,
+};
+
+/// Max length of a command line, longer lines are rejected.
+pub const MAX_LINE_LEN: usize = 64;
+
+/// Max bytes in a firmware chunk, its hex fills a line after `OTA` and a 7 digit offset.
+pub const MAX_CHUNK_LEN: usize = 26;
+
+/// Code of an error reply, stays the same between firmware versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Line is too long or isn't UTF-8.
+    BadLine = 1,
+    UnknownCommand = 2,
+    /// Argument is missing, extra or can't be parsed.
+    BadArgument = 3,
+    NoSuchJoint = 4,
+    UnknownPose = 5,
+    /// Arm is stopped, only queries, `STOP` and `RELEASE` are accepted.
+    Stopped = 6,
+    /// Command was accepted, but the arm failed to run it.
+    Failed = 7,
+    UnknownSpeed = 8,
+    UnknownTeleop = 9,
+    UnknownModule = 10,
+    UnknownLevel = 11,
+    /// Command isn't run by this transport or in this mode.
+    Unavailable = 12,
+}
+
+impl ErrorCode {
+    fn message(self) -> &'static str {
+        match self {
+            ErrorCode::BadLine => "bad line",
+            ErrorCode::UnknownCommand => "unknown command",
+            ErrorCode::BadArgument => "bad argument",
+            ErrorCode::NoSuchJoint => "no such joint",
+            ErrorCode::UnknownPose => "unknown pose",
+            ErrorCode::Stopped => "arm is stopped",
+            ErrorCode::Failed => "failed",
+            ErrorCode::UnknownSpeed => "unknown speed mode",
+            ErrorCode::UnknownTeleop => "unknown teleop mode",
+            ErrorCode::UnknownModule => "unknown log module",
+            ErrorCode::UnknownLevel => "unknown log level",
+            ErrorCode::Unavailable => "not available here",
+        }
+    }
+}
+
+/// Why a command wasn't done.
+#[derive(Debug, Clone)]
+pub enum CommandError {
+    /// Command is invalid or not allowed now.
+    Rejected(ErrorCode),
+    /// Arm failed to run the command.
+    Failed(Report),
+}
+
+impl CommandError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CommandError::Rejected(code) => *code,
+            CommandError::Failed(_) => ErrorCode::Failed,
+        }
+    }
+}
+
+impl From<ErrorCode> for CommandError {
+    fn from(code: ErrorCode) -> Self {
+        CommandError::Rejected(code)
+    }
+}
+
+impl From<Report> for CommandError {
+    fn from(report: Report) -> Self {
+        CommandError::Failed(report)
+    }
+}
+
+impl fmt::Display for CommandError {
+    /// Formats the error reply without the line end.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.code();
+        write!(f, "ERR {} {}", code as u8, code.message())?;
+        if let CommandError::Failed(report) = self {
+            write!(f, ": {report}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Successful reply of a command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reply<const N: usize> {
+    Done,
+    Angles([Degrees; N]),
+    Status {
+        stopped: bool,
+        faults: bool,
+    },
+    Speed(SpeedMode),
+    Teleop(TeleopMode),
+    /// Bytes of the firmware image received.
+    Received(u32),
+}
+
+impl<const N: usize> fmt::Display for Reply<N> {
+    /// Formats the `OK` reply without the line end.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OK")?;
+        match self {
+            Reply::Done => Ok(()),
+            Reply::Angles(angles) => {
+                for angle in angles {
+                    write!(f, " {:.2}", angle.get())?;
+                }
+                Ok(())
+            }
+            Reply::Status { stopped, faults } => {
+                write!(f, " stopped={} faults={}", *stopped as u8, *faults as u8)
+            }
+            Reply::Speed(mode) => write!(f, " {}", mode.name()),
+            Reply::Teleop(mode) => write!(f, " {}", mode.name()),
+            Reply::Received(bytes) => write!(f, " {bytes}"),
+        }
+    }
+}
+
+/// Where a joint should move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Absolute(Degrees),
+    /// Offset from the commanded angle.
+    Relative(Degrees),
+}
+
+impl Target {
+    /// Angles with a sign are relative, angles are never negative.
+    fn parse(arg: &str) -> Result<Self, ErrorCode> {
+        let angle = |val: &str| match val.parse::<f32>() {
+            Ok(val) if val.is_finite() => Ok(Degrees::new(val)),
+            _ => Err(ErrorCode::BadArgument),
+        };
+        if let Some(val) = arg.strip_prefix('+') {
+            Ok(Target::Relative(angle(val)?))
+        } else if let Some(val) = arg.strip_prefix('-') {
+            Ok(Target::Relative(Degrees::ZERO - angle(val)?))
+        } else {
+            Ok(Target::Absolute(angle(arg)?))
+        }
+    }
+}
+
+/// Command parsed from a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command<'a> {
+    /// Moves a joint, the index is zero based.
+    MoveJoint {
+        joint: usize,
+        target: Target,
+    },
+    /// Moves the arm to a named pose.
+    Pose(&'a str),
+    Angles,
+    Status,
+    Stop,
+    Release,
+    ResetFaults,
+    SetSpeed(SpeedMode),
+    Speed,
+    SetTeleop(TeleopMode),
+    Teleop,
+    RunScript,
+    HaltScript,
+    /// Sets the log level of the named module.
+    SetLogLevel {
+        module: &'a str,
+        level: LevelFilter,
+    },
+    LogLevels,
+    /// Calibrates the gamepad sticks and stores the calibration.
+    Calibrate,
+    /// Stores the default settings, they're used after reset.
+    ResetSettings,
+    /// Closes the gripper until its current goes over the limit in milliamps,
+    /// `None` stops gripping.
+    Grip(Option<u32>),
+    /// Drives the joint to its min end stop to find how far the servo is off, the index is
+    /// zero based.
+    Home(usize),
+    /// G-code line, the whole of it.
+    GCode(&'a str),
+    /// Starts a firmware update of an image of `size` bytes with the CRC-32.
+    OtaBegin {
+        size: u32,
+        crc: u32,
+    },
+    /// Chunk of the firmware image at the offset, hex encoded, decode it with [`decode_hex`].
+    OtaChunk {
+        offset: u32,
+        data: &'a str,
+    },
+    /// Checks the received image and selects it to boot after a reset.
+    OtaEnd,
+    OtaReceived,
+}
+
+impl<'a> Command<'a> {
+    /// Parses a non-empty line without the line end.
+    pub fn parse(line: &'a str) -> Result<Self, ErrorCode> {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().ok_or(ErrorCode::UnknownCommand)?;
+        let is = |name: &str| keyword.eq_ignore_ascii_case(name);
+        if let [b'G' | b'g' | b'M' | b'm' | b'N' | b'n', b'0'..=b'9', ..] = keyword.as_bytes() {
+            return Ok(Command::GCode(line));
+        }
+
+        let cmd = if is("POSE") {
+            Command::Pose(words.next().ok_or(ErrorCode::BadArgument)?)
+        } else if is("ANGLES?") {
+            Command::Angles
+        } else if is("STATUS?") {
+            Command::Status
+        } else if is("STOP") {
+            Command::Stop
+        } else if is("RELEASE") {
+            Command::Release
+        } else if is("RESET") {
+            Command::ResetFaults
+        } else if is("SPEED") {
+            let name = words.next().ok_or(ErrorCode::BadArgument)?;
+            Command::SetSpeed(SpeedMode::from_name(name).ok_or(ErrorCode::UnknownSpeed)?)
+        } else if is("SPEED?") {
+            Command::Speed
+        } else if is("TELEOP") {
+            let name = words.next().ok_or(ErrorCode::BadArgument)?;
+            Command::SetTeleop(TeleopMode::from_name(name).ok_or(ErrorCode::UnknownTeleop)?)
+        } else if is("TELEOP?") {
+            Command::Teleop
+        } else if is("RUN") {
+            Command::RunScript
+        } else if is("HALT") {
+            Command::HaltScript
+        } else if is("LOG") {
+            let module = words.next().ok_or(ErrorCode::BadArgument)?;
+            let level = words.next().ok_or(ErrorCode::BadArgument)?;
+            let level = level.parse().map_err(|_| ErrorCode::UnknownLevel)?;
+            Command::SetLogLevel { module, level }
+        } else if is("LOG?") {
+            Command::LogLevels
+        } else if is("CALIBRATE") {
+            Command::Calibrate
+        } else if is("DEFAULTS") {
+            Command::ResetSettings
+        } else if is("GRIP") {
+            let arg = words.next().ok_or(ErrorCode::BadArgument)?;
+            if arg.eq_ignore_ascii_case("off") {
+                Command::Grip(None)
+            } else {
+                match arg.parse() {
+                    Ok(0) | Err(_) => return Err(ErrorCode::BadArgument),
+                    Ok(limit_ma) => Command::Grip(Some(limit_ma)),
+                }
+            }
+        } else if is("HOME") {
+            let joint: usize = words
+                .next()
+                .ok_or(ErrorCode::BadArgument)?
+                .parse()
+                .map_err(|_| ErrorCode::BadArgument)?;
+            Command::Home(joint.checked_sub(1).ok_or(ErrorCode::NoSuchJoint)?)
+        } else if is("OTA") {
+            let arg = words.next().ok_or(ErrorCode::BadArgument)?;
+            if arg.eq_ignore_ascii_case("BEGIN") {
+                let size = words.next().ok_or(ErrorCode::BadArgument)?;
+                let size = size.parse().map_err(|_| ErrorCode::BadArgument)?;
+                let crc = words.next().ok_or(ErrorCode::BadArgument)?;
+                let crc = u32::from_str_radix(crc, 16).map_err(|_| ErrorCode::BadArgument)?;
+                Command::OtaBegin { size, crc }
+            } else if arg.eq_ignore_ascii_case("END") {
+                Command::OtaEnd
+            } else {
+                let offset = arg.parse().map_err(|_| ErrorCode::BadArgument)?;
+                let data = words.next().ok_or(ErrorCode::BadArgument)?;
+                decode_hex(data, &mut [0; MAX_CHUNK_LEN])?;
+                Command::OtaChunk { offset, data }
+            }
+        } else if is("OTA?") {
+            Command::OtaReceived
+        } else if let Some(joint) = keyword.strip_prefix(['J', 'j']) {
+            let joint: usize = joint.parse().map_err(|_| ErrorCode::UnknownCommand)?;
+            let joint = joint.checked_sub(1).ok_or(ErrorCode::NoSuchJoint)?;
+            let target = Target::parse(words.next().ok_or(ErrorCode::BadArgument)?)?;
+            Command::MoveJoint { joint, target }
+        } else {
+            return Err(ErrorCode::UnknownCommand);
+        };
+
+        if words.next().is_some() {
+            return Err(ErrorCode::BadArgument);
+        }
+        Ok(cmd)
+    }
+
+    /// Runs the command on the arm.
+    pub fn execute<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        self,
+        bot: &mut ArmBot<G, D, N, B>,
+    ) -> Result<Reply<N>, CommandError> {
+        let moves = matches!(
+            self,
+            Command::MoveJoint { .. }
+                | Command::Pose(_)
+                | Command::RunScript
+                | Command::Grip(Some(_))
+                | Command::Home(_)
+                | Command::GCode(_)
+        );
+        if moves && bot.is_stopped() {
+            return Err(ErrorCode::Stopped.into());
+        }
+
+        match self {
+            Command::MoveJoint { joint, target } => {
+                let angles = bot.joint_angles();
+                let current = angles.get(joint).ok_or(ErrorCode::NoSuchJoint)?;
+                let angle = match target {
+                    Target::Absolute(angle) => angle,
+                    Target::Relative(offset) => *current + offset,
+                };
+                bot.move_joint(joint, angle)?;
+            }
+            Command::Pose(name) => {
+                let pose = PoseName::from_name(name).ok_or(ErrorCode::UnknownPose)?;
+                bot.goto_pose(pose)?;
+            }
+            Command::Angles => return Ok(Reply::Angles(bot.joint_angles())),
+            Command::Status => {
+                return Ok(Reply::Status {
+                    stopped: bot.is_stopped(),
+                    faults: bot.has_faults(),
+                })
+            }
+            Command::Stop => bot.run_action(Action::EmergencyStop)?,
+            Command::Release => bot.release_stop()?,
+            Command::ResetFaults => bot.reset_faults(),
+            Command::SetSpeed(mode) => bot.set_speed_mode(mode),
+            Command::Speed => return Ok(Reply::Speed(bot.speed_mode())),
+            Command::SetTeleop(mode) => bot.set_teleop_mode(mode).map_err(Report::from)?,
+            Command::Teleop => return Ok(Reply::Teleop(bot.teleop_mode())),
+            Command::RunScript => bot.run_script()?,
+            Command::HaltScript => bot.stop_script(),
+            Command::GCode(line) => bot.run_gcode(line)?,
+            // the arm has no logger, flash, current sensor nor end stops, the firmware console
+            // runs these
+            Command::SetLogLevel { .. }
+            | Command::LogLevels
+            | Command::Calibrate
+            | Command::ResetSettings
+            | Command::Grip(_)
+            | Command::Home(_)
+            | Command::OtaBegin { .. }
+            | Command::OtaChunk { .. }
+            | Command::OtaEnd
+            | Command::OtaReceived => return Err(ErrorCode::Unavailable.into()),
+        }
+        Ok(Reply::Done)
+    }
+}
+
+/// Parses and runs a command line, for the console and for any other transport
+/// that carries the same commands.
+pub fn run_line<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+    line: &str,
+    bot: &mut ArmBot<G, D, N, B>,
+) -> Result<Reply<N>, CommandError> {
+    Command::parse(line)
+        .map_err(CommandError::from)
+        .and_then(|cmd| cmd.execute(bot))
+}
+
+/// Decodes the hex of a firmware chunk into the buffer, returns the decoded bytes.
+pub fn decode_hex<'b>(hex: &str, buf: &'b mut [u8; MAX_CHUNK_LEN]) -> Result<&'b [u8], ErrorCode> {
+    let (pairs, rest) = hex.as_bytes().as_chunks::<2>();
+    if pairs.is_empty() || !rest.is_empty() || pairs.len() > MAX_CHUNK_LEN {
+        return Err(ErrorCode::BadArgument);
+    }
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(ErrorCode::BadArgument);
+    for (byte, [high, low]) in buf.iter_mut().zip(pairs) {
+        *byte = ((digit(*high)? << 4) | digit(*low)?) as u8;
+    }
+    Ok(&buf[..pairs.len()])
+}
+
+/// Collects received bytes into lines.
+#[derive(Debug)]
+pub struct LineBuffer {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+    /// Set when the current line didn't fit, it's rejected once it ends.
+    overflow: bool,
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Adds a byte, returns the line without the line end once `\n` is received.
+    /// `\r` is dropped, so both `\n` and `\r\n` end a line.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, ErrorCode>> {
+        match byte {
+            b'\r' => None,
+            b'\n' => {
+                let len = core::mem::take(&mut self.len);
+                if core::mem::take(&mut self.overflow) {
+                    return Some(Err(ErrorCode::BadLine));
+                }
+                Some(core::str::from_utf8(&self.buf[..len]).map_err(|_| ErrorCode::BadLine))
+            }
+            _ if self.len == MAX_LINE_LEN => {
+                self.overflow = true;
+                None
+            }
+            _ => {
+                self.buf[self.len] = byte;
+                self.len += 1;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        armbot::ArmBotConfig,
+        sim::{SimGamepad, SimServo},
+    };
+
+    fn bot() -> ArmBot<SimGamepad, SimServo> {
+        let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+        ArmBot::new(ArmBotConfig::default(), SimGamepad::new(), servos).unwrap()
+    }
+
+    /// Reply line of the command as the console sends it.
+    fn reply(line: &str, bot: &mut ArmBot<SimGamepad, SimServo>) -> String {
+        match run_line(line, bot) {
+            Ok(reply) => reply.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn joint_targets_are_absolute_or_relative() {
+        assert_eq!(
+            Command::parse("J1 90"),
+            Ok(Command::MoveJoint {
+                joint: 0,
+                target: Target::Absolute(Degrees::from_whole(90)),
+            })
+        );
+        assert_eq!(
+            Command::parse("j3 -5"),
+            Ok(Command::MoveJoint {
+                joint: 2,
+                target: Target::Relative(Degrees::new(-5.0)),
+            })
+        );
+        assert_eq!(
+            Command::parse("J2 +2.5"),
+            Ok(Command::MoveJoint {
+                joint: 1,
+                target: Target::Relative(Degrees::new(2.5)),
+            })
+        );
+    }
+
+    #[test]
+    fn keywords_and_names_ignore_case() {
+        assert_eq!(Command::parse("angles?"), Ok(Command::Angles));
+        assert_eq!(Command::parse("pose Home"), Ok(Command::Pose("Home")));
+        assert_eq!(
+            Command::parse("Speed FAST"),
+            Ok(Command::SetSpeed(SpeedMode::Fast))
+        );
+        assert_eq!(
+            Command::parse("TELEOP cartesian"),
+            Ok(Command::SetTeleop(TeleopMode::Cartesian))
+        );
+        assert_eq!(
+            Command::parse("log Servo DEBUG"),
+            Ok(Command::SetLogLevel {
+                module: "Servo",
+                level: LevelFilter::Debug,
+            })
+        );
+        assert_eq!(Command::parse("Log?"), Ok(Command::LogLevels));
+        assert_eq!(Command::parse("calibrate"), Ok(Command::Calibrate));
+        assert_eq!(Command::parse("grip 300"), Ok(Command::Grip(Some(300))));
+        assert_eq!(Command::parse("GRIP Off"), Ok(Command::Grip(None)));
+        assert_eq!(Command::parse("home 2"), Ok(Command::Home(1)));
+        assert_eq!(
+            Command::parse("g1 A90 F600"),
+            Ok(Command::GCode("g1 A90 F600"))
+        );
+        assert_eq!(Command::parse("N10 M3"), Ok(Command::GCode("N10 M3")));
+    }
+
+    #[test]
+    fn bad_lines_get_their_error_code() {
+        for (line, code) in [
+            ("FLY", ErrorCode::UnknownCommand),
+            ("Jx 90", ErrorCode::UnknownCommand),
+            ("J0 90", ErrorCode::NoSuchJoint),
+            ("J1", ErrorCode::BadArgument),
+            ("J1 ninety", ErrorCode::BadArgument),
+            ("J1 inf", ErrorCode::BadArgument),
+            ("STOP now", ErrorCode::BadArgument),
+            ("SPEED turbo", ErrorCode::UnknownSpeed),
+            ("TELEOP xyz", ErrorCode::UnknownTeleop),
+            ("LOG servo", ErrorCode::BadArgument),
+            ("LOG servo loud", ErrorCode::UnknownLevel),
+            ("GRIP", ErrorCode::BadArgument),
+            ("GRIP 0", ErrorCode::BadArgument),
+            ("GRIP -5", ErrorCode::BadArgument),
+            ("HOME", ErrorCode::BadArgument),
+            ("HOME 0", ErrorCode::NoSuchJoint),
+            ("HOME elbow", ErrorCode::BadArgument),
+            ("OTA", ErrorCode::BadArgument),
+            ("OTA BEGIN 1024", ErrorCode::BadArgument),
+            ("OTA BEGIN 1024 xyz", ErrorCode::BadArgument),
+            ("OTA 0 e9f", ErrorCode::BadArgument),
+            ("OTA 0 e9zz", ErrorCode::BadArgument),
+        ] {
+            assert_eq!(Command::parse(line), Err(code), "{line}");
+        }
+    }
+
+    #[test]
+    fn commands_drive_the_arm() {
+        let mut bot = bot();
+        assert_eq!(reply("J1 100", &mut bot), "OK");
+        assert_eq!(reply("J2 -10", &mut bot), "OK");
+        // rate limited joints get there in a few steps
+        for _ in 0..50 {
+            bot.do_step().unwrap();
+        }
+        assert_eq!(reply("ANGLES?", &mut bot), "OK 100.00 80.00 45.00");
+        assert_eq!(reply("J4 90", &mut bot), "ERR 4 no such joint");
+        assert_eq!(reply("POSE nowhere", &mut bot), "ERR 5 unknown pose");
+        assert!(reply("J3 170", &mut bot).starts_with("ERR 7 failed: "));
+        assert_eq!(reply("SPEED?", &mut bot), "OK normal");
+        assert_eq!(reply("LOG?", &mut bot), "ERR 12 not available here");
+    }
+
+    #[test]
+    fn gcode_lines_queue_moves() {
+        let mut bot = bot();
+        assert_eq!(reply("G91", &mut bot), "OK");
+        assert_eq!(reply("G1 A10 B-10 F1200", &mut bot), "OK");
+        assert_eq!(reply("G1 A10", &mut bot), "OK");
+        // 20°/s, a second for both moves
+        for _ in 0..150 {
+            bot.do_step().unwrap();
+        }
+        assert_eq!(reply("ANGLES?", &mut bot), "OK 110.00 80.00 45.00");
+        assert!(reply("G1 A100", &mut bot).starts_with("ERR 7 failed: "));
+        assert!(reply("G2 A1", &mut bot).starts_with("ERR 7 failed: "));
+    }
+
+    #[test]
+    fn ota_lines_carry_hex_chunks() {
+        assert_eq!(
+            Command::parse("ota begin 1024 1A2B3C4D"),
+            Ok(Command::OtaBegin {
+                size: 1024,
+                crc: 0x1a2b_3c4d
+            })
+        );
+        assert_eq!(
+            Command::parse("OTA 512 e90aFF"),
+            Ok(Command::OtaChunk {
+                offset: 512,
+                data: "e90aFF"
+            })
+        );
+        assert_eq!(Command::parse("OTA END"), Ok(Command::OtaEnd));
+        assert_eq!(Command::parse("OTA?"), Ok(Command::OtaReceived));
+        assert_eq!(Reply::<3>::Received(512).to_string(), "OK 512");
+
+        let mut buf = [0; MAX_CHUNK_LEN];
+        assert_eq!(decode_hex("e90aFF", &mut buf), Ok(&[0xe9, 0x0a, 0xff][..]));
+        let longest = "ab".repeat(MAX_CHUNK_LEN);
+        assert!(decode_hex(&longest, &mut buf).is_ok());
+        assert!(Command::parse(&format!("OTA 1000000 {longest}")).is_ok());
+        assert!(format!("OTA 1000000 {longest}").len() <= MAX_LINE_LEN);
+        assert_eq!(
+            decode_hex(&"ab".repeat(MAX_CHUNK_LEN + 1), &mut buf),
+            Err(ErrorCode::BadArgument)
+        );
+    }
+
+    #[test]
+    fn stopped_arm_only_takes_queries_and_release() {
+        let mut bot = bot();
+        assert_eq!(reply("STOP", &mut bot), "OK");
+        assert_eq!(reply("STATUS?", &mut bot), "OK stopped=1 faults=0");
+        assert_eq!(reply("J1 100", &mut bot), "ERR 6 arm is stopped");
+        assert_eq!(reply("POSE home", &mut bot), "ERR 6 arm is stopped");
+        assert_eq!(reply("GRIP 300", &mut bot), "ERR 6 arm is stopped");
+        assert_eq!(reply("HOME 1", &mut bot), "ERR 6 arm is stopped");
+        assert_eq!(reply("G0 A100", &mut bot), "ERR 6 arm is stopped");
+        assert_eq!(reply("RELEASE", &mut bot), "OK");
+        assert_eq!(reply("J1 100", &mut bot), "OK");
+    }
+
+    #[test]
+    fn line_buffer_splits_lines_and_drops_long_ones() {
+        let mut lines = LineBuffer::new();
+        let mut push_all = |bytes: &[u8]| {
+            let mut ended = Vec::new();
+            for &byte in bytes {
+                if let Some(line) = lines.push(byte) {
+                    ended.push(line.map(str::to_owned));
+                }
+            }
+            ended
+        };
+        assert_eq!(
+            push_all(b"J1 90\r\nSTOP\n"),
+            [Ok("J1 90".to_owned()), Ok("STOP".to_owned())]
+        );
+        assert_eq!(push_all(&[b'x'; MAX_LINE_LEN + 1]), []);
+        assert_eq!(push_all(b"\n"), [Err(ErrorCode::BadLine)]);
+        assert_eq!(push_all(&[0xff, b'\n']), [Err(ErrorCode::BadLine)]);
+        assert_eq!(push_all(b"RESET\n"), [Ok("RESET".to_owned())]);
+    }
+}
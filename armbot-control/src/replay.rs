@@ -0,0 +1,168 @@
+use core::{ops::Range, time::Duration};
+
+use crate::{
+    clock::{Clock, Instant},
+    error::Error,
+    gamepad::{AxisConfig, Gamepad, GamepadConfig, RawState, State, AXES},
+};
+
+/// Gamepad that plays back recorded raw inputs, one frame per control step.
+///
+/// Time is virtual: frame `n` happens at `n * timestep` no matter how long the step took, so the
+/// same recording produces the same motion on every run, on the device or on a host.
+/// Frames can be taken from the `raw state` trace log of the real gamepad.
+pub struct ReplayGamepad<'a> {
+    config: GamepadConfig,
+    centers: [Range<u32>; AXES],
+    frames: &'a [RawState],
+    timestep: Duration,
+    /// Index of the next frame.
+    position: usize,
+}
+
+impl<'a> ReplayGamepad<'a> {
+    /// Creates the replay, the first frame is treated as the center position
+    /// if `config.use_real_center` is set, as the real gamepad does at boot.
+    pub fn new(config: GamepadConfig, frames: &'a [RawState], timestep: Duration) -> Self {
+        let centers = core::array::from_fn(|idx| {
+            let axis = &config.axes[idx];
+            match frames.first() {
+                Some(first) if config.use_real_center => axis.center_range(first.axes[idx]),
+                _ => axis.default_center_range(),
+            }
+        });
+
+        Self {
+            config,
+            centers,
+            frames,
+            timestep,
+            position: 0,
+        }
+    }
+
+    /// Returns true when all frames were played.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+
+    /// Starts the replay from the first frame.
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+}
+
+/// Virtual time of the next frame, the epoch is the start of the replay.
+impl Clock for ReplayGamepad<'_> {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.timestep.as_micros() as u64 * self.position as u64)
+    }
+}
+
+impl Gamepad for ReplayGamepad<'_> {
+    fn read_raw_state(&mut self) -> Result<RawState, Error> {
+        let frame = self
+            .frames
+            .get(self.position)
+            .cloned()
+            .ok_or(Error::Other("replay is finished"))?;
+        self.position += 1;
+        Ok(frame)
+    }
+
+    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error> {
+        let raw = self.read_raw_state()?;
+        State::from_raw(&raw, &self.config, &self.centers, output)
+    }
+
+    /// Recordings are made with calibrated values already.
+    fn calibrate(
+        &mut self,
+        _clock: &impl Clock,
+        _hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        Err(Error::Other("replay can't be calibrated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        armbot::{ArmBot, ArmBotConfig, JOINTS},
+        gamepad::{Axis, BUTTONS},
+        sim::SimServo,
+        CONTROL_PERIOD,
+    };
+
+    /// Middle of the default axis range.
+    const CENTER: u32 = (10 + 2757) / 2;
+
+    /// Shoulder pushed up with a wobble of the raw value, then released, then the elbow down.
+    fn recording() -> Vec<RawState> {
+        let frame = |shoulder, elbow| {
+            let mut axes = [CENTER; AXES];
+            axes[Axis::Shoulder as usize] = shoulder;
+            axes[Axis::Elbow as usize] = elbow;
+            RawState {
+                axes,
+                buttons: [false; BUTTONS],
+            }
+        };
+        let mut frames = vec![frame(CENTER, CENTER); 5];
+        frames.extend((0..40).map(|idx| frame(2000 + idx % 7 * 100, CENTER)));
+        frames.extend((0..30).map(|_| frame(CENTER, CENTER)));
+        frames.extend((0..40).map(|idx| frame(CENTER, 600 - idx * 10)));
+        frames
+    }
+
+    /// Joint angles after every step of the replay.
+    fn play(frames: &[RawState]) -> Vec<[f32; JOINTS]> {
+        let gamepad = ReplayGamepad::new(GamepadConfig::default(), frames, CONTROL_PERIOD);
+        let servos = [90.0, 90.0, 45.0].map(SimServo::new);
+        let mut bot = ArmBot::new(ArmBotConfig::default(), gamepad, servos).unwrap();
+        let mut angles = Vec::new();
+        while !bot.gamepad_mut().is_finished() {
+            bot.do_step().unwrap();
+            angles.push(bot.joint_angles().map(|angle| angle.get()));
+        }
+        angles
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let frames = recording();
+        let first = play(&frames);
+        assert_eq!(first.len(), frames.len());
+        assert_eq!(first, play(&frames));
+
+        let [shoulder, elbow, _] = *first.last().unwrap();
+        assert!(shoulder > 90.0, "shoulder at {shoulder}");
+        assert!(elbow < 90.0, "elbow at {elbow}");
+    }
+
+    #[test]
+    fn time_advances_by_the_timestep_per_frame() {
+        let frames = recording();
+        let mut gamepad = ReplayGamepad::new(GamepadConfig::default(), &frames, CONTROL_PERIOD);
+        assert_eq!(gamepad.now(), Instant::from_micros(0));
+        for _ in 0..3 {
+            gamepad.read_raw_state().unwrap();
+        }
+        assert_eq!(gamepad.now(), Instant::from_micros(30_000));
+
+        gamepad.rewind();
+        assert_eq!(gamepad.now(), Instant::from_micros(0));
+    }
+
+    #[test]
+    fn finished_replay_fails_to_read() {
+        let frames = recording();
+        let mut gamepad =
+            ReplayGamepad::new(GamepadConfig::default(), &frames[..2], CONTROL_PERIOD);
+        gamepad.read_raw_state().unwrap();
+        gamepad.read_raw_state().unwrap();
+        assert!(gamepad.is_finished());
+        assert!(gamepad.read_raw_state().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Pose sequences loaded from flash, so a pick and place routine changes without a rebuild.
+//!
+//! A script is text with a step per line, `#` starts a comment, keywords are case insensitive:
+//!
+//! | Step                 | What it does                                                  |
+//! |----------------------|---------------------------------------------------------------|
+//! | `pose pick`          | Moves to the named pose, see [`PoseName`]                     |
+//! | `move 90 45 20 1.5`  | Moves the joints to the angles in 1.5 s, ends at rest         |
+//! | `wait 500`           | Waits 500 ms                                                  |
+//! | `grip close`         | Closes the gripper, `open` opens it                           |
+//! | `repeat 3` ... `end` | Runs the steps in between 3 times, without a count forever    |
+//!
+//! A step starts once the previous move is done. Grips jump the servo, a `wait` after them
+//! gives the gripper time to close. The whole script is parsed when it's loaded, so a typo
+//! is reported at boot with its line instead of halfway through the routine.
+//! JSON or TOML would need a parser crate in the firmware, the line format follows the console
+//! commands and parses without allocation.
+
+use core::time::Duration;
+
+use crate::{
+    armbot::{Action, PoseName},
+    error::Error,
+    gamepad::Axis,
+    units::Degrees,
+};
+
+/// Max size of a script, a flash sector.
+pub const MAX_LEN: usize = 4096;
+/// Max number of steps, `repeat` and `end` count as steps.
+pub const MAX_STEPS: usize = 64;
+/// Max nesting of `repeat`.
+pub const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step<const N: usize> {
+    Pose(PoseName),
+    /// Moves the joints to the angles in the duration, all joints arrive together.
+    Move {
+        targets: [Degrees; N],
+        duration: Duration,
+    },
+    Wait(Duration),
+    /// Runs the action, e.g. moves the gripper.
+    Action(Action),
+    /// Runs the steps up to the matching `End` the number of times, forever without it.
+    Repeat(Option<u32>),
+    End,
+}
+
+/// Parsed script of an arm with `N` joints.
+#[derive(Debug, Clone)]
+pub struct Script<const N: usize> {
+    steps: [Step<N>; MAX_STEPS],
+    len: usize,
+}
+
+impl<const N: usize> Script<N> {
+    /// Parses the whole script, fails with the first bad line.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut script = Self {
+            steps: [Step::End; MAX_STEPS],
+            len: 0,
+        };
+        // lines and steps of the open `repeat`s
+        let mut open = [(0, 0); MAX_DEPTH];
+        let mut depth = 0;
+        for (idx, line) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let err = |reason| Error::Script {
+                line: line_no,
+                reason,
+            };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_step(line).map_err(err)?;
+            match step {
+                Step::Repeat(_) => {
+                    let slot = open.get_mut(depth).ok_or(err("repeat nested too deep"))?;
+                    *slot = (line_no, script.len);
+                    depth += 1;
+                }
+                Step::End => {
+                    depth = depth.checked_sub(1).ok_or(err("end without repeat"))?;
+                    // an empty body would loop without ever moving
+                    if open[depth].1 + 1 == script.len {
+                        return Err(err("repeat without steps"));
+                    }
+                }
+                _ => {}
+            }
+            let slot = script
+                .steps
+                .get_mut(script.len)
+                .ok_or(err("script has too many steps"))?;
+            *slot = step;
+            script.len += 1;
+        }
+        if let Some(&(line, _)) = open[..depth].last() {
+            return Err(Error::Script {
+                line,
+                reason: "repeat without end",
+            });
+        }
+        Ok(script)
+    }
+
+    pub fn steps(&self) -> &[Step<N>] {
+        &self.steps[..self.len]
+    }
+}
+
+/// Parses a line without the comment.
+fn parse_step<const N: usize>(line: &str) -> Result<Step<N>, &'static str> {
+    let mut words = line.split_whitespace();
+    let keyword = words.next().unwrap_or_default();
+    let is = |name: &str| keyword.eq_ignore_ascii_case(name);
+    let mut number = || match words.next().map(str::parse::<f32>) {
+        Some(Ok(val)) if val.is_finite() => Ok(val),
+        Some(_) => Err("bad number"),
+        None => Err("missing number"),
+    };
+
+    let step = if is("pose") {
+        let name = words.next().ok_or("missing pose name")?;
+        Step::Pose(PoseName::from_name(name).ok_or("unknown pose")?)
+    } else if is("move") {
+        let mut targets = [Degrees::ZERO; N];
+        for target in targets.iter_mut() {
+            *target = Degrees::new(number()?);
+        }
+        let duration = Duration::try_from_secs_f32(number()?).map_err(|_| "bad move duration")?;
+        Step::Move { targets, duration }
+    } else if is("wait") {
+        let ms = words.next().ok_or("missing wait time")?;
+        let ms: u64 = ms.parse().map_err(|_| "bad wait time")?;
+        Step::Wait(Duration::from_millis(ms))
+    } else if is("grip") {
+        match words.next() {
+            Some(arg) if arg.eq_ignore_ascii_case("open") => {
+                Step::Action(Action::JointToMax(Axis::Gripper))
+            }
+            Some(arg) if arg.eq_ignore_ascii_case("close") => {
+                Step::Action(Action::JointToMin(Axis::Gripper))
+            }
+            _ => return Err("grip needs open or close"),
+        }
+    } else if is("repeat") {
+        match words.next() {
+            None => Step::Repeat(None),
+            Some(count) => match count.parse() {
+                Ok(count) if count > 0 => Step::Repeat(Some(count)),
+                _ => return Err("bad repeat count"),
+            },
+        }
+    } else if is("end") {
+        Step::End
+    } else {
+        return Err("unknown step");
+    };
+
+    if words.next().is_some() {
+        return Err("extra words");
+    }
+    Ok(step)
+}
+
+/// `repeat` being run.
+#[derive(Debug, Clone, Copy, Default)]
+struct Loop {
+    /// Index of the first step of the body.
+    start: usize,
+    /// Runs left, `None` forever.
+    left: Option<u32>,
+}
+
+/// Progress through a script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptRun {
+    /// Index of the next step.
+    next: usize,
+    loops: [Loop; MAX_DEPTH],
+    depth: usize,
+    /// Control periods left of a wait.
+    wait: u32,
+}
+
+impl ScriptRun {
+    /// Returns the next step to run, `None` once the script is done.
+    /// Loops are run here, `Repeat` and `End` are never returned.
+    pub fn next<const N: usize>(&mut self, script: &Script<N>) -> Option<Step<N>> {
+        loop {
+            let step = *script.steps().get(self.next)?;
+            self.next += 1;
+            match step {
+                Step::Repeat(count) => {
+                    // parsing limits the nesting
+                    self.loops[self.depth] = Loop {
+                        start: self.next,
+                        left: count,
+                    };
+                    self.depth += 1;
+                }
+                Step::End => {
+                    let current = &mut self.loops[self.depth - 1];
+                    current.left = current.left.map(|left| left - 1);
+                    if current.left == Some(0) {
+                        self.depth -= 1;
+                    } else {
+                        self.next = current.start;
+                    }
+                }
+                step => return Some(step),
+            }
+        }
+    }
+
+    /// Holds the script for the duration, counted in control periods.
+    pub fn wait(&mut self, duration: Duration) {
+        let period = crate::CONTROL_PERIOD.as_micros();
+        self.wait = duration.as_micros().div_ceil(period) as u32;
+    }
+
+    /// Counts down a control period of the wait, returns true while it lasts.
+    pub fn waiting(&mut self) -> bool {
+        if self.wait == 0 {
+            return false;
+        }
+        self.wait -= 1;
+        true
+    }
+}
@@ -0,0 +1,481 @@
+use core::ops::Range;
+
+use armbot_core::storage::checksum;
+
+use crate::{
+    armbot::{ArmBotConfig, JOINTS},
+    error::Error,
+    gamepad::{AxisConfig, GamepadConfig, ResponseCurve, AXES},
+    units::Degrees,
+};
+
+/// Marks a stored settings blob, "ARMB".
+const MAGIC: u32 = u32::from_le_bytes(*b"ARMB");
+
+/// Version of the layout written by this firmware.
+///
+/// When the layout changes: bump the version, keep the decoder of the previous layout and
+/// convert its result in [`Settings::migrate`]. Never change the layout of a released version.
+pub const VERSION: u16 = 5;
+
+/// Duty counts per degree of the SG90 with 14 bit duty,
+/// version 1 stored step sizes in duty counts of that servo.
+const V1_DUTY_PER_DEGREE: f32 = (2048 - 409) as f32 / 180.0;
+
+/// magic(4) + version(2) + payload length(2) + checksum(2)
+const HEADER_LEN: usize = 10;
+
+/// Max size of the encoded settings.
+pub const MAX_LEN: usize = 128;
+
+/// Max servo trim, larger ones mean the horn should be remounted.
+const MAX_TRIM_US: i32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SettingsError {
+    /// No settings were stored yet.
+    BadMagic,
+    /// Stored blob is corrupted.
+    BadChecksum,
+    /// Stored blob is shorter than its header claims.
+    Truncated,
+    /// Settings were written by a newer firmware, they're left untouched.
+    UnsupportedVersion(u16),
+    /// Values are out of their valid ranges.
+    Invalid(&'static str),
+}
+
+/// Calibration that survives firmware updates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Gamepad axis ranges, stored as raw values. Response curves aren't stored.
+    pub axes: [AxisConfig; AXES],
+    pub use_real_center: bool,
+
+    /// Angle ranges of shoulder, elbow and gripper.
+    /// Stored in whole degrees.
+    pub angle_ranges: [Range<Degrees>; JOINTS],
+    /// Stored in hundredths of a degree.
+    pub step_size: Range<Degrees>,
+    /// Servo trims of shoulder, elbow and gripper in microseconds.
+    pub trims_us: [i32; JOINTS],
+}
+
+impl Settings {
+    /// Takes calibration values from the configs, servo trims go in the order of joints.
+    pub fn new(gamepad: &GamepadConfig, arm: &ArmBotConfig, trims_us: [i32; JOINTS]) -> Self {
+        Self {
+            axes: gamepad.axes,
+            use_real_center: gamepad.use_real_center,
+            angle_ranges: core::array::from_fn(|idx| arm.joints[idx].angle_range.clone()),
+            step_size: arm.step_size.clone(),
+            trims_us,
+        }
+    }
+
+    /// Overrides calibration values of the configs, servo trims are taken from
+    /// [`Settings::trims_us`].
+    pub fn apply(&self, gamepad: &mut GamepadConfig, arm: &mut ArmBotConfig) {
+        // response curves aren't calibration, they stay as configured
+        for (axis, stored) in gamepad.axes.iter_mut().zip(&self.axes) {
+            *axis = AxisConfig {
+                curve: axis.curve,
+                ..*stored
+            };
+        }
+        gamepad.use_real_center = self.use_real_center;
+        for (joint, range) in arm.joints.iter_mut().zip(&self.angle_ranges) {
+            joint.angle_range = range.clone();
+        }
+        arm.step_size = self.step_size.clone();
+    }
+
+    /// Checks that values are consistent, so the arm can be operated with them.
+    pub fn validate(&self) -> Result<(), Error> {
+        fn check(ok: bool, reason: &'static str) -> Result<(), Error> {
+            if ok {
+                Ok(())
+            } else {
+                Err(SettingsError::Invalid(reason).into())
+            }
+        }
+
+        for axis in &self.axes {
+            check(
+                axis.max_value <= u16::MAX as u32,
+                "axis max value must fit 16 bits",
+            )?;
+            axis.validate().map_err(|err| match err {
+                Error::OutOfRange(reason) => SettingsError::Invalid(reason).into(),
+                err => err,
+            })?;
+        }
+        for range in &self.angle_ranges {
+            check(
+                Degrees::ZERO <= range.start
+                    && range.start < range.end
+                    && range.end <= Degrees::from_whole(180),
+                "angle range must be non empty and within 0..180",
+            )?;
+        }
+        check(
+            Degrees::ZERO < self.step_size.start
+                && self.step_size.start < self.step_size.end
+                && self.step_size.end.to_hundredths().is_ok(),
+            "step size range must be non empty, start above zero and fit hundredths of a degree",
+        )?;
+        check(
+            self.trims_us.iter().all(|trim| trim.abs() <= MAX_TRIM_US),
+            "servo trim must be within ±500 µs",
+        )
+    }
+
+    /// Encodes settings with the current layout version, returns the number of written bytes.
+    pub fn encode(&self, buf: &mut [u8; MAX_LEN]) -> usize {
+        let mut writer = Writer {
+            buf: &mut buf[HEADER_LEN..],
+            pos: 0,
+        };
+        writer.u16(self.use_real_center as u16);
+        for axis in &self.axes {
+            writer.u16(axis.min_value as u16);
+            writer.u16(axis.max_value as u16);
+            writer.u16(axis.center_offset as u16);
+            // a valid center is never zero
+            writer.u16(axis.center.unwrap_or_default() as u16);
+        }
+        for range in &self.angle_ranges {
+            writer.range(range);
+        }
+        writer.u16(self.step_size.start.to_hundredths().unwrap_or_default());
+        writer.u16(self.step_size.end.to_hundredths().unwrap_or_default());
+        for trim_us in self.trims_us {
+            writer.i16(trim_us as i16);
+        }
+        let len = writer.pos;
+
+        let checksum = checksum(&buf[HEADER_LEN..HEADER_LEN + len]);
+        buf[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        buf[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        buf[6..8].copy_from_slice(&(len as u16).to_le_bytes());
+        buf[8..10].copy_from_slice(&checksum.to_le_bytes());
+        HEADER_LEN + len
+    }
+
+    /// Decodes settings written by this or any older firmware.
+    pub fn decode(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < HEADER_LEN {
+            return Err(SettingsError::Truncated.into());
+        }
+        let magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        if magic != MAGIC {
+            return Err(SettingsError::BadMagic.into());
+        }
+        let version = u16::from_le_bytes([buf[4], buf[5]]);
+        let len = u16::from_le_bytes([buf[6], buf[7]]) as usize;
+        let stored_checksum = u16::from_le_bytes([buf[8], buf[9]]);
+
+        let payload = buf
+            .get(HEADER_LEN..HEADER_LEN + len)
+            .ok_or(SettingsError::Truncated)?;
+        if checksum(payload) != stored_checksum {
+            return Err(SettingsError::BadChecksum.into());
+        }
+
+        Self::migrate(version, payload)
+    }
+
+    /// Decodes the payload of the specified layout version and converts it to the current one.
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, Error> {
+        match version {
+            1 => Self::decode_v1(payload),
+            2 => Self::decode_v2(payload),
+            3 => Self::decode_v3(payload),
+            4 => Self::decode_v4(payload),
+            5 => Self::decode_v5(payload),
+            _ => Err(SettingsError::UnsupportedVersion(version).into()),
+        }
+    }
+
+    /// Version 1 stored step sizes in duty counts.
+    fn decode_v1(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buf: payload,
+            pos: 0,
+        };
+        let counts_to_deg = |counts: u16| Degrees::new(counts as f32 / V1_DUTY_PER_DEGREE);
+        let (axes, use_real_center) = Self::read_shared_axes(&mut reader)?;
+        Ok(Self {
+            axes,
+            use_real_center,
+            angle_ranges: [reader.range()?, reader.range()?, reader.range()?],
+            step_size: counts_to_deg(reader.u16()?)..counts_to_deg(reader.u16()?),
+            trims_us: [0; JOINTS],
+        })
+    }
+
+    fn decode_v2(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buf: payload,
+            pos: 0,
+        };
+        Self::read_v2(&mut reader)
+    }
+
+    /// Version 3 appended servo trims to version 2.
+    fn decode_v3(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buf: payload,
+            pos: 0,
+        };
+        Self::read_v3(&mut reader)
+    }
+
+    /// Version 4 appended joystick calibration to version 3.
+    fn decode_v4(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buf: payload,
+            pos: 0,
+        };
+        let mut settings = Self::read_v3(&mut reader)?;
+        if reader.u16()? != 0 {
+            for axis in settings.axes.iter_mut() {
+                axis.min_value = reader.u16()? as u32;
+                axis.center = Some(reader.u16()? as u32);
+                axis.max_value = reader.u16()? as u32;
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Version 5 made the gamepad settings per axis.
+    fn decode_v5(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buf: payload,
+            pos: 0,
+        };
+        let use_real_center = reader.u16()? != 0;
+        let mut axes = [AxisConfig::default(); AXES];
+        for axis in axes.iter_mut() {
+            axis.min_value = reader.u16()? as u32;
+            axis.max_value = reader.u16()? as u32;
+            axis.center_offset = reader.u16()? as u32;
+            axis.center = match reader.u16()? {
+                0 => None,
+                center => Some(center as u32),
+            };
+        }
+        let mut settings = Self::read_joints(&mut reader, axes, use_real_center)?;
+        for trim_us in settings.trims_us.iter_mut() {
+            *trim_us = reader.i16()? as i32;
+        }
+        Ok(settings)
+    }
+
+    fn read_v3(reader: &mut Reader) -> Result<Self, Error> {
+        let mut settings = Self::read_v2(reader)?;
+        for trim_us in settings.trims_us.iter_mut() {
+            *trim_us = reader.i16()? as i32;
+        }
+        Ok(settings)
+    }
+
+    fn read_v2(reader: &mut Reader) -> Result<Self, Error> {
+        let (axes, use_real_center) = Self::read_shared_axes(reader)?;
+        Self::read_joints(reader, axes, use_real_center)
+    }
+
+    /// Versions before 5 stored one range for all axes.
+    fn read_shared_axes(reader: &mut Reader) -> Result<([AxisConfig; AXES], bool), Error> {
+        let axis = AxisConfig {
+            min_value: reader.u16()? as u32,
+            max_value: reader.u16()? as u32,
+            center_offset: reader.u16()? as u32,
+            center: None,
+            curve: ResponseCurve::Linear,
+        };
+        let use_real_center = reader.u16()? != 0;
+        Ok(([axis; AXES], use_real_center))
+    }
+
+    /// Reads angle ranges and step sizes, stored the same way since version 2.
+    fn read_joints(
+        reader: &mut Reader,
+        axes: [AxisConfig; AXES],
+        use_real_center: bool,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            axes,
+            use_real_center,
+            angle_ranges: [reader.range()?, reader.range()?, reader.range()?],
+            step_size: Degrees::from_hundredths(reader.u16()?)
+                ..Degrees::from_hundredths(reader.u16()?),
+            trims_us: [0; JOINTS],
+        })
+    }
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn u16(&mut self, val: u16) {
+        self.buf[self.pos..self.pos + 2].copy_from_slice(&val.to_le_bytes());
+        self.pos += 2;
+    }
+
+    fn i16(&mut self, val: i16) {
+        self.u16(val as u16);
+    }
+
+    /// Writes an angle range in whole degrees, angles must be validated before.
+    fn range(&mut self, range: &Range<Degrees>) {
+        self.u16(range.start.to_whole().unwrap_or_default());
+        self.u16(range.end.to_whole().unwrap_or_default());
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u16(&mut self) -> Result<u16, SettingsError> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + 2)
+            .ok_or(SettingsError::Truncated)?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, SettingsError> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn range(&mut self) -> Result<Range<Degrees>, SettingsError> {
+        Ok(Degrees::from_whole(self.u16()?)..Degrees::from_whole(self.u16()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stored blob of the layout version with the payload of 16 bit words.
+    fn blob(version: u16, words: &[u16]) -> Vec<u8> {
+        let payload: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&MAGIC.to_le_bytes());
+        blob.extend_from_slice(&version.to_le_bytes());
+        blob.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        blob.extend_from_slice(&checksum(&payload).to_le_bytes());
+        blob.extend_from_slice(&payload);
+        blob
+    }
+
+    /// Shared axis range and the joint ranges as versions 1 to 4 stored them.
+    const V1_HEAD: [u16; 10] = [100, 2700, 60, 1, 30, 150, 40, 140, 20, 70];
+
+    fn ranges() -> [Range<Degrees>; JOINTS] {
+        [(30, 150), (40, 140), (20, 70)]
+            .map(|(start, end)| Degrees::from_whole(start)..Degrees::from_whole(end))
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let settings = Settings::new(
+            &GamepadConfig::default(),
+            &ArmBotConfig::default(),
+            [-20, 0, 35],
+        );
+        settings.validate().unwrap();
+        let mut buf = [0; MAX_LEN];
+        let len = settings.encode(&mut buf);
+        assert_eq!(Settings::decode(&buf[..len]).unwrap(), settings);
+    }
+
+    #[test]
+    fn v1_step_sizes_are_converted_from_duty_counts() {
+        let mut words = V1_HEAD.to_vec();
+        // 0.5° and 2° of the SG90
+        words.extend([5, 18]);
+        let settings = Settings::decode(&blob(1, &words)).unwrap();
+
+        assert_eq!(settings.axes[0].min_value, 100);
+        assert_eq!(settings.axes[3].max_value, 2700);
+        assert_eq!(settings.axes[2].center_offset, 60);
+        assert!(settings.use_real_center);
+        assert_eq!(settings.angle_ranges, ranges());
+        assert!((settings.step_size.start.get() - 0.55).abs() < 0.01);
+        assert!((settings.step_size.end.get() - 1.98).abs() < 0.01);
+        assert_eq!(settings.trims_us, [0; JOINTS]);
+    }
+
+    #[test]
+    fn v2_step_sizes_are_in_hundredths() {
+        let mut words = V1_HEAD.to_vec();
+        words.extend([10, 100]);
+        let settings = Settings::decode(&blob(2, &words)).unwrap();
+
+        assert_eq!(settings.angle_ranges, ranges());
+        assert_eq!(
+            settings.step_size,
+            Degrees::from_hundredths(10)..Degrees::from_hundredths(100)
+        );
+        assert_eq!(settings.trims_us, [0; JOINTS]);
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn v4_reads_trims_and_joystick_calibration() {
+        let mut words = V1_HEAD.to_vec();
+        words.extend([10, 100]);
+        words.extend([(-20i16) as u16, 0, 35]);
+        words.push(1);
+        for axis in 0..AXES as u16 {
+            words.extend([200 + axis, 1400 + axis, 2600 + axis]);
+        }
+        let settings = Settings::decode(&blob(4, &words)).unwrap();
+
+        assert_eq!(settings.trims_us, [-20, 0, 35]);
+        assert_eq!(settings.axes[1].min_value, 201);
+        assert_eq!(settings.axes[1].center, Some(1401));
+        assert_eq!(settings.axes[1].max_value, 2601);
+        assert_eq!(settings.axes[1].center_offset, 60);
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn damaged_or_newer_blobs_are_rejected() {
+        let mut words = V1_HEAD.to_vec();
+        words.extend([10, 100]);
+
+        let mut corrupt = blob(2, &words);
+        *corrupt.last_mut().unwrap() ^= 0x01;
+        assert!(matches!(
+            Settings::decode(&corrupt),
+            Err(Error::Settings(SettingsError::BadChecksum))
+        ));
+
+        let short = blob(2, &words);
+        assert!(matches!(
+            Settings::decode(&short[..short.len() - 1]),
+            Err(Error::Settings(SettingsError::Truncated))
+        ));
+
+        assert!(matches!(
+            Settings::decode(&[0xff; MAX_LEN]),
+            Err(Error::Settings(SettingsError::BadMagic))
+        ));
+
+        assert!(matches!(
+            Settings::decode(&blob(VERSION + 1, &words)),
+            Err(Error::Settings(SettingsError::UnsupportedVersion(6)))
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+//! Simulated servos and gamepad, for running the arm logic without the hardware.
+//!
+//! [`SimServo`] keeps the angle in memory and [`SimGamepad`] is driven by keys, so a host runner
+//! can feed it from the keyboard and print the joint angles, and tests can drive the arm.
+
+use core::{ops::Range, time::Duration};
+
+use servo_driver::{Dir, MotionProfile, ServoDriver, ServoError, StepResult};
+
+use crate::{
+    clock::Clock,
+    error::Error,
+    gamepad::{AxisConfig, Button, Gamepad, Position, RawState, State, AXES, BUTTONS},
+};
+
+/// Servo that moves to every commanded angle at once.
+/// Motion profiles are stored but not simulated.
+#[derive(Debug, Clone)]
+pub struct SimServo {
+    angle: f64,
+    limits: (f64, f64),
+    dir: Dir,
+    profile: Option<MotionProfile>,
+    enabled: bool,
+    failing: bool,
+}
+
+impl SimServo {
+    /// Servo at the angle, with limits of a 180° servo.
+    pub fn new(angle: f64) -> Self {
+        Self {
+            angle,
+            limits: (0.0, 180.0),
+            dir: Dir::CCW,
+            profile: None,
+            enabled: true,
+            failing: false,
+        }
+    }
+
+    /// Returns false after [`ServoDriver::disable`], a real servo would go limp.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn profile(&self) -> Option<&MotionProfile> {
+        self.profile.as_ref()
+    }
+
+    /// Makes moves fail with [`ServoError::Backend`] until cleared, as a broken servo would.
+    pub fn set_failing(&mut self, failing: bool) {
+        self.failing = failing;
+    }
+}
+
+impl ServoDriver for SimServo {
+    type Error = ServoError;
+
+    /// Steps are in degrees, there are no pulse units.
+    fn step(&mut self, step: f32) -> Result<StepResult, ServoError> {
+        self.step_deg(step)
+    }
+
+    fn step_deg(&mut self, step: f32) -> Result<StepResult, ServoError> {
+        if self.failing {
+            return Err(ServoError::Backend);
+        }
+        // CCW makes longer pulses, that is greater angles
+        let step = match self.dir {
+            Dir::CW => -step as f64,
+            Dir::CCW => step as f64,
+        };
+        let wanted = self.angle + step;
+        let (min, max) = self.limits;
+        self.angle = wanted.clamp(min, max);
+        Ok(if wanted < min {
+            StepResult::ClampedToMin
+        } else if wanted > max {
+            StepResult::ClampedToMax
+        } else {
+            StepResult::Stepped
+        })
+    }
+
+    fn set_angle(&mut self, angle: f64) -> Result<(), ServoError> {
+        if self.failing {
+            return Err(ServoError::Backend);
+        }
+        let (min, max) = self.limits;
+        if angle < min || angle > max {
+            let limit = if angle < min { min } else { max };
+            return Err(ServoError::LimitReached { limit });
+        }
+        self.angle = angle;
+        Ok(())
+    }
+
+    fn get_angle(&self) -> f64 {
+        self.angle
+    }
+
+    fn set_dir(&mut self, dir: Dir) {
+        self.dir = dir;
+    }
+
+    fn set_limits(&mut self, min_deg: f64, max_deg: f64) -> Result<(), ServoError> {
+        if min_deg > max_deg {
+            return Err(ServoError::InvalidLimits);
+        }
+        self.limits = (min_deg, max_deg);
+        self.angle = self.angle.clamp(min_deg, max_deg);
+        Ok(())
+    }
+
+    fn set_profile(&mut self, profile: Option<MotionProfile>) -> Result<(), ServoError> {
+        if let Some(profile) = &profile {
+            profile.validate()?;
+        }
+        self.profile = profile;
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), ServoError> {
+        self.enabled = false;
+        Ok(())
+    }
+
+    fn enable(&mut self) -> Result<(), ServoError> {
+        self.enabled = true;
+        Ok(())
+    }
+}
+
+/// Keys of the simulated gamepad: the axis they push and whether towards [`Position::High`].
+pub const AXIS_KEYS: [(u8, usize, bool); 2 * AXES] = [
+    (b'a', 0, false),
+    (b'd', 0, true),
+    (b's', 1, false),
+    (b'w', 1, true),
+    (b'k', 2, false),
+    (b'i', 2, true),
+    (b'j', 3, false),
+    (b'l', 3, true),
+];
+
+/// Keys of the buttons, indexed by [`Button`].
+pub const BUTTON_KEYS: [u8; BUTTONS] = *b"1234";
+
+/// Gamepad driven by keys, see [`AXIS_KEYS`] and [`BUTTON_KEYS`].
+///
+/// A key stays held until it's released, as a terminal reports key presses only.
+/// Held axes move at [`SimGamepad::set_deflection`] of the full speed.
+#[derive(Debug, Clone)]
+pub struct SimGamepad {
+    axes: [Option<bool>; AXES],
+    buttons: [bool; BUTTONS],
+    /// How far held axes are pushed, from 0 to 1.
+    deflection: f32,
+    failing: bool,
+    stale: bool,
+}
+
+impl Default for SimGamepad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimGamepad {
+    pub fn new() -> Self {
+        Self {
+            axes: [None; AXES],
+            buttons: [false; BUTTONS],
+            deflection: 0.5,
+            failing: false,
+            stale: false,
+        }
+    }
+
+    /// Holds the axis or button of the key, returns false for an unknown key.
+    pub fn press(&mut self, key: u8) -> bool {
+        let key = key.to_ascii_lowercase();
+        if let Some((_, axis, high)) = AXIS_KEYS.iter().find(|(k, _, _)| *k == key) {
+            self.axes[*axis] = Some(*high);
+            return true;
+        }
+        if let Some(button) = BUTTON_KEYS.iter().position(|k| *k == key) {
+            self.buttons[button] = true;
+            return true;
+        }
+        false
+    }
+
+    pub fn press_button(&mut self, button: Button) {
+        self.buttons[button as usize] = true;
+    }
+
+    /// Centers all axes and releases all buttons.
+    pub fn release_all(&mut self) {
+        self.axes = [None; AXES];
+        self.buttons = [false; BUTTONS];
+    }
+
+    /// Sets how far held axes are pushed, clamped to `0..=1`.
+    pub fn set_deflection(&mut self, deflection: f32) {
+        self.deflection = deflection.clamp(0.0, 1.0);
+    }
+
+    /// Makes reads fail with [`Error::Adc`] until cleared, as a broken wire would.
+    pub fn set_failing(&mut self, failing: bool) {
+        self.failing = failing;
+    }
+
+    /// Reports the input as stale until cleared, as a wireless gamepad out of range would.
+    /// Reads still return the held keys.
+    pub fn set_stale(&mut self, stale: bool) {
+        self.stale = stale;
+    }
+}
+
+impl Gamepad for SimGamepad {
+    /// Raw values as a stick at its ends or at rest would give with the default axis config.
+    fn read_raw_state(&mut self) -> Result<RawState, Error> {
+        if self.failing {
+            return Err(Error::Adc);
+        }
+        let config = AxisConfig::default();
+        let center = (config.min_value + config.max_value) / 2;
+        Ok(RawState {
+            axes: self.axes.map(|held| match held {
+                None => center,
+                Some(false) => config.min_value,
+                Some(true) => config.max_value,
+            }),
+            buttons: self.buttons,
+        })
+    }
+
+    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error> {
+        if self.failing {
+            return Err(Error::Adc);
+        }
+        let span = output.end.saturating_sub(output.start) as f32;
+        let step = output.start + (span * self.deflection) as u32;
+        Ok(State {
+            axes: self.axes.map(|held| match held {
+                None => Position::Center,
+                Some(false) => Position::Low(step),
+                Some(true) => Position::High(step),
+            }),
+            buttons: self.buttons,
+        })
+    }
+
+    /// Keys need no calibration, the default axis configs are returned.
+    fn calibrate(
+        &mut self,
+        _clock: &impl Clock,
+        _hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        Ok([AxisConfig::default(); AXES])
+    }
+
+    fn is_stale(&self) -> bool {
+        self.stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn servo_steps_are_clamped_to_the_limits() {
+        let mut servo = SimServo::new(90.0);
+        servo.set_limits(30.0, 150.0).unwrap();
+        servo.set_dir(Dir::CCW);
+        assert_eq!(servo.step_deg(50.0).unwrap(), StepResult::Stepped);
+        assert_eq!(servo.step_deg(20.0).unwrap(), StepResult::ClampedToMax);
+        assert_eq!(servo.get_angle(), 150.0);
+        servo.set_dir(Dir::CW);
+        assert_eq!(servo.step_deg(200.0).unwrap(), StepResult::ClampedToMin);
+        assert_eq!(servo.get_angle(), 30.0);
+    }
+
+    #[test]
+    fn servo_rejects_angles_out_of_the_limits() {
+        let mut servo = SimServo::new(90.0);
+        servo.set_limits(30.0, 150.0).unwrap();
+        assert!(matches!(
+            servo.set_angle(160.0),
+            Err(ServoError::LimitReached { limit }) if limit == 150.0
+        ));
+        assert_eq!(servo.get_angle(), 90.0);
+        assert!(matches!(
+            servo.set_limits(100.0, 50.0),
+            Err(ServoError::InvalidLimits)
+        ));
+    }
+
+    #[test]
+    fn keys_hold_axes_and_buttons() {
+        let mut gamepad = SimGamepad::new();
+        assert!(gamepad.press(b'W'));
+        assert!(gamepad.press(b'2'));
+        assert!(!gamepad.press(b'z'));
+        let state = gamepad.read_state(&(0..100)).unwrap();
+        assert_eq!(state.axes[1], Position::High(50));
+        assert_eq!(state.axes[0], Position::Center);
+        assert!(state.buttons[1]);
+
+        gamepad.release_all();
+        let state = gamepad.read_state(&(0..100)).unwrap();
+        assert_eq!(state, State::default());
+    }
+}
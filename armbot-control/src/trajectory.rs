@@ -0,0 +1,260 @@
+//! Queue of timed joint moves, played back by the control loop while the sticks are centered.
+//!
+//! A segment moves every joint from where the previous one ended to its targets in its
+//! duration, all joints arrive together. The first segment starts from the angles of the arm.
+//! Playback, G-code and network control push segments, [`crate::armbot::ArmBot::do_step`]
+//! takes a step of the running one every control period.
+
+use core::time::Duration;
+
+use crate::{
+    error::Error,
+    units::Degrees,
+    util::{interp::lerp, ring::RingBuffer, spline::hermite},
+};
+
+/// Max number of segments waiting in the queue.
+pub const MAX_SEGMENTS: usize = 16;
+
+/// How joints move along a segment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    /// Constant speed, the speed jumps between segments.
+    Linear,
+    /// Speed changes smoothly through the segment ends and is zero at the end of the queue.
+    #[default]
+    Cubic,
+}
+
+/// Move of all joints to the targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment<const N: usize> {
+    pub targets: [Degrees; N],
+    pub duration: Duration,
+}
+
+impl<const N: usize> Segment<N> {
+    pub const fn new(targets: [Degrees; N], duration: Duration) -> Self {
+        Self { targets, duration }
+    }
+}
+
+/// Segment being played.
+#[derive(Debug, Clone, Copy)]
+struct Running<const N: usize> {
+    start: [f32; N],
+    end: [f32; N],
+    /// Speed at the start and at the end, in degrees per length of the segment.
+    start_tangent: [f32; N],
+    end_tangent: [f32; N],
+    /// Steps the segment takes.
+    steps: u32,
+    /// Steps made so far.
+    done: u32,
+    /// Speed at the end in °/s, the start speed of the next segment.
+    end_speed: [f32; N],
+}
+
+/// Segments waiting to be played and the one being played.
+#[derive(Debug, Clone)]
+pub struct Trajectory<const N: usize> {
+    interpolation: Interpolation,
+    queue: RingBuffer<Segment<N>, MAX_SEGMENTS>,
+    running: Option<Running<N>>,
+    /// Speed the last segment ended with in °/s, zero when the arm was at rest.
+    speed: [f32; N],
+}
+
+impl<const N: usize> Trajectory<N> {
+    pub const fn new(interpolation: Interpolation) -> Self {
+        Self {
+            interpolation,
+            queue: RingBuffer::new(),
+            running: None,
+            speed: [0.0; N],
+        }
+    }
+
+    /// Adds a segment to the end of the queue, fails if the queue is full.
+    pub fn push(&mut self, segment: Segment<N>) -> Result<(), Error> {
+        if self.queue.is_full() {
+            return Err(Error::Other("trajectory queue is full"));
+        }
+        self.queue.push(segment);
+        Ok(())
+    }
+
+    /// Returns true if no segment is running or waiting.
+    pub fn is_idle(&self) -> bool {
+        self.running.is_none() && self.queue.is_empty()
+    }
+
+    /// Number of segments waiting, without the running one.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Drops the running and the waiting segments, the arm stays where it is.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.running = None;
+        self.speed = [0.0; N];
+    }
+
+    /// Advances by a control period and returns the angles the joints should be at,
+    /// `None` when idle. A new segment starts from the `current` angles of the arm.
+    pub fn next_angles(&mut self, current: &[Degrees; N]) -> Option<[Degrees; N]> {
+        if self.running.is_none() {
+            let segment = self.queue.pop()?.value;
+            let start = current.map(Degrees::get);
+            self.running = Some(self.start(&start, &segment));
+        }
+        let running = self.running.as_mut()?;
+        running.done += 1;
+        let t = running.done as f32 / running.steps as f32;
+
+        let angles = core::array::from_fn(|idx| {
+            let (start, end) = (running.start[idx], running.end[idx]);
+            let angle = match self.interpolation {
+                Interpolation::Linear => lerp(start, end, t),
+                Interpolation::Cubic => hermite(
+                    start,
+                    end,
+                    running.start_tangent[idx],
+                    running.end_tangent[idx],
+                    t,
+                ),
+            };
+            Degrees::new(angle)
+        });
+        if running.done >= running.steps {
+            self.speed = running.end_speed;
+            self.running = None;
+            if self.queue.is_empty() {
+                self.speed = [0.0; N];
+            }
+        }
+        Some(angles)
+    }
+
+    /// Prepares the segment for playing. The end speed is taken from the segment queued
+    /// after it, a segment pushed later ends at rest.
+    fn start(&self, start: &[f32; N], segment: &Segment<N>) -> Running<N> {
+        let period = crate::CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0;
+        let secs = segment.duration.as_secs_f32().max(period);
+        let steps = libm::ceilf(secs / period) as u32;
+        let end = segment.targets.map(Degrees::get);
+        let next = self.queue.iter().next().map(|entry| &entry.value);
+
+        let end_speed = core::array::from_fn(|idx| {
+            let Some(next) = next else {
+                return 0.0;
+            };
+            let speed = (end[idx] - start[idx]) / secs;
+            let next_secs = next.duration.as_secs_f32().max(period);
+            let next_speed = (next.targets[idx].get() - end[idx]) / next_secs;
+            // turning joints stop at the waypoint instead of overshooting it
+            if speed * next_speed <= 0.0 {
+                0.0
+            } else {
+                (speed + next_speed) / 2.0
+            }
+        });
+        Running {
+            start: *start,
+            end,
+            start_tangent: self.speed.map(|speed| speed * secs),
+            end_tangent: end_speed.map(|speed| speed * secs),
+            steps,
+            done: 0,
+            end_speed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS_100: Duration = Duration::from_millis(100);
+
+    fn targets(angles: [f32; 2]) -> [Degrees; 2] {
+        angles.map(Degrees::new)
+    }
+
+    /// Plays the trajectory to the end, returns the angles of every step.
+    fn play(trajectory: &mut Trajectory<2>, start: [f32; 2]) -> Vec<[f32; 2]> {
+        let mut current = targets(start);
+        let mut steps = Vec::new();
+        while let Some(angles) = trajectory.next_angles(&current) {
+            current = angles;
+            steps.push(angles.map(Degrees::get));
+        }
+        steps
+    }
+
+    #[test]
+    fn linear_segment_steps_evenly() {
+        let mut trajectory = Trajectory::new(Interpolation::Linear);
+        trajectory
+            .push(Segment::new(targets([10.0, -10.0]), MS_100))
+            .unwrap();
+        let steps = play(&mut trajectory, [0.0, 0.0]);
+        assert_eq!(steps.len(), 10);
+        for (idx, [first, second]) in steps.iter().enumerate() {
+            let expected = (idx + 1) as f32;
+            assert!((first - expected).abs() < 1e-4, "{steps:?}");
+            assert!((second + expected).abs() < 1e-4, "{steps:?}");
+        }
+        assert!(trajectory.is_idle());
+    }
+
+    #[test]
+    fn cubic_segments_end_at_their_targets() {
+        let mut trajectory = Trajectory::new(Interpolation::Cubic);
+        trajectory
+            .push(Segment::new(targets([10.0, 0.0]), MS_100))
+            .unwrap();
+        trajectory
+            .push(Segment::new(targets([20.0, 5.0]), MS_100))
+            .unwrap();
+        assert_eq!(trajectory.pending(), 2);
+        let steps = play(&mut trajectory, [0.0, 0.0]);
+        assert_eq!(steps.len(), 20);
+        assert_eq!(steps[9], [10.0, 0.0]);
+        assert_eq!(steps[19], [20.0, 5.0]);
+        // the first joint keeps going through the waypoint
+        assert!(steps.windows(2).all(|pair| pair[1][0] >= pair[0][0]));
+    }
+
+    #[test]
+    fn turning_joint_stops_at_the_waypoint() {
+        let mut trajectory = Trajectory::new(Interpolation::Cubic);
+        trajectory
+            .push(Segment::new(targets([10.0, 0.0]), MS_100))
+            .unwrap();
+        trajectory
+            .push(Segment::new(targets([0.0, 0.0]), MS_100))
+            .unwrap();
+        let steps = play(&mut trajectory, [0.0, 0.0]);
+        let peak = steps.iter().map(|[angle, _]| *angle).fold(0.0, f32::max);
+        assert_eq!(peak, 10.0);
+    }
+
+    #[test]
+    fn full_queue_rejects_segments() {
+        let mut trajectory = Trajectory::new(Interpolation::Linear);
+        for _ in 0..MAX_SEGMENTS {
+            trajectory
+                .push(Segment::new(targets([1.0, 1.0]), MS_100))
+                .unwrap();
+        }
+        assert!(trajectory
+            .push(Segment::new(targets([1.0, 1.0]), MS_100))
+            .is_err());
+        trajectory.clear();
+        assert!(trajectory.is_idle());
+        assert_eq!(trajectory.next_angles(&targets([0.0, 0.0])), None);
+    }
+}
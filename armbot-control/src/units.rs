@@ -0,0 +1,76 @@
+//! Typed physical units, so angles can't be mixed up with raw numbers.
+use core::{
+    fmt,
+    ops::{Add, Sub},
+};
+
+use crate::{error::Error, util::Scalar};
+
+/// Angle in degrees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Degrees(pub f32);
+
+impl Degrees {
+    pub const ZERO: Degrees = Degrees(0.0);
+
+    pub const fn new(val: f32) -> Self {
+        Self(val)
+    }
+
+    pub const fn get(self) -> f32 {
+        self.0
+    }
+
+    /// Angle from whole degrees, as written in configs and settings.
+    pub const fn from_whole(val: u16) -> Self {
+        Self(val as f32)
+    }
+
+    /// Rounds to whole degrees, fails on negative, too large or non finite angles.
+    pub fn to_whole(self) -> Result<u16, Error> {
+        if !self.0.is_finite() || self.0 < 0.0 || self.0 > u16::MAX as f32 {
+            return Err(Error::OutOfRange("angle doesn't fit whole degrees"));
+        }
+        Ok(u16::from_f64_rounded(self.0 as f64))
+    }
+
+    /// Angle from hundredths of a degree, as stored in settings.
+    pub fn from_hundredths(val: u16) -> Self {
+        Self(val as f32 / 100.0)
+    }
+
+    /// Rounds to hundredths of a degree, fails on negative, too large or non finite angles.
+    pub fn to_hundredths(self) -> Result<u16, Error> {
+        let val = self.0 * 100.0;
+        if !val.is_finite() || val < 0.0 || val > u16::MAX as f32 {
+            return Err(Error::OutOfRange(
+                "angle doesn't fit hundredths of a degree",
+            ));
+        }
+        Ok(u16::from_f64_rounded(val as f64))
+    }
+}
+
+impl Add for Degrees {
+    type Output = Degrees;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Degrees(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Degrees {
+    type Output = Degrees;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Degrees(self.0 - rhs.0)
+    }
+}
+
+impl fmt::Display for Degrees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}°", self.0)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::Error;
+
+pub mod angle;
+pub mod debounce;
+pub mod filter;
+pub mod interp;
+pub mod pid;
+pub mod ring;
+#[cfg(feature = "serde")]
+pub mod serde_array;
+pub mod slew;
+pub mod spline;
+pub mod table;
+
+/// Number that can be re-mapped between ranges.
+pub trait Scalar: Copy + PartialOrd {
+    fn to_f64(self) -> f64;
+
+    /// Converts back from `f64`, integers are rounded half away from zero
+    /// and saturate at type bounds.
+    fn from_f64_rounded(val: f64) -> Self;
+}
+
+macro_rules! impl_scalar {
+    (int $($t:ty),+) => {$(
+        impl Scalar for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64_rounded(val: f64) -> Self {
+                // `as` truncates toward zero and saturates
+                if val >= 0.0 {
+                    (val + 0.5) as $t
+                } else {
+                    (val - 0.5) as $t
+                }
+            }
+        }
+    )+};
+    (float $($t:ty),+) => {$(
+        impl Scalar for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64_rounded(val: f64) -> Self {
+                val as $t
+            }
+        }
+    )+};
+}
+
+impl_scalar!(int u8, u16, u32, u64, i8, i16, i32, i64);
+impl_scalar!(float f32, f64);
+
+/// Re-maps a number from one range to another.
+///
+/// Value is clamped to `from` range first. Both ranges may be inverted (`min > max`) and signed,
+/// e.g. mapping `0..100` to `10..-10` gives `-10` for `100`.
+/// Returns an error if `from` range is empty, since any output would be arbitrary.
+/// Integer results are rounded to the nearest value, so the whole output range gets equal share
+/// of the input.
+pub fn rescale<T: Scalar>(
+    from: T,
+    from_min: T,
+    from_max: T,
+    to_min: T,
+    to_max: T,
+) -> Result<T, Error> {
+    let (from, from_min, from_max) = (from.to_f64(), from_min.to_f64(), from_max.to_f64());
+    let (to_min, to_max) = (to_min.to_f64(), to_max.to_f64());
+
+    let from_range = from_max - from_min;
+    if from_range == 0.0 {
+        return Err(Error::DegenerateRange);
+    }
+
+    // position within `from` range, 0 at from_min and 1 at from_max
+    let ratio = ((from - from_min) / from_range).clamp(0.0, 1.0);
+    Ok(T::from_f64_rounded(to_min + ratio * (to_max - to_min)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescales_and_clamps() {
+        assert_eq!(rescale(50u32, 0, 100, 0, 1000).unwrap(), 500);
+        assert_eq!(rescale(150u32, 0, 100, 0, 1000).unwrap(), 1000);
+        assert_eq!(rescale(2.5f32, 0.0, 10.0, 0.0, 1.0).unwrap(), 0.25);
+    }
+
+    #[test]
+    fn rounds_integers_to_the_nearest() {
+        assert_eq!(rescale(1u8, 0, 3, 0, 2).unwrap(), 1);
+        assert_eq!(rescale(2u8, 0, 3, 0, 2).unwrap(), 1);
+        assert_eq!(rescale(-1i8, -3, 0, -2, 0).unwrap(), -1);
+    }
+
+    #[test]
+    fn inverted_ranges() {
+        assert_eq!(rescale(100i32, 0, 100, 10, -10).unwrap(), -10);
+        assert_eq!(rescale(25i32, 0, 100, 10, -10).unwrap(), 5);
+        // inverted input range, the value is still clamped to it
+        assert_eq!(rescale(0i32, 100, 0, 0, 10).unwrap(), 10);
+        assert_eq!(rescale(-5i32, 100, 0, 0, 10).unwrap(), 10);
+    }
+
+    #[test]
+    fn signed_ranges() {
+        assert_eq!(rescale(0i16, -100, 100, -1000, 1000).unwrap(), 0);
+        assert_eq!(rescale(-50i16, -100, 100, -1000, 1000).unwrap(), -500);
+        assert_eq!(rescale(-0.5f64, -1.0, 1.0, 0.0, 4.0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn degenerate_range_is_an_error() {
+        assert!(matches!(
+            rescale(5u32, 7, 7, 0, 10),
+            Err(Error::DegenerateRange)
+        ));
+        // an empty output range is fine, everything maps onto it
+        assert_eq!(rescale(5u32, 0, 10, 3, 3).unwrap(), 3);
+    }
+}
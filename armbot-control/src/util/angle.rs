@@ -0,0 +1,59 @@
+//! Wrap-around math for angles of a joint that can rotate full circle, in degrees.
+
+/// Full turn in degrees.
+pub const FULL_TURN: f32 = 360.0;
+
+/// Normalizes the angle into `0..360`.
+pub fn normalize(angle: f32) -> f32 {
+    let angle = angle % FULL_TURN;
+    if angle < 0.0 {
+        // a tiny negative angle plus 360 rounds up to 360 in f32
+        let angle = angle + FULL_TURN;
+        if angle >= FULL_TURN {
+            0.0
+        } else {
+            angle
+        }
+    } else {
+        angle
+    }
+}
+
+/// Shortest signed rotation from `from` to `to`, in `-180..=180`.
+/// Positive is counter-clockwise (increasing angle).
+pub fn shortest_diff(from: f32, to: f32) -> f32 {
+    let diff = normalize(to - from);
+    if diff > FULL_TURN / 2.0 {
+        diff - FULL_TURN
+    } else {
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_into_a_turn() {
+        assert_eq!(normalize(0.0), 0.0);
+        assert_eq!(normalize(360.0), 0.0);
+        assert_eq!(normalize(370.0), 10.0);
+        assert_eq!(normalize(-90.0), 270.0);
+        assert_eq!(normalize(-720.0), 0.0);
+        // rounds up to a full turn in f32
+        assert_eq!(normalize(-1e-6), 0.0);
+    }
+
+    #[test]
+    fn shortest_diff_crosses_the_seam() {
+        assert_eq!(shortest_diff(10.0, 30.0), 20.0);
+        assert_eq!(shortest_diff(30.0, 10.0), -20.0);
+        assert_eq!(shortest_diff(170.0, -170.0), 20.0);
+        assert_eq!(shortest_diff(-170.0, 170.0), -20.0);
+        assert_eq!(shortest_diff(350.0, 10.0), 20.0);
+        // half a turn either way is +180
+        assert_eq!(shortest_diff(0.0, 180.0), 180.0);
+        assert_eq!(shortest_diff(0.0, -180.0), 180.0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Debouncing of digital inputs (buttons, limit switches).
+
+use core::time::Duration;
+
+use crate::clock::Instant;
+
+/// When a new raw level is accepted as the stable one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DebounceMode {
+    /// Level must be read the same this many updates in a row.
+    Count(u32),
+    /// Level must stay the same for at least this long.
+    Time(Duration),
+}
+
+/// Edge of the debounced level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// Filters out contact bounce of a digital input.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    mode: DebounceMode,
+    /// Debounced level.
+    stable: bool,
+    /// Last raw level that differs from the stable one.
+    candidate: bool,
+    /// Number of updates the candidate was seen in a row.
+    count: u32,
+    /// When the candidate was first seen.
+    since: Instant,
+}
+
+impl Debouncer {
+    pub fn new(mode: DebounceMode, initial: bool) -> Self {
+        Self {
+            mode,
+            stable: initial,
+            candidate: initial,
+            count: 0,
+            since: Instant::default(),
+        }
+    }
+
+    /// Feeds a raw reading taken at `now`.
+    /// Returns the edge if the debounced level has changed with this reading.
+    pub fn update(&mut self, raw: bool, now: Instant) -> Option<Edge> {
+        if raw == self.stable {
+            self.candidate = raw;
+            self.count = 0;
+            return None;
+        }
+
+        if raw != self.candidate || self.count == 0 {
+            self.candidate = raw;
+            self.count = 0;
+            self.since = now;
+        }
+        self.count = self.count.saturating_add(1);
+
+        let settled = match self.mode {
+            DebounceMode::Count(required) => self.count >= required,
+            DebounceMode::Time(required) => now.duration_since(self.since) >= required,
+        };
+        if !settled {
+            return None;
+        }
+
+        self.stable = raw;
+        self.count = 0;
+        Some(if raw { Edge::Rising } else { Edge::Falling })
+    }
+
+    /// Returns the debounced level.
+    pub fn is_high(&self) -> bool {
+        self.stable
+    }
+
+    /// Forces the debounced level, e.g. after the input was re-initialized.
+    pub fn reset(&mut self, level: bool) {
+        self.stable = level;
+        self.candidate = level;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1000)
+    }
+
+    #[test]
+    fn count_mode_needs_readings_in_a_row() {
+        let mut debouncer = Debouncer::new(DebounceMode::Count(3), false);
+        assert_eq!(debouncer.update(true, ms(0)), None);
+        assert_eq!(debouncer.update(true, ms(0)), None);
+        assert_eq!(debouncer.update(true, ms(0)), Some(Edge::Rising));
+        assert!(debouncer.is_high());
+        assert_eq!(debouncer.update(true, ms(0)), None);
+    }
+
+    #[test]
+    fn time_mode_needs_the_level_to_hold() {
+        let mut debouncer = Debouncer::new(DebounceMode::Time(Duration::from_millis(10)), true);
+        assert_eq!(debouncer.update(false, ms(100)), None);
+        assert_eq!(debouncer.update(false, ms(105)), None);
+        assert_eq!(debouncer.update(false, ms(110)), Some(Edge::Falling));
+        assert!(!debouncer.is_high());
+    }
+
+    #[test]
+    fn bouncing_input_is_rejected() {
+        let mut debouncer = Debouncer::new(DebounceMode::Count(3), false);
+        for _ in 0..10 {
+            assert_eq!(debouncer.update(true, ms(0)), None);
+            assert_eq!(debouncer.update(false, ms(0)), None);
+        }
+        assert!(!debouncer.is_high());
+
+        let mut debouncer = Debouncer::new(DebounceMode::Time(Duration::from_millis(10)), false);
+        assert_eq!(debouncer.update(true, ms(0)), None);
+        assert_eq!(debouncer.update(false, ms(2)), None);
+        // the bounce restarts the wait
+        assert_eq!(debouncer.update(true, ms(4)), None);
+        assert_eq!(debouncer.update(true, ms(13)), None);
+        assert_eq!(debouncer.update(true, ms(14)), Some(Edge::Rising));
+    }
+
+    #[test]
+    fn reset_forces_the_level() {
+        let mut debouncer = Debouncer::new(DebounceMode::Count(2), false);
+        debouncer.update(true, ms(0));
+        debouncer.reset(true);
+        assert!(debouncer.is_high());
+        assert_eq!(debouncer.update(true, ms(0)), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Filters for noisy readings (ADC, gamepad sticks).
+
+/// Median of the last `N` values, removes single spikes that an average would smear.
+#[derive(Debug, Clone)]
+pub struct MedianFilter<const N: usize> {
+    values: [u32; N],
+    /// Index of the slot for the next value.
+    next: usize,
+    /// Number of values pushed so far, up to `N`.
+    len: usize,
+}
+
+impl<const N: usize> MedianFilter<N> {
+    pub const fn new() -> Self {
+        Self {
+            values: [0; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds a value and returns the median of the last `N` values,
+    /// or of all values pushed so far if there are fewer.
+    pub fn push(&mut self, val: u32) -> u32 {
+        self.values[self.next] = val;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+        self.value()
+    }
+
+    /// Returns the current median, 0 if nothing was pushed.
+    pub fn value(&self) -> u32 {
+        if self.len == 0 {
+            return 0;
+        }
+
+        // insertion sort, N is small
+        let mut sorted = self.values;
+        let sorted = &mut sorted[..self.len];
+        for i in 1..sorted.len() {
+            let mut j = i;
+            while j > 0 && sorted[j - 1] > sorted[j] {
+                sorted.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        sorted[sorted.len() / 2]
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<const N: usize> Default for MedianFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponential moving average, smooths noise keeping a single value.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    /// Weight of a new value.
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    /// `alpha` in `0.0..=1.0` is the weight of a new value, lower is smoother but lags more.
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, value: None }
+    }
+
+    /// Adds a value and returns the average, the first value is taken as is.
+    pub fn push(&mut self, val: u32) -> u32 {
+        let val = val as f32;
+        let avg = match self.value {
+            Some(avg) => avg + self.alpha * (val - avg),
+            None => val,
+        };
+        self.value = Some(avg);
+        self.value()
+    }
+
+    /// Returns the current average rounded, 0 if nothing was pushed.
+    pub fn value(&self) -> u32 {
+        self.value.map_or(0, |avg| (avg + 0.5) as u32)
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_rejects_a_spike() {
+        let mut median = MedianFilter::<3>::new();
+        for val in [100, 101, 99] {
+            median.push(val);
+        }
+        assert_eq!(median.push(4000), 101);
+        assert_eq!(median.push(100), 100);
+        // two spikes in a row are a real change
+        median.push(4000);
+        assert_eq!(median.push(4000), 4000);
+    }
+
+    #[test]
+    fn median_works_before_the_window_fills() {
+        let mut median = MedianFilter::<5>::new();
+        assert_eq!(median.value(), 0);
+        assert_eq!(median.push(10), 10);
+        assert_eq!(median.push(30), 30);
+        assert_eq!(median.push(20), 20);
+        median.reset();
+        assert_eq!(median.value(), 0);
+    }
+
+    #[test]
+    fn ema_follows_a_step() {
+        let mut ema = Ema::new(0.5);
+        assert_eq!(ema.value(), 0);
+        assert_eq!(ema.push(0), 0);
+        let steps: Vec<u32> = (0..6).map(|_| ema.push(1000)).collect();
+        assert_eq!(steps, [500, 750, 875, 938, 969, 984]);
+        ema.reset();
+        assert_eq!(ema.push(1000), 1000);
+    }
+}
@@ -0,0 +1,129 @@
+//! Interpolation and easing math shared by motion, playback and input curves.
+//!
+//! `t` is a normalized parameter, 0 at the start and 1 at the end; it's clamped to `0..=1`
+//! by every function taking it, so callers don't have to.
+
+/// Linear interpolation between `a` and `b`.
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    a + (b - a) * t
+}
+
+/// Inverse of [`lerp`]: returns `t` at which `lerp(a, b, t) == val`, clamped to `0..=1`.
+/// Returns 0 for an empty range.
+pub fn inverse_lerp(a: f32, b: f32, val: f32) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    ((val - a) / (b - a)).clamp(0.0, 1.0)
+}
+
+/// Hermite smoothstep, 0 below `edge0` and 1 above `edge1` with zero slope at both edges.
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = inverse_lerp(edge0, edge1, x);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Cubic ease-in: starts slow, ends fast.
+pub fn ease_in_cubic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * t
+}
+
+/// Cubic ease-out: starts fast, ends slow.
+pub fn ease_out_cubic(t: f32) -> f32 {
+    let t = 1.0 - t.clamp(0.0, 1.0);
+    1.0 - t * t * t
+}
+
+/// Cubic ease-in-out: slow at both ends.
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        let t = -2.0 * t + 2.0;
+        1.0 - t * t * t / 2.0
+    }
+}
+
+/// Easing curve selectable in configs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    #[default]
+    Linear,
+    Smoothstep,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+}
+
+impl Easing {
+    /// Maps normalized progress `t` through the curve.
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t.clamp(0.0, 1.0),
+            Easing::Smoothstep => smoothstep(0.0, 1.0, t),
+            Easing::CubicIn => ease_in_cubic(t),
+            Easing::CubicOut => ease_out_cubic(t),
+            Easing::CubicInOut => ease_in_out_cubic(t),
+        }
+    }
+
+    /// Interpolates between `a` and `b` along the curve.
+    pub fn interpolate(&self, a: f32, b: f32, t: f32) -> f32 {
+        lerp(a, b, self.apply(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Easing; 5] = [
+        Easing::Linear,
+        Easing::Smoothstep,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+    ];
+
+    #[test]
+    fn lerp_clamps_and_inverts() {
+        assert_eq!(lerp(10.0, 20.0, 0.25), 12.5);
+        assert_eq!(lerp(10.0, 20.0, -1.0), 10.0);
+        assert_eq!(lerp(10.0, 20.0, 2.0), 20.0);
+        assert_eq!(inverse_lerp(10.0, 20.0, 12.5), 0.25);
+        assert_eq!(inverse_lerp(20.0, 10.0, 12.5), 0.75);
+        assert_eq!(inverse_lerp(5.0, 5.0, 7.0), 0.0);
+    }
+
+    #[test]
+    fn curves_hit_the_endpoints() {
+        for curve in CURVES {
+            assert_eq!(curve.apply(0.0), 0.0, "{curve:?}");
+            assert_eq!(curve.apply(1.0), 1.0, "{curve:?}");
+            assert_eq!(curve.apply(-0.5), 0.0, "{curve:?}");
+            assert_eq!(curve.apply(1.5), 1.0, "{curve:?}");
+            assert_eq!(curve.interpolate(10.0, 20.0, 1.0), 20.0, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn curves_stay_in_range_and_rise() {
+        for curve in CURVES {
+            let mut last = 0.0;
+            for step in 0..=100 {
+                let val = curve.apply(step as f32 / 100.0);
+                assert!((0.0..=1.0).contains(&val), "{curve:?} gives {val}");
+                assert!(val >= last, "{curve:?} falls at {step}");
+                last = val;
+            }
+        }
+        assert_eq!(Easing::CubicInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Smoothstep.apply(0.5), 0.5);
+        assert!(Easing::CubicIn.apply(0.5) < 0.5);
+        assert!(Easing::CubicOut.apply(0.5) > 0.5);
+    }
+}
@@ -0,0 +1,160 @@
+//! PID controller for closed-loop joints and the base motor.
+
+/// Gains and limits of a [`Pid`].
+#[derive(Debug, Clone)]
+pub struct PidConfig {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Output is clamped into `-output_limit..=output_limit`.
+    pub output_limit: f32,
+    /// Integral term is clamped into `-integral_limit..=integral_limit`, prevents windup.
+    pub integral_limit: f32,
+    /// Smoothing of the derivative term, `0.0` is no filtering, closer to `1.0` is smoother.
+    pub derivative_filter: f32,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        Self {
+            kp: 1.0,
+            ki: 0.0,
+            kd: 0.0,
+            output_limit: f32::MAX,
+            integral_limit: f32::MAX,
+            derivative_filter: 0.0,
+        }
+    }
+}
+
+/// PID controller with a fixed update period.
+#[derive(Debug, Clone)]
+pub struct Pid {
+    config: PidConfig,
+    integral: f32,
+    /// Measurement of the previous update, `None` right after creation or reset.
+    prev_measurement: Option<f32>,
+    derivative: f32,
+}
+
+impl Pid {
+    pub fn new(config: PidConfig) -> Self {
+        Self {
+            config,
+            integral: 0.0,
+            prev_measurement: None,
+            derivative: 0.0,
+        }
+    }
+
+    /// Computes the output for the elapsed time `dt` in seconds.
+    ///
+    /// Derivative is taken on the measurement, so setpoint jumps don't kick the output.
+    pub fn update(&mut self, setpoint: f32, measurement: f32, dt: f32) -> f32 {
+        let cfg = &self.config;
+        let error = setpoint - measurement;
+        let p = cfg.kp * error;
+
+        let d = match self.prev_measurement {
+            Some(prev) if dt > 0.0 => {
+                let raw = -(measurement - prev) / dt;
+                self.derivative =
+                    cfg.derivative_filter * self.derivative + (1.0 - cfg.derivative_filter) * raw;
+                cfg.kd * self.derivative
+            }
+            _ => 0.0,
+        };
+        self.prev_measurement = Some(measurement);
+
+        let integral =
+            (self.integral + cfg.ki * error * dt).clamp(-cfg.integral_limit, cfg.integral_limit);
+        let unclamped = p + integral + d;
+        let output = unclamped.clamp(-cfg.output_limit, cfg.output_limit);
+
+        // anti-windup: don't integrate further while saturated in the same direction
+        let saturated = output != unclamped;
+        if !saturated || (unclamped > 0.0) != (error > 0.0) {
+            self.integral = integral;
+        }
+
+        output
+    }
+
+    /// Clears the accumulated state, e.g. after the joint was re-homed.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.prev_measurement = None;
+        self.derivative = 0.0;
+    }
+
+    pub fn config(&self) -> &PidConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: PidConfig) {
+        self.config = config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = Pid::new(PidConfig {
+            kp: 10.0,
+            output_limit: 5.0,
+            ..PidConfig::default()
+        });
+        assert_eq!(pid.update(100.0, 0.0, 0.01), 5.0);
+        assert_eq!(pid.update(-100.0, 0.0, 0.01), -5.0);
+        assert_eq!(pid.update(0.2, 0.0, 0.01), 2.0);
+    }
+
+    #[test]
+    fn integral_doesnt_wind_up_while_saturated() {
+        let mut pid = Pid::new(PidConfig {
+            kp: 1.0,
+            ki: 10.0,
+            output_limit: 5.0,
+            ..PidConfig::default()
+        });
+        for _ in 0..100 {
+            assert_eq!(pid.update(10.0, 0.0, 0.01), 5.0);
+        }
+        // the integral stopped growing once the output saturated, so it recovers at once
+        let output = pid.update(0.0, 1.0, 0.01);
+        assert!(output < 0.0, "{output}");
+    }
+
+    #[test]
+    fn integral_is_limited() {
+        let mut pid = Pid::new(PidConfig {
+            kp: 0.0,
+            ki: 1.0,
+            integral_limit: 2.0,
+            ..PidConfig::default()
+        });
+        for _ in 0..100 {
+            pid.update(1.0, 0.0, 0.1);
+        }
+        assert_eq!(pid.update(1.0, 0.0, 0.1), 2.0);
+        pid.reset();
+        assert_eq!(pid.update(1.0, 0.0, 0.1), 0.1);
+    }
+
+    #[test]
+    fn derivative_is_taken_on_the_measurement() {
+        let mut pid = Pid::new(PidConfig {
+            kp: 0.0,
+            kd: 1.0,
+            ..PidConfig::default()
+        });
+        assert_eq!(pid.update(0.0, 0.0, 0.1), 0.0);
+        // a setpoint jump doesn't kick the output
+        assert_eq!(pid.update(50.0, 0.0, 0.1), 0.0);
+        // a rising measurement pushes back
+        assert_eq!(pid.update(50.0, 1.0, 0.1), -10.0);
+    }
+}
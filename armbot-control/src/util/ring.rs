@@ -0,0 +1,149 @@
+//! Fixed-size ring buffer for telemetry, log streaming and input recording.
+
+/// Entry of a [`RingBuffer`] with its sequence number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<T> {
+    /// Number of the entry since the buffer was created, never reused.
+    /// A gap between sequence numbers seen by the reader means entries were overwritten.
+    pub seq: u32,
+    pub value: T,
+}
+
+/// Ring buffer of the last `N` values, a full buffer overwrites its oldest value.
+///
+/// `N` must be a power of two, so the slot of a sequence number doesn't jump
+/// when the sequence numbers wrap around.
+///
+/// Buffer isn't synchronized, a producer in an interrupt and a consumer in the main loop
+/// share it through a `critical_section::Mutex`.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T: Copy, const N: usize> {
+    slots: [Option<Entry<T>>; N],
+    /// Sequence number of the next pushed value.
+    next_seq: u32,
+    /// Sequence number of the oldest value not popped yet.
+    read_seq: u32,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        const { assert!(N.is_power_of_two(), "ring size must be a power of two") };
+        Self {
+            slots: [None; N],
+            next_seq: 0,
+            read_seq: 0,
+        }
+    }
+
+    /// Adds a value, overwriting the oldest one if the buffer is full.
+    /// Returns the sequence number of the added value.
+    pub fn push(&mut self, value: T) -> u32 {
+        let seq = self.next_seq;
+        self.slots[seq as usize % N] = Some(Entry { seq, value });
+        self.next_seq = seq.wrapping_add(1);
+        if self.len() > N {
+            // oldest unread value was overwritten
+            self.read_seq = self.next_seq.wrapping_sub(N as u32);
+        }
+        seq
+    }
+
+    /// Removes and returns the oldest unread value.
+    pub fn pop(&mut self) -> Option<Entry<T>> {
+        if self.is_empty() {
+            return None;
+        }
+        let entry = self.slots[self.read_seq as usize % N];
+        self.read_seq = self.read_seq.wrapping_add(1);
+        entry
+    }
+
+    /// Returns the value with the sequence number, if it wasn't overwritten yet.
+    /// Popped values stay available until overwritten.
+    pub fn get(&self, seq: u32) -> Option<&Entry<T>> {
+        self.slots[seq as usize % N]
+            .as_ref()
+            .filter(|entry| entry.seq == seq)
+    }
+
+    /// Iterates over unread values, oldest first, without removing them.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry<T>> {
+        (0..self.len() as u32).filter_map(move |idx| self.get(self.read_seq.wrapping_add(idx)))
+    }
+
+    /// Number of unread values.
+    pub fn len(&self) -> usize {
+        self.next_seq.wrapping_sub(self.read_seq) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Sequence number the next pushed value will get.
+    pub fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    /// Drops all unread values, sequence numbers keep counting.
+    pub fn clear(&mut self) {
+        self.read_seq = self.next_seq;
+    }
+}
+
+impl<T: Copy, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_get_increasing_sequence_numbers() {
+        let mut ring = RingBuffer::<u8, 4>::new();
+        assert_eq!(ring.push(10), 0);
+        assert_eq!(ring.push(11), 1);
+        assert_eq!(ring.next_seq(), 2);
+        assert_eq!(ring.pop(), Some(Entry { seq: 0, value: 10 }));
+        // popped values stay readable
+        assert_eq!(ring.get(0), Some(&Entry { seq: 0, value: 10 }));
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(ring.push(12), 2);
+    }
+
+    #[test]
+    fn full_buffer_overwrites_the_oldest() {
+        let mut ring = RingBuffer::<u8, 4>::new();
+        for value in 0..6 {
+            ring.push(value);
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.get(1), None);
+        let seqs: Vec<u32> = ring.iter().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, [2, 3, 4, 5]);
+        assert_eq!(ring.pop(), Some(Entry { seq: 2, value: 2 }));
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut ring = RingBuffer::<u8, 4>::new();
+        ring.next_seq = u32::MAX - 2;
+        ring.read_seq = u32::MAX - 2;
+        for value in 0..6 {
+            ring.push(value);
+        }
+        let entries: Vec<(u32, u8)> = core::iter::from_fn(|| ring.pop())
+            .map(|entry| (entry.seq, entry.value))
+            .collect();
+        assert_eq!(entries, [(u32::MAX, 2), (0, 3), (1, 4), (2, 5)]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Rate-of-change limiting for commands (joystick, servo angles, speed scale).
+
+/// Limits how fast a value can change per update.
+#[derive(Debug, Clone)]
+pub struct SlewLimiter {
+    /// Max increase per update.
+    max_rise: f32,
+    /// Max decrease per update, positive number.
+    max_fall: f32,
+    value: f32,
+}
+
+impl SlewLimiter {
+    /// Creates the limiter starting at `initial`, rates are made non-negative.
+    pub fn new(max_rise: f32, max_fall: f32, initial: f32) -> Self {
+        Self {
+            max_rise: max_rise.abs(),
+            max_fall: max_fall.abs(),
+            value: initial,
+        }
+    }
+
+    /// Same rate in both directions.
+    pub fn symmetric(max_rate: f32, initial: f32) -> Self {
+        Self::new(max_rate, max_rate, initial)
+    }
+
+    /// Moves the value toward `target` by at most the allowed rate and returns it.
+    pub fn update(&mut self, target: f32) -> f32 {
+        let delta = (target - self.value).clamp(-self.max_fall, self.max_rise);
+        self.value += delta;
+        self.value
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Returns true if the last update has reached the target.
+    pub fn is_settled(&self, target: f32) -> bool {
+        self.value == target
+    }
+
+    /// Jumps to `value` without rate limiting, e.g. after homing.
+    pub fn reset(&mut self, value: f32) {
+        self.value = value;
+    }
+
+    pub fn set_rates(&mut self, max_rise: f32, max_fall: f32) {
+        self.max_rise = max_rise.abs();
+        self.max_fall = max_fall.abs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_rise_and_fall() {
+        let mut slew = SlewLimiter::new(1.0, 2.0, 0.0);
+        assert_eq!(slew.update(10.0), 1.0);
+        assert_eq!(slew.update(10.0), 2.0);
+        assert_eq!(slew.update(-10.0), 0.0);
+        assert_eq!(slew.update(-10.0), -2.0);
+    }
+
+    #[test]
+    fn stops_at_the_target() {
+        let mut slew = SlewLimiter::symmetric(1.0, 0.0);
+        assert_eq!(slew.update(0.5), 0.5);
+        assert!(slew.is_settled(0.5));
+        assert!(!slew.is_settled(1.0));
+    }
+
+    #[test]
+    fn rates_are_made_positive() {
+        let mut slew = SlewLimiter::new(-1.0, -1.0, 0.0);
+        assert_eq!(slew.update(5.0), 1.0);
+        slew.set_rates(-3.0, -0.5);
+        assert_eq!(slew.update(5.0), 4.0);
+        assert_eq!(slew.update(0.0), 3.5);
+    }
+
+    #[test]
+    fn reset_jumps() {
+        let mut slew = SlewLimiter::symmetric(1.0, 0.0);
+        slew.reset(10.0);
+        assert_eq!(slew.value(), 10.0);
+        assert_eq!(slew.update(0.0), 9.0);
+    }
+}
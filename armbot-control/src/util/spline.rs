@@ -0,0 +1,52 @@
+//! Smooth interpolation between waypoints with continuous velocity.
+
+/// Cubic hermite between `p0` and `p1` with tangents `m0` and `m1`, `t` in `0..=1`.
+pub fn hermite(p0: f32, p1: f32, m0: f32, m1: f32, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+        + (t3 - 2.0 * t2 + t) * m0
+        + (-2.0 * t3 + 3.0 * t2) * p1
+        + (t3 - t2) * m1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Slope at `t` by a central difference, one sided at the ends.
+    fn slope(f: impl Fn(f32) -> f32, t: f32) -> f32 {
+        const H: f32 = 1e-3;
+        let (lo, hi) = ((t - H).max(0.0), (t + H).min(1.0));
+        (f(hi) - f(lo)) / (hi - lo)
+    }
+
+    #[test]
+    fn passes_through_the_endpoints() {
+        assert_eq!(hermite(10.0, 20.0, 5.0, -5.0, 0.0), 10.0);
+        assert_eq!(hermite(10.0, 20.0, 5.0, -5.0, 1.0), 20.0);
+        // `t` is clamped
+        assert_eq!(hermite(10.0, 20.0, 5.0, -5.0, -1.0), 10.0);
+        assert_eq!(hermite(10.0, 20.0, 5.0, -5.0, 2.0), 20.0);
+    }
+
+    #[test]
+    fn starts_and_ends_with_the_tangents() {
+        let curve = |t| hermite(0.0, 10.0, 4.0, -2.0, t);
+        assert!((slope(curve, 0.0) - 4.0).abs() < 0.05);
+        assert!((slope(curve, 1.0) + 2.0).abs() < 0.05);
+        // zero tangents ease in and out
+        let eased = |t| hermite(0.0, 10.0, 0.0, 0.0, t);
+        assert!(slope(eased, 0.0).abs() < 0.05);
+        assert_eq!(eased(0.5), 5.0);
+    }
+
+    #[test]
+    fn segments_sharing_a_tangent_join_smoothly() {
+        let first = |t| hermite(0.0, 10.0, 0.0, 12.0, t);
+        let second = |t| hermite(10.0, 30.0, 12.0, 0.0, t);
+        assert_eq!(first(1.0), second(0.0));
+        assert!((slope(first, 1.0) - slope(second, 0.0)).abs() < 0.05);
+    }
+}
@@ -0,0 +1,168 @@
+//! Piecewise-linear lookup tables for calibration curves.
+
+use crate::error::Error;
+
+/// Breakpoint of a [`Table`]: output `y` for input `x`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// Table of `N` breakpoints with linear interpolation between them.
+///
+/// Used for calibration curves, e.g. the battery voltage to charge mapping.
+#[derive(Debug, Clone)]
+pub struct Table<const N: usize> {
+    points: [Point; N],
+}
+
+impl<const N: usize> Table<N> {
+    /// Requires at least two breakpoints with strictly increasing `x`.
+    pub fn new(points: [Point; N]) -> Result<Self, Error> {
+        if N < 2 {
+            return Err(Error::Other("table needs at least two points"));
+        }
+        if points.iter().any(|p| !p.x.is_finite() || !p.y.is_finite()) {
+            return Err(Error::Other("table values must be finite"));
+        }
+        if points.windows(2).any(|w| w[1].x <= w[0].x) {
+            return Err(Error::Other("table x values must be strictly increasing"));
+        }
+        Ok(Self { points })
+    }
+
+    /// Same as [`Table::new`], but also requires `y` to be strictly increasing or
+    /// strictly decreasing, so the table can be inverted.
+    pub fn new_monotonic(points: [Point; N]) -> Result<Self, Error> {
+        let table = Self::new(points)?;
+        if !table.is_monotonic() {
+            return Err(Error::Other("table y values must be strictly monotonic"));
+        }
+        Ok(table)
+    }
+
+    /// Returns true if `y` strictly increases or strictly decreases along the table.
+    pub fn is_monotonic(&self) -> bool {
+        let rising = self.points.windows(2).all(|w| w[0].y < w[1].y);
+        let falling = self.points.windows(2).all(|w| w[0].y > w[1].y);
+        rising || falling
+    }
+
+    /// Returns interpolated `y` for `x`, inputs out of the table are clamped to its ends.
+    pub fn eval(&self, x: f32) -> f32 {
+        let first = self.points[0];
+        let last = self.points[N - 1];
+        if x <= first.x {
+            return first.y;
+        }
+        if x >= last.x {
+            return last.y;
+        }
+
+        let idx = self.points.partition_point(|p| p.x <= x) - 1;
+        interpolate(self.points[idx], self.points[idx + 1], x)
+    }
+
+    /// Returns `x` for `y`, the reverse of [`Table::eval`].
+    /// Fails unless the table is monotonic, outputs out of the table are clamped to its ends.
+    pub fn inverse(&self, y: f32) -> Result<f32, Error> {
+        if !self.is_monotonic() {
+            return Err(Error::Other("non monotonic table can't be inverted"));
+        }
+        let rising = self.points[0].y < self.points[N - 1].y;
+        let (low, high) = if rising {
+            (self.points[0], self.points[N - 1])
+        } else {
+            (self.points[N - 1], self.points[0])
+        };
+        if y <= low.y {
+            return Ok(low.x);
+        }
+        if y >= high.y {
+            return Ok(high.x);
+        }
+
+        let idx = self.points.partition_point(|p| (p.y <= y) == rising) - 1;
+        let (a, b) = (self.points[idx], self.points[idx + 1]);
+        Ok(interpolate(Point::new(a.y, a.x), Point::new(b.y, b.x), y))
+    }
+
+    pub fn points(&self) -> &[Point; N] {
+        &self.points
+    }
+}
+
+fn interpolate(a: Point, b: Point, x: f32) -> f32 {
+    a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rising() -> Table<3> {
+        Table::new_monotonic([
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 100.0),
+            Point::new(20.0, 150.0),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn interpolates_and_clamps_at_the_ends() {
+        let table = rising();
+        assert_eq!(table.eval(5.0), 50.0);
+        assert_eq!(table.eval(15.0), 125.0);
+        assert_eq!(table.eval(10.0), 100.0);
+        assert_eq!(table.eval(-5.0), 0.0);
+        assert_eq!(table.eval(25.0), 150.0);
+    }
+
+    #[test]
+    fn inverse_undoes_eval() {
+        let table = rising();
+        assert_eq!(table.inverse(125.0).unwrap(), 15.0);
+        assert_eq!(table.inverse(-1.0).unwrap(), 0.0);
+        assert_eq!(table.inverse(200.0).unwrap(), 20.0);
+
+        let falling = Table::new_monotonic([
+            Point::new(0.0, 10.0),
+            Point::new(4.0, 2.0),
+            Point::new(8.0, 0.0),
+        ])
+        .unwrap();
+        assert_eq!(falling.inverse(6.0).unwrap(), 2.0);
+        assert_eq!(falling.inverse(1.0).unwrap(), 6.0);
+        assert_eq!(falling.inverse(20.0).unwrap(), 0.0);
+        assert_eq!(falling.inverse(-1.0).unwrap(), 8.0);
+    }
+
+    #[test]
+    fn non_monotonic_table_isnt_inverted() {
+        let points = [
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 5.0),
+            Point::new(2.0, 5.0),
+        ];
+        assert!(Table::new_monotonic(points).is_err());
+        let table = Table::new(points).unwrap();
+        assert!(!table.is_monotonic());
+        assert!(table.inverse(2.0).is_err());
+    }
+
+    #[test]
+    fn bad_breakpoints_are_rejected() {
+        assert!(Table::new([Point::new(0.0, 0.0)]).is_err());
+        assert!(Table::new([Point::new(1.0, 0.0), Point::new(1.0, 1.0)]).is_err());
+        assert!(Table::new([Point::new(0.0, f32::NAN), Point::new(1.0, 1.0)]).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Kinds of failures shared by the firmware, the libraries and host tools, so an error reported
+//! by the arm means the same everywhere. Builds keep their own detailed errors and map them
+//! to these kinds.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What failed, without the details of a particular build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Error {
+    /// Analog input can't be read.
+    Adc,
+    /// Servo can't be driven, or a joint stopped responding.
+    Servo,
+    /// Gamepad can't be read.
+    Gamepad,
+    /// Config, settings or wiring is invalid.
+    Config,
+    /// Value or move is out of its allowed range.
+    Limit,
+    /// Transfer to a host or a device failed.
+    Comms,
+    /// Anything else, the detailed error tells more.
+    Other,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::Adc => "ADC error",
+            Error::Servo => "servo error",
+            Error::Gamepad => "gamepad error",
+            Error::Config => "config error",
+            Error::Limit => "limit error",
+            Error::Comms => "communication error",
+            Error::Other => "error",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,26 @@
+//! Types shared by the firmware and host tools (teach mode, storage, web UI, host CLI).
+//!
+//! Everything here is `no_std` and is serialized with postcard, so a pose saved by one
+//! interface can be read by any other.
+#![cfg_attr(not(test), no_std)]
+
+pub mod error;
+pub mod pose;
+pub mod storage;
+
+pub use error::Error;
+pub use postcard::Error as WireError;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Version of the wire format, bumped on any incompatible change of the shared types.
+pub const WIRE_VERSION: u16 = 1;
+
+/// Serializes the value into `buf`, returns the written part of the buffer.
+pub fn encode<'a, T: Serialize>(value: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8], WireError> {
+    postcard::to_slice(value, buf)
+}
+
+/// Deserializes a value written by [`encode`].
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, WireError> {
+    postcard::from_bytes(bytes)
+}
@@ -0,0 +1,55 @@
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+
+/// Max number of joints a pose can describe.
+pub const MAX_JOINTS: usize = 6;
+/// Max number of keyframes in a sequence.
+pub const MAX_KEYFRAMES: usize = 32;
+/// Max length of a sequence name in bytes.
+pub const MAX_NAME_LEN: usize = 24;
+
+/// Angles of all joints, in degrees, in the joint order of the arm.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pose {
+    pub angles: Vec<f32, MAX_JOINTS>,
+}
+
+impl Pose {
+    /// Creates a pose from angles, `None` if there are more than [`MAX_JOINTS`].
+    pub fn new(angles: &[f32]) -> Option<Self> {
+        Vec::from_slice(angles).ok().map(|angles| Self { angles })
+    }
+
+    pub fn joints(&self) -> usize {
+        self.angles.len()
+    }
+}
+
+/// Pose to reach and how long to take to reach it from the previous one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub pose: Pose,
+    /// Time to move from the previous keyframe, in milliseconds.
+    pub duration_ms: u32,
+    /// Time to hold the pose once reached, in milliseconds.
+    pub hold_ms: u32,
+}
+
+/// Named list of keyframes played one after another.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String<MAX_NAME_LEN>,
+    pub keyframes: Vec<Keyframe, MAX_KEYFRAMES>,
+    /// Start over after the last keyframe.
+    pub looped: bool,
+}
+
+impl Sequence {
+    /// Total play time of one pass, in milliseconds.
+    pub fn duration_ms(&self) -> u64 {
+        self.keyframes
+            .iter()
+            .map(|frame| frame.duration_ms as u64 + frame.hold_ms as u64)
+            .sum()
+    }
+}
@@ -0,0 +1,291 @@
+//! Files of the arm in a flash partition: settings, a script and recorded sequences.
+//!
+//! A flash filesystem like LittleFS or SPIFFS needs esp-idf or a C port, which the bare-metal
+//! firmware doesn't have. The arm keeps a few files of known kinds, so the partition is split
+//! into a fixed area per file instead, see [`File::area`]. Any [`embedded_storage::Storage`]
+//! holds the partition: esp-storage on the chip, a file or a RAM image on a host.
+//!
+//! Raw files carry their own framing, e.g. the versioned settings layout of the firmware.
+//! Typed files are written with postcard behind a header of magic, length and checksum, so an
+//! erased area reads as no file and a torn write as a corrupt one.
+
+use core::{fmt, ops::Range};
+
+use embedded_storage::Storage as Flash;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Size of a flash sector, every area starts on one.
+pub const SECTOR: u32 = 0x1000;
+/// Size of the `nvs` partition of the default partition table, which holds the files.
+pub const PARTITION_LEN: u32 = 0x6000;
+/// Number of recorded sequences kept.
+pub const SEQUENCE_SLOTS: u8 = 4;
+const _: () = assert!(
+    (2 + SEQUENCE_SLOTS as u32) * SECTOR <= PARTITION_LEN,
+    "every file needs an area in the partition"
+);
+
+/// Marks a typed file, "ARMF".
+const MAGIC: u32 = u32::from_le_bytes(*b"ARMF");
+/// magic(4) + content length(2) + checksum(2)
+const HEADER_LEN: usize = 8;
+
+/// File kept in the partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum File {
+    /// Settings and calibration, raw.
+    Settings,
+    /// Text of the script, raw, ends at the first erased or zero byte.
+    Script,
+    /// Recorded [`crate::pose::Sequence`] in the slot, typed.
+    Sequence(u8),
+}
+
+impl File {
+    /// Bytes of the partition the file may take.
+    pub fn area(self) -> Result<Range<u32>, StorageError> {
+        let sector = match self {
+            File::Settings => 0,
+            File::Script => 1,
+            File::Sequence(slot) if slot < SEQUENCE_SLOTS => 2 + slot as u32,
+            File::Sequence(_) => return Err(StorageError::NoSuchFile),
+        };
+        Ok(sector * SECTOR..(sector + 1) * SECTOR)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// Flash can't be read or written.
+    Flash,
+    NoSuchFile,
+    /// Content is larger than the area of the file or the buffer.
+    TooLarge,
+    /// Header or checksum doesn't match, e.g. after a power loss while writing.
+    Corrupt,
+    /// Content doesn't encode or decode as the type.
+    Wire(postcard::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Flash => write!(f, "flash access failed"),
+            StorageError::NoSuchFile => write!(f, "no such file"),
+            StorageError::TooLarge => write!(f, "file is too large"),
+            StorageError::Corrupt => write!(f, "file is corrupt"),
+            StorageError::Wire(err) => write!(f, "file doesn't match its type: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for StorageError {}
+
+/// Files in the partition starting at `base` of the flash.
+pub struct Storage<S> {
+    flash: S,
+    base: u32,
+}
+
+impl<S: Flash> Storage<S> {
+    pub fn new(flash: S, base: u32) -> Self {
+        Self { flash, base }
+    }
+
+    /// Flash outside of the partition, e.g. for firmware updates.
+    pub fn flash(&mut self) -> &mut S {
+        &mut self.flash
+    }
+
+    /// Reads the whole area of the file into `buf`, returns the read part.
+    pub fn read_raw<'a>(
+        &mut self,
+        file: File,
+        buf: &'a mut [u8],
+    ) -> Result<&'a [u8], StorageError> {
+        let area = file.area()?;
+        let len = buf.len().min(area.len());
+        self.flash
+            .read(self.base + area.start, &mut buf[..len])
+            .map_err(|_| StorageError::Flash)?;
+        Ok(&buf[..len])
+    }
+
+    /// Writes the bytes at the start of the area, the rest of the area is left as it was.
+    pub fn write_raw(&mut self, file: File, bytes: &[u8]) -> Result<(), StorageError> {
+        let area = file.area()?;
+        if bytes.len() > area.len() {
+            return Err(StorageError::TooLarge);
+        }
+        self.flash
+            .write(self.base + area.start, bytes)
+            .map_err(|_| StorageError::Flash)
+    }
+
+    /// Reads a typed file, `None` if it was never written. `buf` must hold the encoded file.
+    pub fn load<T: DeserializeOwned>(
+        &mut self,
+        file: File,
+        buf: &mut [u8],
+    ) -> Result<Option<T>, StorageError> {
+        let bytes = self.read_raw(file, buf)?;
+        let header = bytes.get(..HEADER_LEN).ok_or(StorageError::TooLarge)?;
+        // erased flash reads as 0xff
+        if header.iter().all(|&byte| byte == 0xff) {
+            return Ok(None);
+        }
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let stored_checksum = u16::from_le_bytes([header[6], header[7]]);
+        if magic != MAGIC {
+            return Err(StorageError::Corrupt);
+        }
+        let content = bytes
+            .get(HEADER_LEN..HEADER_LEN + len)
+            .ok_or(StorageError::Corrupt)?;
+        if checksum(content) != stored_checksum {
+            return Err(StorageError::Corrupt);
+        }
+        crate::decode(content).map(Some).map_err(StorageError::Wire)
+    }
+
+    /// Writes a typed file, `buf` is used for encoding it.
+    pub fn save<T: Serialize>(
+        &mut self,
+        file: File,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<(), StorageError> {
+        let (header, rest) = buf
+            .split_at_mut_checked(HEADER_LEN)
+            .ok_or(StorageError::TooLarge)?;
+        let content = crate::encode(value, rest).map_err(StorageError::Wire)?;
+        let len = u16::try_from(content.len()).map_err(|_| StorageError::TooLarge)?;
+        header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        header[4..6].copy_from_slice(&len.to_le_bytes());
+        header[6..8].copy_from_slice(&checksum(content).to_le_bytes());
+        self.write_raw(file, &buf[..HEADER_LEN + len as usize])
+    }
+
+    /// Erases the file, it reads as never written.
+    pub fn remove(&mut self, file: File) -> Result<(), StorageError> {
+        self.write_raw(file, &[0xff; HEADER_LEN])
+    }
+}
+
+/// Fletcher-16 checksum.
+pub fn checksum(data: &[u8]) -> u16 {
+    let (mut sum1, mut sum2) = (0u16, 0u16);
+    for byte in data {
+        sum1 = (sum1 + *byte as u16) % 255;
+        sum2 = (sum2 + sum1) % 255;
+    }
+    (sum2 << 8) | sum1
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_storage::ReadStorage;
+
+    use super::*;
+    use crate::pose::{Keyframe, Pose, Sequence};
+
+    /// Erased partition in RAM.
+    struct RamFlash([u8; PARTITION_LEN as usize]);
+
+    impl RamFlash {
+        fn new() -> Self {
+            Self([0xff; PARTITION_LEN as usize])
+        }
+    }
+
+    impl ReadStorage for RamFlash {
+        type Error = Infallible;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            bytes.copy_from_slice(&self.0[start..start + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl Flash for RamFlash {
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            self.0[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    fn sequence() -> Sequence {
+        let mut sequence = Sequence {
+            name: "wave".try_into().unwrap(),
+            looped: true,
+            ..Default::default()
+        };
+        for angles in [[90.0, 45.0, 30.0], [60.0, 90.0, 0.0]] {
+            let frame = Keyframe {
+                pose: Pose::new(&angles).unwrap(),
+                duration_ms: 500,
+                hold_ms: 100,
+            };
+            sequence.keyframes.push(frame).unwrap();
+        }
+        sequence
+    }
+
+    #[test]
+    fn saved_file_loads_back() {
+        let mut storage = Storage::new(RamFlash::new(), 0);
+        let mut buf = [0; SECTOR as usize];
+        let file = File::Sequence(1);
+        assert_eq!(storage.load::<Sequence>(file, &mut buf), Ok(None));
+
+        storage.save(file, &sequence(), &mut buf).unwrap();
+        assert_eq!(storage.load(file, &mut buf), Ok(Some(sequence())));
+        // other files are left erased
+        assert_eq!(
+            storage.load::<Sequence>(File::Sequence(0), &mut buf),
+            Ok(None)
+        );
+
+        storage.remove(file).unwrap();
+        assert_eq!(storage.load::<Sequence>(file, &mut buf), Ok(None));
+    }
+
+    #[test]
+    fn damaged_file_is_corrupt() {
+        let mut storage = Storage::new(RamFlash::new(), 0);
+        let mut buf = [0; SECTOR as usize];
+        let file = File::Sequence(0);
+        storage.save(file, &sequence(), &mut buf).unwrap();
+
+        let offset = file.area().unwrap().start + HEADER_LEN as u32;
+        storage.flash().write(offset, &[0]).unwrap();
+        assert_eq!(
+            storage.load::<Sequence>(file, &mut buf),
+            Err(StorageError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn file_must_fit_its_area() {
+        let mut storage = Storage::new(RamFlash::new(), 0);
+        let mut buf = [0; SECTOR as usize];
+        assert_eq!(
+            storage.save(File::Sequence(SEQUENCE_SLOTS), &sequence(), &mut buf),
+            Err(StorageError::NoSuchFile)
+        );
+        let large = [0; SECTOR as usize + 1];
+        assert_eq!(
+            storage.write_raw(File::Script, &large),
+            Err(StorageError::TooLarge)
+        );
+    }
+}
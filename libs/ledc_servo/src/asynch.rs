@@ -0,0 +1,49 @@
+use embassy_time::{Duration, Ticker};
+use esp_hal::ledc::timer::TimerSpeed;
+
+use crate::{Error, Servo, ServoDriver, ServoGroup};
+
+/// How often async moves update the servos, a 50Hz servo can't follow faster updates.
+const UPDATE_PERIOD: Duration = Duration::from_millis(20);
+
+impl<S: TimerSpeed> Servo<'_, S> {
+    /// Moves the servo to the angle with the speed in degrees per second
+    /// and completes when it's there, see [`Servo::goto_angle`].
+    ///
+    /// Fails with [`Error::LimitReached`] without moving if the angle is out of the limits.
+    pub async fn move_to(&mut self, angle: f64, deg_per_sec: f64) -> Result<(), Error> {
+        let (min, max) = self.limits();
+        if angle < min || angle > max {
+            let limit = if angle < min { min } else { max };
+            return Err(Error::LimitReached { limit });
+        }
+
+        self.goto_angle(angle, deg_per_sec);
+        run(UPDATE_PERIOD, |dt| self.update(dt)).await
+    }
+}
+
+impl<D: ServoDriver, const N: usize> ServoGroup<D, N> {
+    /// Moves every servo to its target and completes when all of them are there,
+    /// see [`ServoGroup::move_to`].
+    pub async fn move_to_async(
+        &mut self,
+        targets: [f64; N],
+        deg_per_sec: f64,
+    ) -> Result<(), D::Error> {
+        self.move_to(targets, deg_per_sec);
+        run(UPDATE_PERIOD, |dt| self.update(dt)).await
+    }
+}
+
+/// Calls `update` every period until it returns false.
+async fn run<E>(period: Duration, mut update: impl FnMut(f64) -> Result<bool, E>) -> Result<(), E> {
+    let dt = period.as_micros() as f64 / 1_000_000.0;
+    let mut ticker = Ticker::every(period);
+    loop {
+        ticker.next().await;
+        if !update(dt)? {
+            return Ok(());
+        }
+    }
+}
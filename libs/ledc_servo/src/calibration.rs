@@ -0,0 +1,145 @@
+use crate::ConfigError;
+
+/// Max number of points of a [`Calibration`].
+pub const MAX_CALIBRATION_POINTS: usize = 8;
+
+/// Pulse width measured for an angle of the servo shaft.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationPoint {
+    pub angle: f64,
+    pub pulse_us: u32,
+}
+
+/// Angle to pulse mapping of a servo that isn't linear, cheap SG90 clones are far from it.
+/// Pulses are interpolated linearly between the points.
+///
+/// Points are measured with the servo itself: move the horn to marks on a protractor with
+/// [`crate::Servo::set_pulse_width_us`] and record every mark:
+///
+/// ```rust,ignore
+/// let mut calibration = Calibration::new();
+/// for angle in [0.0, 45.0, 90.0, 135.0, 180.0] {
+///     // jog until the horn points at the mark
+///     calibration.insert(angle, servo.get_pulse_width_us())?;
+/// }
+/// let config = ServoConfig::builder().calibration(calibration).build()?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "CalibrationRepr")
+)]
+pub struct Calibration {
+    /// Sorted by angle, only `len` first are used.
+    points: [CalibrationPoint; MAX_CALIBRATION_POINTS],
+    len: usize,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibration {
+    pub const fn new() -> Self {
+        Self {
+            points: [CalibrationPoint {
+                angle: 0.0,
+                pulse_us: 0,
+            }; MAX_CALIBRATION_POINTS],
+            len: 0,
+        }
+    }
+
+    /// Adds a measured point, a point with the same angle is replaced.
+    pub fn insert(&mut self, angle: f64, pulse_us: u32) -> Result<(), ConfigError> {
+        if !angle.is_finite() {
+            return Err(ConfigError::InvalidCalibration);
+        }
+        let point = CalibrationPoint { angle, pulse_us };
+        let idx = self.points().partition_point(|p| p.angle < angle);
+        if idx < self.len && self.points[idx].angle == angle {
+            self.points[idx] = point;
+            return Ok(());
+        }
+        if self.len == MAX_CALIBRATION_POINTS {
+            return Err(ConfigError::CalibrationFull);
+        }
+        self.points.copy_within(idx..self.len, idx + 1);
+        self.points[idx] = point;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn points(&self) -> &[CalibrationPoint] {
+        &self.points[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Checks that the points cover `0..=max_angle` and pulses grow with the angle,
+    /// so every pulse maps back to a single angle.
+    pub(crate) fn validate(&self, max_angle: f64) -> Result<(), ConfigError> {
+        let points = self.points();
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return Err(ConfigError::InvalidCalibration);
+        };
+        if points.len() < 2 || first.angle != 0.0 || last.angle != max_angle {
+            return Err(ConfigError::InvalidCalibration);
+        }
+        if points.windows(2).any(|w| w[0].pulse_us >= w[1].pulse_us) {
+            return Err(ConfigError::InvalidCalibration);
+        }
+        Ok(())
+    }
+
+    /// Pulse width for the shaft angle, the table must be valid.
+    pub(crate) fn pulse(&self, angle: f64) -> f64 {
+        let points = self.points();
+        let idx = points
+            .partition_point(|p| p.angle < angle)
+            .clamp(1, points.len() - 1);
+        let (a, b) = (points[idx - 1], points[idx]);
+        let t = (angle - a.angle) / (b.angle - a.angle);
+        a.pulse_us as f64 + (b.pulse_us as f64 - a.pulse_us as f64) * t
+    }
+
+    /// Shaft angle for the pulse width, the table must be valid.
+    pub(crate) fn angle(&self, pulse_us: f64) -> f64 {
+        let points = self.points();
+        let idx = points
+            .partition_point(|p| (p.pulse_us as f64) < pulse_us)
+            .clamp(1, points.len() - 1);
+        let (a, b) = (points[idx - 1], points[idx]);
+        let t = (pulse_us - a.pulse_us as f64) / (b.pulse_us as f64 - a.pulse_us as f64);
+        a.angle + (b.angle - a.angle) * t
+    }
+}
+
+/// Unchecked [`Calibration`], a stored one can have any length.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct CalibrationRepr {
+    points: [CalibrationPoint; MAX_CALIBRATION_POINTS],
+    len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CalibrationRepr> for Calibration {
+    type Error = &'static str;
+
+    fn try_from(repr: CalibrationRepr) -> Result<Self, Self::Error> {
+        if repr.len > MAX_CALIBRATION_POINTS {
+            return Err("too many calibration points");
+        }
+        Ok(Self {
+            points: repr.points,
+            len: repr.len,
+        })
+    }
+}
@@ -0,0 +1,340 @@
+use core::ops::Range;
+
+use esp_hal::{
+    ledc::{
+        timer::{self, config::Duty, TimerIFace, TimerSpeed},
+        Ledc,
+    },
+    time::Rate,
+};
+
+use crate::{Calibration, ConfigError, Error};
+
+/// Pulse and PWM parameters of a servo model.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServoConfig {
+    /// Max rotation angle in degrees, reached with `max_pulse_us`.
+    pub max_angle: f64,
+    /// Pulse width for 0 degrees, in microseconds.
+    pub min_pulse_us: u32,
+    /// Pulse width for the max angle, in microseconds.
+    pub max_pulse_us: u32,
+    /// PWM frequency in Hz.
+    pub frequency: u32,
+    /// Duty resolution of the LEDC timer.
+    #[cfg_attr(feature = "serde", serde(with = "duty_bits"))]
+    pub duty: Duty,
+    /// Offset added to every pulse, in microseconds.
+    /// Corrects a horn that isn't mounted exactly at the spline tooth it should be.
+    pub trim_us: i32,
+    /// Servo is mounted mirrored: angles and step directions are flipped,
+    /// so the same command moves every joint the same way mechanically.
+    pub inverted: bool,
+    /// Measured angle to pulse mapping, replaces the linear one.
+    /// Its first and last pulses must be `min_pulse_us` and `max_pulse_us`.
+    pub calibration: Option<Calibration>,
+}
+
+impl ServoConfig {
+    /// SG90 and SG90s micro servo, 180 degrees with 0.5..2.5ms pulses at 50Hz.
+    pub fn sg90(duty: Duty) -> Self {
+        Self::custom(500..2500, 180.0, 50, duty)
+    }
+
+    /// MG90S metal gear micro servo, 180 degrees with 0.5..2.4ms pulses at 50Hz.
+    pub fn mg90s(duty: Duty) -> Self {
+        Self::custom(500..2400, 180.0, 50, duty)
+    }
+
+    /// MG996R standard servo, 180 degrees with 0.5..2.5ms pulses at 50Hz.
+    pub fn mg996r(duty: Duty) -> Self {
+        Self::custom(500..2500, 180.0, 50, duty)
+    }
+
+    /// DS3218 digital servo (270 degrees version), 0.5..2.5ms pulses at 50Hz.
+    pub fn ds3218(duty: Duty) -> Self {
+        Self::custom(500..2500, 270.0, 50, duty)
+    }
+
+    /// Servo with the pulse range in microseconds for `0..max_angle` degrees
+    /// at the frequency in Hz.
+    pub fn custom(pulse_us: Range<u32>, max_angle: f64, frequency: u32, duty: Duty) -> Self {
+        Self {
+            max_angle,
+            min_pulse_us: pulse_us.start,
+            max_pulse_us: pulse_us.end,
+            frequency,
+            duty,
+            trim_us: 0,
+            inverted: false,
+            calibration: None,
+        }
+    }
+
+    /// FS90R continuous rotation servo, 0.7..2.3ms pulses at 50Hz, stops at 1.5ms.
+    /// Use it with [`crate::ContinuousServo`], the angle is only a scale for the speed.
+    pub fn fs90r(duty: Duty) -> Self {
+        Self::custom(700..2300, 180.0, 50, duty)
+    }
+
+    /// Starts a validated config, defaults are of [`ServoConfig::sg90`] with 14 bit duty.
+    pub fn builder() -> ServoConfigBuilder {
+        ServoConfigBuilder {
+            config: Self::sg90(Duty::Duty14Bit),
+        }
+    }
+
+    /// Checks that the config produces pulses within the period and that the duty
+    /// resolution is fine enough for 1 degree steps.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.frequency == 0 {
+            return Err(ConfigError::ZeroFrequency);
+        }
+        if !self.max_angle.is_finite() || self.max_angle <= 0.0 {
+            return Err(ConfigError::InvalidMaxAngle);
+        }
+        if self.min_pulse_us >= self.max_pulse_us {
+            return Err(ConfigError::EmptyPulseRange);
+        }
+        if (self.min_pulse_us as i64 + self.trim_us as i64) < 0 {
+            return Err(ConfigError::TrimOutOfRange);
+        }
+        let period_us = self.period_us();
+        let (_, max_pulse_us) = self.pulse_range();
+        if max_pulse_us > period_us {
+            return Err(ConfigError::PulseLongerThanPeriod {
+                pulse_us: max_pulse_us,
+                period_us,
+            });
+        }
+        let counts_per_degree = self.duty_per_degree() as f32;
+        if counts_per_degree < 1.0 {
+            return Err(ConfigError::ResolutionTooLow { counts_per_degree });
+        }
+        if let Some(calibration) = &self.calibration {
+            calibration.validate(self.max_angle)?;
+            let points = calibration.points();
+            if points[0].pulse_us != self.min_pulse_us
+                || points[points.len() - 1].pulse_us != self.max_pulse_us
+            {
+                return Err(ConfigError::InvalidCalibration);
+            }
+        }
+        Ok(())
+    }
+
+    /// Configures a LEDC timer with the frequency and duty resolution of the servo.
+    /// Servos with the same config can share the timer. Fails if the config isn't valid.
+    pub fn configure_timer<'d, S: TimerSpeed>(
+        &self,
+        ledc: &Ledc<'d>,
+        number: timer::Number,
+        clock_source: S::ClockSourceType,
+    ) -> Result<timer::Timer<'d, S>, Error>
+    where
+        timer::Timer<'d, S>: TimerIFace<S>,
+    {
+        self.validate()?;
+        let mut timer = ledc.timer::<S>(number);
+        timer.configure(timer::config::Config {
+            duty: self.duty,
+            clock_source,
+            frequency: Rate::from_hz(self.frequency),
+        })?;
+        Ok(timer)
+    }
+
+    /// PWM period in microseconds.
+    pub fn period_us(&self) -> u32 {
+        1_000_000 / self.frequency
+    }
+
+    /// Duty value of 100%.
+    pub fn full_duty(&self) -> u32 {
+        1 << self.duty as u32
+    }
+
+    /// Converts a pulse width to a duty value.
+    pub fn pulse_to_duty(&self, pulse_us: u32) -> u32 {
+        (pulse_us as u64 * self.full_duty() as u64 / self.period_us() as u64) as u32
+    }
+
+    /// Converts a duty value to a pulse width.
+    pub fn duty_to_pulse(&self, duty: u32) -> u32 {
+        (duty as u64 * self.period_us() as u64 / self.full_duty() as u64) as u32
+    }
+
+    /// Pulse widths for 0 and for the max angle with the trim applied, in microseconds.
+    pub fn pulse_range(&self) -> (u32, u32) {
+        let trim = |pulse_us: u32| pulse_us.saturating_add_signed(self.trim_us);
+        (trim(self.min_pulse_us), trim(self.max_pulse_us))
+    }
+
+    /// Duty values for 0 and for the max angle, with the trim applied.
+    pub fn duty_range(&self) -> (u32, u32) {
+        let (min, max) = self.pulse_range();
+        (self.pulse_to_duty(min), self.pulse_to_duty(max))
+    }
+
+    /// Number of duty counts that move the servo by one degree.
+    pub fn duty_per_degree(&self) -> f64 {
+        let (min, max) = self.duty_range();
+        (max - min) as f64 / self.max_angle
+    }
+
+    /// Converts a trim in degrees to microseconds.
+    pub fn trim_deg_to_us(&self, trim_deg: f64) -> i32 {
+        let us = trim_deg * (self.max_pulse_us - self.min_pulse_us) as f64 / self.max_angle;
+        // round half away from zero
+        if us < 0.0 {
+            (us - 0.5) as i32
+        } else {
+            (us + 0.5) as i32
+        }
+    }
+
+    /// Converts between an angle of the joint and an angle of the servo shaft, they differ
+    /// for inverted servos. The angle is clamped to `0..=max_angle`.
+    pub fn shaft_angle(&self, angle: f64) -> f64 {
+        let angle = angle.clamp(0.0, self.max_angle);
+        if self.inverted {
+            self.max_angle - angle
+        } else {
+            angle
+        }
+    }
+
+    /// Converts an angle to a duty value, the angle is clamped to `0..=max_angle`.
+    pub fn angle_to_duty(&self, angle: f64) -> u32 {
+        if self.calibration.is_some() {
+            let duty =
+                self.angle_to_pulse(angle) * self.full_duty() as f64 / self.period_us() as f64;
+            return (duty + 0.5) as u32;
+        }
+        let angle = self.shaft_angle(angle);
+        let (min, max) = self.duty_range();
+        let duty = min as f64 + (max - min) as f64 * angle / self.max_angle;
+        // round to the nearest, duty is never negative
+        (duty + 0.5) as u32
+    }
+
+    /// Converts an angle to a pulse width in microseconds, the angle is clamped to
+    /// `0..=max_angle`. Keeps the fraction for backends with sub-microsecond resolution.
+    pub fn angle_to_pulse(&self, angle: f64) -> f64 {
+        let angle = self.shaft_angle(angle);
+        if let Some(calibration) = &self.calibration {
+            return calibration.pulse(angle) + self.trim_us as f64;
+        }
+        let (min, max) = self.pulse_range();
+        let (min, max) = (min as f64, max as f64);
+        min + (max - min) * angle / self.max_angle
+    }
+
+    /// Converts a duty value to an angle.
+    pub fn duty_to_angle(&self, duty: u32) -> f64 {
+        let (min, max) = self.duty_range();
+        let duty = duty.clamp(min, max);
+        if self.calibration.is_some() {
+            let pulse_us = duty as f64 * self.period_us() as f64 / self.full_duty() as f64;
+            return self.pulse_to_angle(pulse_us);
+        }
+        self.shaft_angle((duty - min) as f64 * self.max_angle / (max - min) as f64)
+    }
+
+    /// Converts a pulse width in microseconds to an angle,
+    /// pulses out of the range give the closest angle.
+    pub fn pulse_to_angle(&self, pulse_us: f64) -> f64 {
+        let (min, max) = self.pulse_range();
+        let pulse_us = pulse_us.clamp(min as f64, max as f64);
+        let shaft_angle = match &self.calibration {
+            Some(calibration) => calibration.angle(pulse_us - self.trim_us as f64),
+            None => (pulse_us - min as f64) * self.max_angle / (max - min) as f64,
+        };
+        self.shaft_angle(shaft_angle)
+    }
+}
+
+/// Builds a [`ServoConfig`] and validates it.
+#[derive(Debug, Clone)]
+pub struct ServoConfigBuilder {
+    config: ServoConfig,
+}
+
+impl ServoConfigBuilder {
+    /// Pulse range in microseconds for `0..max_angle` degrees.
+    pub fn pulse_us(mut self, pulse_us: Range<u32>) -> Self {
+        self.config.min_pulse_us = pulse_us.start;
+        self.config.max_pulse_us = pulse_us.end;
+        self
+    }
+
+    pub fn max_angle(mut self, max_angle: f64) -> Self {
+        self.config.max_angle = max_angle;
+        self
+    }
+
+    /// PWM frequency in Hz.
+    pub fn frequency(mut self, frequency: u32) -> Self {
+        self.config.frequency = frequency;
+        self
+    }
+
+    pub fn duty(mut self, duty: Duty) -> Self {
+        self.config.duty = duty;
+        self
+    }
+
+    /// Flips angles and step directions of a mirrored servo.
+    pub fn inverted(mut self, inverted: bool) -> Self {
+        self.config.inverted = inverted;
+        self
+    }
+
+    /// Offset added to every pulse, in microseconds.
+    pub fn trim_us(mut self, trim_us: i32) -> Self {
+        self.config.trim_us = trim_us;
+        self
+    }
+
+    /// Offset of every position in degrees, converted with the pulse range and max angle
+    /// set so far.
+    pub fn trim_deg(mut self, trim_deg: f64) -> Self {
+        self.config.trim_us = self.config.trim_deg_to_us(trim_deg);
+        self
+    }
+
+    /// Measured angle to pulse mapping, sets the pulse range and the max angle
+    /// from its first and last points.
+    pub fn calibration(mut self, calibration: Calibration) -> Self {
+        let points = calibration.points();
+        if let (Some(first), Some(last)) = (points.first(), points.last()) {
+            self.config.min_pulse_us = first.pulse_us;
+            self.config.max_pulse_us = last.pulse_us;
+            self.config.max_angle = last.angle;
+        }
+        self.config.calibration = Some(calibration);
+        self
+    }
+
+    pub fn build(self) -> Result<ServoConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Duty resolution as the number of bits.
+#[cfg(feature = "serde")]
+mod duty_bits {
+    use esp_hal::ledc::timer::config::Duty;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duty: &Duty, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*duty as u8)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duty, D::Error> {
+        let bits = u8::deserialize(deserializer)?;
+        Duty::try_from(bits as u32).map_err(|_| D::Error::custom("unsupported duty resolution"))
+    }
+}
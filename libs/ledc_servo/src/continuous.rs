@@ -0,0 +1,47 @@
+use esp_hal::ledc::timer::TimerSpeed;
+
+use crate::{Error, Servo};
+
+/// Continuous rotation servo (FS90R and similar), the pulse width sets the speed
+/// instead of the angle.
+///
+/// The middle of the pulse range of the config stops the servo, its ends are full speed
+/// in either direction.
+pub struct ContinuousServo<'d, S: TimerSpeed> {
+    servo: Servo<'d, S>,
+    /// Last commanded speed, `-1.0..=1.0`.
+    speed: f32,
+}
+
+impl<'d, S: TimerSpeed> ContinuousServo<'d, S> {
+    /// Wraps the servo and stops it.
+    pub fn new(servo: Servo<'d, S>) -> Self {
+        let mut servo = Self { servo, speed: 0.0 };
+        servo.stop();
+        servo
+    }
+
+    /// Sets the speed within `-1.0..=1.0`, positive is counterclockwise.
+    /// Values out of the range are clamped.
+    pub fn set_speed(&mut self, speed: f32) -> Result<(), Error> {
+        let speed = speed.clamp(-1.0, 1.0);
+        let half = self.servo.config().max_angle / 2.0;
+        self.servo.set_angle(half + half * speed as f64)?;
+        self.speed = speed;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.servo.center();
+        self.speed = 0.0;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Returns the wrapped servo.
+    pub fn into_inner(self) -> Servo<'d, S> {
+        self.servo
+    }
+}
@@ -0,0 +1,49 @@
+use esp_hal::ledc::timer::TimerSpeed;
+
+use crate::{Dir, Error, MotionProfile, Servo, ServoDriver, StepResult};
+
+impl<S: TimerSpeed> ServoDriver for Servo<'_, S> {
+    type Error = Error;
+
+    fn step(&mut self, step: f32) -> Result<StepResult, Error> {
+        Servo::step(self, step)
+    }
+
+    fn step_deg(&mut self, step: f32) -> Result<StepResult, Error> {
+        Servo::step_deg(self, step)
+    }
+
+    fn set_angle(&mut self, angle: f64) -> Result<(), Error> {
+        Servo::set_angle(self, angle)
+    }
+
+    fn get_angle(&self) -> f64 {
+        self.angle()
+    }
+
+    fn pulse_width_us(&self) -> Option<u32> {
+        Some(self.get_pulse_width_us())
+    }
+
+    fn set_dir(&mut self, dir: Dir) {
+        Servo::set_dir(self, dir)
+    }
+
+    fn set_limits(&mut self, min_deg: f64, max_deg: f64) -> Result<(), Error> {
+        Servo::set_limits(self, min_deg, max_deg)
+    }
+
+    fn set_profile(&mut self, profile: Option<MotionProfile>) -> Result<(), Error> {
+        Servo::set_profile(self, profile)
+    }
+
+    fn disable(&mut self) -> Result<(), Error> {
+        self.detach();
+        Ok(())
+    }
+
+    fn enable(&mut self) -> Result<(), Error> {
+        self.attach();
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use esp_hal::ledc::{channel, timer};
+
+use crate::ServoError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// LEDC timer can't be configured with the servo frequency and duty resolution.
+    Timer(timer::Error),
+    Channel(channel::Error),
+    Config(ConfigError),
+    /// Angle is out of the soft limits, the servo wasn't moved.
+    LimitReached {
+        limit: f64,
+    },
+    /// Lower limit is above the upper one.
+    InvalidLimits,
+    /// Motion profile values must be positive and finite.
+    InvalidProfile,
+    /// Position feedback couldn't be read.
+    Adc,
+    /// Pulse is longer than the PWM period, the servo wasn't moved.
+    PulseOutOfPeriod {
+        pulse_us: u32,
+        period_us: u32,
+    },
+}
+
+/// Servo config that can't produce correct pulses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    ZeroFrequency,
+    /// Max angle must be positive and finite.
+    InvalidMaxAngle,
+    /// Min pulse must be shorter than the max one.
+    EmptyPulseRange,
+    /// Trim makes the min pulse negative.
+    TrimOutOfRange,
+    /// Max pulse doesn't fit into the PWM period.
+    PulseLongerThanPeriod {
+        pulse_us: u32,
+        period_us: u32,
+    },
+    /// Duty resolution can't express 1 degree steps.
+    ResolutionTooLow {
+        counts_per_degree: f32,
+    },
+    /// Feedback readings at 0 degrees and at the max angle are the same.
+    EmptyFeedbackRange,
+    /// Calibration must cover `0..=max_angle` with pulses growing with the angle.
+    InvalidCalibration,
+    /// Calibration has no room for another point.
+    CalibrationFull,
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        Error::Config(err)
+    }
+}
+
+impl From<timer::Error> for Error {
+    fn from(err: timer::Error) -> Self {
+        Error::Timer(err)
+    }
+}
+
+impl From<channel::Error> for Error {
+    fn from(err: channel::Error) -> Self {
+        Error::Channel(err)
+    }
+}
+
+impl From<Error> for ServoError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Timer(_) | Error::Channel(_) => ServoError::Backend,
+            Error::Config(_) => ServoError::Config,
+            Error::LimitReached { limit } => ServoError::LimitReached { limit },
+            Error::InvalidLimits => ServoError::InvalidLimits,
+            Error::InvalidProfile => ServoError::InvalidProfile,
+            Error::Adc => ServoError::Feedback,
+            Error::PulseOutOfPeriod {
+                pulse_us,
+                period_us,
+            } => ServoError::PulseOutOfPeriod {
+                pulse_us,
+                period_us,
+            },
+        }
+    }
+}
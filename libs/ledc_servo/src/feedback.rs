@@ -0,0 +1,111 @@
+use esp_hal::{
+    analog::adc::{Adc, AdcChannel, AdcPin, RegisterAccess},
+    ledc::timer::TimerSpeed,
+    Blocking,
+};
+use log::trace;
+
+use crate::{ConfigError, Error, Servo};
+
+/// How the potentiometer wire of a feedback servo maps to angles.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeedbackConfig {
+    /// ADC reading at 0 degrees.
+    pub raw_at_min: u16,
+    /// ADC reading at the max angle of the servo config.
+    pub raw_at_max: u16,
+    /// Error in degrees that [`FeedbackServo::seek`] accepts as reached.
+    pub tolerance_deg: f64,
+    /// Part of the error that is corrected by a single [`FeedbackServo::seek`] call.
+    pub gain: f64,
+}
+
+impl Default for FeedbackConfig {
+    /// Modified SG90 with the wire read at 11dB attenuation.
+    fn default() -> Self {
+        Self {
+            raw_at_min: 300,
+            raw_at_max: 2800,
+            tolerance_deg: 1.0,
+            gain: 0.5,
+        }
+    }
+}
+
+/// Servo with its internal potentiometer wired to an ADC pin.
+///
+/// Under load the shaft stops short of the commanded angle, [`FeedbackServo::seek`] measures
+/// the real angle and shifts the command until the shaft is where it should be.
+/// The ADC driver is passed to every read, so several feedback servos can share it.
+pub struct FeedbackServo<'d, S: TimerSpeed, PIN, ADCI> {
+    servo: Servo<'d, S>,
+    pin: AdcPin<PIN, ADCI>,
+    config: FeedbackConfig,
+}
+
+impl<'d, S, PIN, ADCI> FeedbackServo<'d, S, PIN, ADCI>
+where
+    S: TimerSpeed,
+    PIN: AdcChannel,
+    ADCI: RegisterAccess + 'd,
+{
+    pub fn new(
+        servo: Servo<'d, S>,
+        pin: AdcPin<PIN, ADCI>,
+        config: FeedbackConfig,
+    ) -> Result<Self, Error> {
+        if config.raw_at_min == config.raw_at_max {
+            return Err(ConfigError::EmptyFeedbackRange.into());
+        }
+        Ok(Self { servo, pin, config })
+    }
+
+    /// Reads the angle of the shaft in degrees.
+    pub fn measured_angle(&mut self, adc: &mut Adc<'d, ADCI, Blocking>) -> Result<f64, Error> {
+        let raw = nb::block!(adc.read_oneshot(&mut self.pin)).map_err(|_| Error::Adc)?;
+        let (min, max) = (self.config.raw_at_min as f64, self.config.raw_at_max as f64);
+        let max_angle = self.servo.config().max_angle;
+        let angle = (raw as f64 - min) * max_angle / (max - min);
+        Ok(angle.clamp(0.0, max_angle))
+    }
+
+    /// Makes one correction toward the angle, must be called periodically
+    /// with enough time in between for the servo to move.
+    /// Returns true while the measured angle is out of the tolerance.
+    ///
+    /// Fails with [`Error::LimitReached`] if the angle is out of the limits.
+    pub fn seek(&mut self, adc: &mut Adc<'d, ADCI, Blocking>, angle: f64) -> Result<bool, Error> {
+        let (min, max) = self.servo.limits();
+        if angle < min || angle > max {
+            let limit = if angle < min { min } else { max };
+            return Err(Error::LimitReached { limit });
+        }
+
+        let measured = self.measured_angle(adc)?;
+        let error = angle - measured;
+        if error.abs() <= self.config.tolerance_deg {
+            return Ok(false);
+        }
+        // commanding past the target compensates the load
+        let command = (self.servo.angle() + error * self.config.gain).clamp(min, max);
+        trace!(
+            "{}: seek {angle}, measured {measured}, command {command}",
+            self.servo.name()
+        );
+        self.servo.set_angle(command)?;
+        Ok(true)
+    }
+
+    pub fn servo(&self) -> &Servo<'d, S> {
+        &self.servo
+    }
+
+    pub fn servo_mut(&mut self) -> &mut Servo<'d, S> {
+        &mut self.servo
+    }
+
+    pub fn into_inner(self) -> (Servo<'d, S>, AdcPin<PIN, ADCI>) {
+        (self.servo, self.pin)
+    }
+}
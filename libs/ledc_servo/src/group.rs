@@ -0,0 +1,105 @@
+use crate::ServoDriver;
+
+/// Servos moved together, so all of them reach their targets at the same time.
+///
+/// The longest move goes with the requested speed, shorter ones are slowed down to finish with
+/// it, so e.g. the end effector of an arm goes straight instead of tracing a curve.
+pub struct ServoGroup<D, const N: usize> {
+    servos: [D; N],
+    motion: Option<GroupMotion<N>>,
+}
+
+/// Move of all servos of the group, angles are interpolated by the elapsed time.
+#[derive(Debug, Clone, Copy)]
+struct GroupMotion<const N: usize> {
+    start: [f64; N],
+    target: [f64; N],
+    /// Seconds.
+    duration: f64,
+    elapsed: f64,
+}
+
+impl<D: ServoDriver, const N: usize> ServoGroup<D, N> {
+    pub fn new(servos: [D; N]) -> Self {
+        Self {
+            servos,
+            motion: None,
+        }
+    }
+
+    /// Starts a move of every servo to its target angle, the longest move goes with
+    /// `deg_per_sec`. Doesn't move the servos by itself,
+    /// [`ServoGroup::update`] must be called periodically.
+    pub fn move_to(&mut self, targets: [f64; N], deg_per_sec: f64) {
+        let start: [f64; N] = core::array::from_fn(|idx| self.servos[idx].get_angle());
+        let longest = start
+            .iter()
+            .zip(&targets)
+            .map(|(start, target)| (target - start).abs())
+            .fold(0.0, f64::max);
+        let speed = deg_per_sec.abs();
+        if longest == 0.0 || speed == 0.0 {
+            self.motion = None;
+            return;
+        }
+        self.motion = Some(GroupMotion {
+            start,
+            target: targets,
+            duration: longest / speed,
+            elapsed: 0.0,
+        });
+    }
+
+    /// Advances the ongoing move by `dt` seconds.
+    /// Returns true while the move isn't finished.
+    ///
+    /// A failed servo stops the move of the whole group where it is.
+    pub fn update(&mut self, dt: f64) -> Result<bool, D::Error> {
+        let Some(mut motion) = self.motion else {
+            return Ok(false);
+        };
+
+        motion.elapsed = (motion.elapsed + dt).min(motion.duration);
+        let progress = motion.elapsed / motion.duration;
+        for (idx, servo) in self.servos.iter_mut().enumerate() {
+            let (start, target) = (motion.start[idx], motion.target[idx]);
+            let angle = if progress >= 1.0 {
+                target
+            } else {
+                start + (target - start) * progress
+            };
+            if let Err(err) = servo.set_angle(angle) {
+                self.motion = None;
+                return Err(err);
+            }
+        }
+
+        let moving = motion.elapsed < motion.duration;
+        self.motion = moving.then_some(motion);
+        Ok(moving)
+    }
+
+    /// Returns true if a [`ServoGroup::move_to`] move is in progress.
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    /// Cancels the ongoing move, servos stay where they are.
+    pub fn stop(&mut self) {
+        self.motion = None;
+    }
+
+    pub fn servos(&self) -> &[D; N] {
+        &self.servos
+    }
+
+    /// Moving a servo directly doesn't cancel the ongoing move of the group.
+    pub fn servos_mut(&mut self) -> &mut [D; N] {
+        &mut self.servos
+    }
+
+    /// Returns the wrapped servos.
+    pub fn into_inner(self) -> [D; N] {
+        self.servos
+    }
+}
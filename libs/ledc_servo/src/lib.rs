@@ -0,0 +1,33 @@
+//! Hobby servo driver on top of the LEDC (LED PWM) peripheral of esp-hal.
+//!
+//! Servo position is set by the width of a pulse repeated with the PWM frequency, a LEDC timer
+//! provides the frequency and each servo gets its own channel of the timer.
+#![no_std]
+
+#[cfg(all(feature = "mcpwm", feature = "esp32c3"))]
+compile_error!("the ESP32-C3 has no MCPWM, build the mcpwm feature for a chip that has it");
+
+#[cfg(feature = "async")]
+mod asynch;
+mod calibration;
+mod config;
+mod continuous;
+mod driver;
+mod error;
+mod feedback;
+mod group;
+#[cfg(feature = "mcpwm")]
+mod mcpwm;
+mod profile;
+mod servo;
+
+pub use calibration::{Calibration, CalibrationPoint, MAX_CALIBRATION_POINTS};
+pub use config::{ServoConfig, ServoConfigBuilder};
+pub use continuous::ContinuousServo;
+pub use error::{ConfigError, Error};
+pub use feedback::{FeedbackConfig, FeedbackServo};
+pub use group::ServoGroup;
+#[cfg(feature = "mcpwm")]
+pub use mcpwm::McpwmServo;
+pub use servo::Servo;
+pub use servo_driver::{Dir, MotionProfile, ServoDriver, ServoError, StepResult};
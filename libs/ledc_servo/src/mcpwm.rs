@@ -0,0 +1,204 @@
+use esp_hal::mcpwm::{operator::PwmPin, PwmPeripheral};
+use log::trace;
+
+use crate::{profile::Jog, Dir, Error, MotionProfile, ServoConfig, ServoDriver, StepResult};
+
+/// Servo on an output of a MCPWM operator.
+///
+/// LEDC at 50Hz gets about 9 duty counts per degree with 14 bits, MCPWM counts the period in
+/// timer ticks instead, so with a 1MHz timer clock the pulse is set with 1µs granularity.
+/// The operator timer must run with the servo frequency, e.g. for 50Hz:
+///
+/// ```rust,ignore
+/// let clock = PeripheralClockConfig::with_frequency(Rate::from_mhz(1))?;
+/// let mut mcpwm = McPwm::new(peripherals.MCPWM0, clock);
+/// mcpwm.operator0.set_timer(&mcpwm.timer0);
+/// let pin = mcpwm.operator0.with_pin_a(pin, PwmPinConfig::UP_ACTIVE_HIGH);
+/// let timer = clock.timer_clock_with_frequency(19_999, PwmWorkingMode::Increase, Rate::from_hz(50))?;
+/// mcpwm.timer0.start(timer);
+/// let servo = McpwmServo::new("shoulder", ServoConfig::sg90(Duty::Duty14Bit), pin);
+/// ```
+///
+/// Steps are in timer ticks.
+pub struct McpwmServo<'d, PWM, const OP: u8, const IS_A: bool> {
+    name: &'static str,
+    config: ServoConfig,
+    pin: PwmPin<'d, PWM, OP, IS_A>,
+    dir: Dir,
+    /// Timestamp that is currently applied.
+    ticks: u16,
+    /// Soft limits in degrees.
+    limits: (f64, f64),
+    /// Timestamp for the lower limit.
+    min_ticks: u16,
+    /// Timestamp for the upper limit.
+    max_ticks: u16,
+    /// False while the pulses are stopped.
+    attached: bool,
+    /// Ramps the speed of steps.
+    profile: Option<MotionProfile>,
+    jog: Jog,
+}
+
+impl<'d, PWM: PwmPeripheral, const OP: u8, const IS_A: bool> McpwmServo<'d, PWM, OP, IS_A> {
+    /// Wraps the pin of a started operator and moves the servo to the center.
+    /// The `duty` of the config isn't used, the resolution comes from the timer period.
+    pub fn new(name: &'static str, config: ServoConfig, pin: PwmPin<'d, PWM, OP, IS_A>) -> Self {
+        let limits = (0.0, config.max_angle);
+        let mut servo = Self {
+            name,
+            config,
+            pin,
+            dir: Dir::CW,
+            ticks: 0,
+            limits,
+            min_ticks: 0,
+            max_ticks: 0,
+            attached: true,
+            profile: None,
+            jog: Jog::default(),
+        };
+        servo.update_bounds();
+        servo.set_ticks((servo.min_ticks + servo.max_ticks) / 2);
+        servo
+    }
+
+    /// Recalculates timestamp bounds from the limits.
+    fn update_bounds(&mut self) {
+        let lower = self.angle_to_ticks(self.limits.0);
+        let upper = self.angle_to_ticks(self.limits.1);
+        // inverted servo has the lower limit at the longer pulse
+        self.min_ticks = lower.min(upper);
+        self.max_ticks = lower.max(upper);
+    }
+
+    fn angle_to_ticks(&self, angle: f64) -> u16 {
+        self.pulse_to_ticks(self.config.angle_to_pulse(angle))
+    }
+
+    fn pulse_to_ticks(&self, pulse_us: f64) -> u16 {
+        let period_ticks = self.pin.period() as f64 + 1.0;
+        let ticks = pulse_us * period_ticks / self.config.period_us() as f64;
+        (ticks + 0.5) as u16
+    }
+
+    /// Commanded pulse width in microseconds.
+    fn pulse_us(&self) -> f64 {
+        let period_ticks = self.pin.period() as f64 + 1.0;
+        self.ticks as f64 * self.config.period_us() as f64 / period_ticks
+    }
+
+    fn ticks_per_degree(&self) -> f64 {
+        let (min, max) = self.config.pulse_range();
+        let range = self.pulse_to_ticks(max as f64) - self.pulse_to_ticks(min as f64);
+        range as f64 / self.config.max_angle
+    }
+
+    fn set_ticks(&mut self, ticks: u16) {
+        trace!("{}: ticks {} -> {ticks}", self.name, self.ticks);
+        if self.attached {
+            self.pin.set_timestamp(ticks);
+        }
+        self.ticks = ticks;
+    }
+}
+
+impl<PWM: PwmPeripheral, const OP: u8, const IS_A: bool> ServoDriver
+    for McpwmServo<'_, PWM, OP, IS_A>
+{
+    type Error = Error;
+
+    fn step(&mut self, step: f32) -> Result<StepResult, Error> {
+        let dir = match (self.dir, self.config.inverted) {
+            (dir, false) => dir,
+            (Dir::CW, true) => Dir::CCW,
+            (Dir::CCW, true) => Dir::CW,
+        };
+        let step = match dir {
+            Dir::CW => -step as f64,
+            Dir::CCW => step as f64,
+        };
+        let units = match self.profile {
+            Some(profile) => self.jog.next(&profile, step, self.ticks_per_degree()),
+            None => step.signum() as i64 * (step.abs() + 0.5) as i64,
+        };
+
+        let wanted = self.ticks as i64 + units;
+        let ticks = wanted.clamp(self.min_ticks as i64, self.max_ticks as i64) as u16;
+        let result = StepResult::new(wanted, ticks as i64, self.config.inverted);
+        if result != StepResult::Stepped {
+            self.jog = Jog::default();
+        }
+        if ticks != self.ticks {
+            self.set_ticks(ticks);
+        }
+        Ok(result)
+    }
+
+    fn step_deg(&mut self, step: f32) -> Result<StepResult, Error> {
+        self.step(step * self.ticks_per_degree() as f32)
+    }
+
+    fn set_angle(&mut self, angle: f64) -> Result<(), Error> {
+        let (min, max) = self.limits;
+        if angle < min || angle > max {
+            let limit = if angle < min { min } else { max };
+            return Err(Error::LimitReached { limit });
+        }
+        self.jog = Jog::default();
+        self.set_ticks(self.angle_to_ticks(angle));
+        Ok(())
+    }
+
+    fn get_angle(&self) -> f64 {
+        self.config.pulse_to_angle(self.pulse_us())
+    }
+
+    fn pulse_width_us(&self) -> Option<u32> {
+        Some((self.pulse_us() + 0.5) as u32)
+    }
+
+    fn set_dir(&mut self, dir: Dir) {
+        self.dir = dir;
+    }
+
+    fn set_limits(&mut self, min_deg: f64, max_deg: f64) -> Result<(), Error> {
+        let min = min_deg.clamp(0.0, self.config.max_angle);
+        let max = max_deg.clamp(0.0, self.config.max_angle);
+        if min > max {
+            return Err(Error::InvalidLimits);
+        }
+        self.limits = (min, max);
+        self.jog = Jog::default();
+        self.update_bounds();
+        let ticks = self.ticks.clamp(self.min_ticks, self.max_ticks);
+        if ticks != self.ticks {
+            self.set_ticks(ticks);
+        }
+        Ok(())
+    }
+
+    fn set_profile(&mut self, profile: Option<MotionProfile>) -> Result<(), Error> {
+        if let Some(profile) = &profile {
+            profile.validate().map_err(|_| Error::InvalidProfile)?;
+        }
+        self.jog = Jog::default();
+        self.profile = profile;
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), Error> {
+        trace!("{}: detached", self.name);
+        self.jog = Jog::default();
+        self.pin.set_timestamp(0);
+        self.attached = false;
+        Ok(())
+    }
+
+    fn enable(&mut self) -> Result<(), Error> {
+        trace!("{}: attached", self.name);
+        self.attached = true;
+        self.pin.set_timestamp(self.ticks);
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use crate::MotionProfile;
+
+/// Ramped relative steps, in the units of the backend (duty counts, timer ticks).
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Jog {
+    /// Signed units per step, positive toward longer pulses.
+    speed: f64,
+    /// Fraction of a unit that wasn't applied yet.
+    rest: f64,
+}
+
+impl Jog {
+    /// Ramps toward the signed `step` and returns the whole units to move by.
+    /// `units_per_degree` converts the profile into the backend units.
+    pub(crate) fn next(
+        &mut self,
+        profile: &MotionProfile,
+        step: f64,
+        units_per_degree: f64,
+    ) -> i64 {
+        let period = profile.step_period;
+        let max = profile.max_speed * period * units_per_degree;
+        let accel = profile.accel * period * period * units_per_degree;
+        let target = step.clamp(-max, max);
+        self.speed = if target > self.speed {
+            (self.speed + accel).min(target)
+        } else {
+            (self.speed - accel).max(target)
+        };
+
+        // truncates toward zero, the rest is applied by the following steps
+        let total = self.speed + self.rest;
+        let units = total as i64;
+        self.rest = total - units as f64;
+        units
+    }
+}
@@ -0,0 +1,416 @@
+use esp_hal::{
+    gpio::{interconnect::PeripheralOutput, DriveMode},
+    ledc::{
+        channel::{self, Channel, ChannelHW, ChannelIFace, FadeError},
+        timer::{Timer, TimerIFace, TimerSpeed},
+        Ledc,
+    },
+    time::Duration,
+};
+use log::trace;
+
+use crate::{profile::Jog, Dir, Error, MotionProfile, ServoConfig, StepResult};
+
+/// Servo connected to a LEDC channel.
+pub struct Servo<'d, S: TimerSpeed> {
+    name: &'static str,
+    config: ServoConfig,
+    channel: Channel<'d, S>,
+    dir: Dir,
+    /// Duty that is currently applied.
+    duty: u32,
+    /// Soft limits in degrees, see [`Servo::set_limits`].
+    limits: (f64, f64),
+    /// Duty for the lower limit.
+    min_duty: u32,
+    /// Duty for the upper limit.
+    max_duty: u32,
+    /// Ongoing speed limited move, see [`Servo::goto_angle`].
+    motion: Option<Motion>,
+    /// False while the pulses are stopped, see [`Servo::detach`].
+    attached: bool,
+    /// Ramps the speed of steps and moves, see [`Servo::set_profile`].
+    profile: Option<MotionProfile>,
+    /// Ramped steps state.
+    jog: Jog,
+}
+
+/// Speed limited move toward a target angle.
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    /// Angle commanded so far, kept with fractions so slow moves don't stall on rounding.
+    angle: f64,
+    target: f64,
+    /// Degrees per second.
+    speed: f64,
+    /// Current speed, ramped by the profile.
+    velocity: f64,
+}
+
+impl<'d, S: TimerSpeed + 'd> Servo<'d, S> {
+    /// Binds the servo to a channel of the timer and moves it to the center.
+    /// Fails if the config isn't valid.
+    pub fn new(
+        name: &'static str,
+        config: ServoConfig,
+        ledc: &Ledc<'d>,
+        timer: &'d Timer<'d, S>,
+        number: channel::Number,
+        pin: impl PeripheralOutput<'d>,
+    ) -> Result<Self, Error>
+    where
+        Timer<'d, S>: TimerIFace<S>,
+    {
+        config.validate()?;
+        let mut channel = ledc.channel(number, pin);
+        channel.configure(channel::config::Config {
+            timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })?;
+
+        let (min_duty, max_duty) = config.duty_range();
+        let limits = (0.0, config.max_angle);
+        let mut servo = Self {
+            name,
+            config,
+            channel,
+            dir: Dir::CW,
+            duty: min_duty,
+            limits,
+            min_duty,
+            max_duty,
+            motion: None,
+            attached: true,
+            profile: None,
+            jog: Jog::default(),
+        };
+        servo.center();
+        Ok(servo)
+    }
+}
+
+impl<S: TimerSpeed> Servo<'_, S> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn config(&self) -> &ServoConfig {
+        &self.config
+    }
+
+    /// Sets the direction of the following steps.
+    pub fn set_dir(&mut self, dir: Dir) {
+        self.dir = dir;
+    }
+
+    /// Moves the servo by `step` duty counts in the current direction, the move is clamped to
+    /// the limits. Tells which limit stopped the step, if any.
+    /// Cancels an ongoing [`Servo::goto_angle`] move.
+    pub fn step(&mut self, step: f32) -> Result<StepResult, Error> {
+        self.motion = None;
+        let step = match self.shaft_dir() {
+            Dir::CW => -step as f64,
+            Dir::CCW => step as f64,
+        };
+        let units = match self.profile {
+            Some(profile) => self.jog.next(&profile, step, self.config.duty_per_degree()),
+            None => step.signum() as i64 * (step.abs() + 0.5) as i64,
+        };
+
+        let wanted = self.duty as i64 + units;
+        let duty = wanted.clamp(self.min_duty as i64, self.max_duty as i64) as u32;
+        let result = StepResult::new(wanted, duty as i64, self.config.inverted);
+        if result != StepResult::Stepped {
+            // limit stops a profiled servo at once
+            self.jog = Jog::default();
+        }
+        if duty != self.duty {
+            self.set_duty(duty);
+        }
+        Ok(result)
+    }
+
+    /// Ramps the speed of steps and of [`Servo::goto_angle`] moves,
+    /// `None` applies commands right away.
+    ///
+    /// With a profile a step sets the speed the servo accelerates to, it keeps moving with
+    /// the reached speed and slows down only with further steps, a step of `0.0` stops it.
+    pub fn set_profile(&mut self, profile: Option<MotionProfile>) -> Result<(), Error> {
+        if let Some(profile) = &profile {
+            profile.validate().map_err(|_| Error::InvalidProfile)?;
+        }
+        self.cancel();
+        self.profile = profile;
+        Ok(())
+    }
+
+    pub fn profile(&self) -> Option<&MotionProfile> {
+        self.profile.as_ref()
+    }
+
+    /// Moves the servo by `step` degrees in the current direction, see [`Servo::step`].
+    /// Steps shorter than half a duty count don't move the servo.
+    pub fn step_deg(&mut self, step: f32) -> Result<StepResult, Error> {
+        self.step(step * self.config.duty_per_degree() as f32)
+    }
+
+    /// Moves the servo to the angle in degrees.
+    /// Cancels an ongoing [`Servo::goto_angle`] move.
+    ///
+    /// Fails with [`Error::LimitReached`] without moving if the angle is out of the limits.
+    pub fn set_angle(&mut self, angle: f64) -> Result<(), Error> {
+        self.cancel();
+        self.check_limits(angle)?;
+        self.set_duty(self.config.angle_to_duty(angle));
+        Ok(())
+    }
+
+    /// Restricts the angles the servo can be moved to, within `0..=max_angle` of the config.
+    /// A servo out of the new limits is moved to the closest one.
+    pub fn set_limits(&mut self, min_deg: f64, max_deg: f64) -> Result<(), Error> {
+        let min = min_deg.clamp(0.0, self.config.max_angle);
+        let max = max_deg.clamp(0.0, self.config.max_angle);
+        if min > max {
+            return Err(Error::InvalidLimits);
+        }
+        self.limits = (min, max);
+        self.update_bounds();
+        Ok(())
+    }
+
+    /// Returns the soft limits in degrees.
+    pub fn limits(&self) -> (f64, f64) {
+        self.limits
+    }
+
+    fn check_limits(&self, angle: f64) -> Result<(), Error> {
+        let (min, max) = self.limits;
+        if angle < min {
+            return Err(Error::LimitReached { limit: min });
+        }
+        if angle > max {
+            return Err(Error::LimitReached { limit: max });
+        }
+        Ok(())
+    }
+
+    /// Direction of the shaft for the current direction of the joint.
+    fn shaft_dir(&self) -> Dir {
+        match (self.dir, self.config.inverted) {
+            (dir, false) => dir,
+            (Dir::CW, true) => Dir::CCW,
+            (Dir::CCW, true) => Dir::CW,
+        }
+    }
+
+    /// Recalculates duty bounds after the limits or the config have changed.
+    fn update_bounds(&mut self) {
+        let lower = self.config.angle_to_duty(self.limits.0);
+        let upper = self.config.angle_to_duty(self.limits.1);
+        // inverted servo has the lower limit at the longer pulse
+        self.min_duty = lower.min(upper);
+        self.max_duty = lower.max(upper);
+        let duty = self.duty.clamp(self.min_duty, self.max_duty);
+        if duty != self.duty {
+            self.cancel();
+            self.set_duty(duty);
+        }
+    }
+
+    /// Moves the servo to the middle of its limits.
+    pub fn center(&mut self) {
+        self.cancel();
+        self.set_duty((self.min_duty + self.max_duty) / 2);
+    }
+
+    /// Returns the commanded angle in degrees.
+    pub fn angle(&self) -> f64 {
+        match &self.motion {
+            Some(motion) => motion.angle,
+            None => self.config.duty_to_angle(self.duty),
+        }
+    }
+
+    /// Starts a move to the angle with the speed in degrees per second,
+    /// the target is clamped to the limits.
+    /// Doesn't move the servo by itself, [`Servo::update`] must be called periodically.
+    /// With a profile the move speeds up from standstill and slows down before the target.
+    pub fn goto_angle(&mut self, target_deg: f64, deg_per_sec: f64) {
+        let target = target_deg.clamp(self.limits.0, self.limits.1);
+        let angle = self.angle();
+        self.jog = Jog::default();
+        self.motion = Some(Motion {
+            angle,
+            target,
+            speed: deg_per_sec.abs(),
+            velocity: 0.0,
+        });
+    }
+
+    /// Advances the ongoing move by `dt` seconds.
+    /// Returns true while the move isn't finished.
+    pub fn update(&mut self, dt: f64) -> Result<bool, Error> {
+        let Some(mut motion) = self.motion else {
+            return Ok(false);
+        };
+
+        let diff = motion.target - motion.angle;
+        motion.velocity = match &self.profile {
+            Some(profile) => profile.next_speed(motion.velocity, motion.speed, diff.abs(), dt),
+            None => motion.speed,
+        };
+        let max_delta = motion.velocity * dt;
+        motion.angle = if diff.abs() <= max_delta {
+            motion.target
+        } else if diff > 0.0 {
+            motion.angle + max_delta
+        } else {
+            motion.angle - max_delta
+        };
+        self.set_duty(self.config.angle_to_duty(motion.angle));
+
+        let moving = motion.angle != motion.target;
+        self.motion = moving.then_some(motion);
+        Ok(moving)
+    }
+
+    /// Moves the servo to the angle in `duration`, the duty is interpolated by the LEDC
+    /// hardware, so no periodic updates are needed.
+    /// Cancels an ongoing [`Servo::goto_angle`] move, any following move stops the fade.
+    ///
+    /// Fails with [`Error::LimitReached`] without moving if the angle is out of the limits,
+    /// or with a fade error if the duration is too long for the servo frequency.
+    pub fn fade_to_angle(&mut self, angle: f64, duration: Duration) -> Result<(), Error> {
+        self.cancel();
+        self.check_limits(angle)?;
+        let target = self.config.angle_to_duty(angle);
+        let diff = target.abs_diff(self.duty);
+        let cycles = duration.as_millis() * self.config.frequency as u64 / 1000;
+        if !self.attached || diff == 0 || cycles == 0 {
+            self.set_duty(target);
+            return Ok(());
+        }
+
+        // duty changes at most once per PWM cycle by the same amount,
+        // the remainder is applied up front so the fade ends exactly at the target
+        let duty_per_step = diff.div_ceil(cycles.min(diff as u64) as u32);
+        let steps = diff / duty_per_step;
+        let cycles_per_step = cycles / steps as u64;
+        if cycles_per_step > 1023 {
+            return Err(channel::Error::Fade(FadeError::Duration).into());
+        }
+        let range_err = || channel::Error::Fade(FadeError::DutyRange);
+        let fade_steps = u16::try_from(steps).map_err(|_| range_err())?;
+        let fade_step = u16::try_from(duty_per_step).map_err(|_| range_err())?;
+
+        let change = steps * duty_per_step;
+        let (start, inc) = if target > self.duty {
+            (target - change, true)
+        } else {
+            (target + change, false)
+        };
+        trace!(
+            "{}: fade {start} -> {target} in {} ms",
+            self.name,
+            duration.as_millis()
+        );
+        self.channel
+            .start_duty_fade_hw(start, inc, fade_steps, cycles_per_step as u16, fade_step);
+        self.duty = target;
+        Ok(())
+    }
+
+    /// Returns the commanded pulse width in microseconds.
+    pub fn get_pulse_width_us(&self) -> u32 {
+        self.config.duty_to_pulse(self.duty)
+    }
+
+    /// Sets the pulse width in microseconds, bypassing the angle mapping and the limits,
+    /// for calibration and diagnostics. Cancels an ongoing move.
+    ///
+    /// Fails with [`Error::PulseOutOfPeriod`] without moving if the pulse doesn't fit the period.
+    pub fn set_pulse_width_us(&mut self, pulse_us: u32) -> Result<(), Error> {
+        let period_us = self.config.period_us();
+        if pulse_us > period_us {
+            return Err(Error::PulseOutOfPeriod {
+                pulse_us,
+                period_us,
+            });
+        }
+        self.cancel();
+        self.set_duty(self.config.pulse_to_duty(pulse_us));
+        Ok(())
+    }
+
+    /// Returns the commanded duty value, its meaning depends on the duty resolution.
+    pub fn get_duty_raw(&self) -> u32 {
+        self.duty
+    }
+
+    /// Returns true while a [`Servo::fade_to_angle`] fade is running.
+    pub fn is_fading(&self) -> bool {
+        self.attached && self.channel.is_duty_fade_running_hw()
+    }
+
+    /// Changes the trim of the config in microseconds, the servo keeps its angle.
+    pub fn set_trim(&mut self, trim_us: i32) -> Result<(), Error> {
+        let angle = self.angle();
+        let mut config = self.config.clone();
+        config.trim_us = trim_us;
+        config.validate()?;
+
+        self.config = config;
+        self.update_bounds();
+        self.set_angle(angle.clamp(self.limits.0, self.limits.1))
+    }
+
+    /// Changes the trim of the config in degrees, the servo keeps its angle.
+    pub fn set_trim_deg(&mut self, trim_deg: f64) -> Result<(), Error> {
+        self.set_trim(self.config.trim_deg_to_us(trim_deg))
+    }
+
+    /// Stops the pulses, the servo goes limp and doesn't draw holding current,
+    /// so the joint can be positioned by hand.
+    ///
+    /// Moves of a detached servo only change the remembered position,
+    /// it's applied by [`Servo::attach`].
+    pub fn detach(&mut self) {
+        self.cancel();
+        trace!("{}: detached", self.name);
+        self.channel.set_duty_hw(0);
+        self.attached = false;
+    }
+
+    /// Starts the pulses again with the last commanded position.
+    pub fn attach(&mut self) {
+        trace!("{}: attached", self.name);
+        self.attached = true;
+        self.channel.set_duty_hw(self.duty);
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// Cancels the ongoing move and ramped steps.
+    fn cancel(&mut self) {
+        self.motion = None;
+        self.jog = Jog::default();
+    }
+
+    /// Returns true if a [`Servo::goto_angle`] move is in progress.
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    fn set_duty(&mut self, duty: u32) {
+        trace!("{}: duty {} -> {duty}", self.name, self.duty);
+        #[cfg(feature = "defmt")]
+        defmt::trace!("{=str}: duty {=u32} -> {=u32}", self.name, self.duty, duty);
+        if self.attached {
+            self.channel.set_duty_hw(duty);
+        }
+        self.duty = duty;
+    }
+}
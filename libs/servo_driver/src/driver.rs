@@ -0,0 +1,86 @@
+use crate::{MotionProfile, ServoError};
+
+/// Direction of relative moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dir {
+    /// Clockwise, towards shorter pulses.
+    CW,
+    /// Counterclockwise, towards longer pulses.
+    CCW,
+}
+
+/// Outcome of a relative step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// Servo moved by the whole step.
+    Stepped,
+    /// Step was cut at the lower limit, the servo may not have moved at all.
+    ClampedToMin,
+    /// Step was cut at the upper limit, the servo may not have moved at all.
+    ClampedToMax,
+}
+
+impl StepResult {
+    /// Compares the wanted position in pulse units (duty counts, timer ticks) with the one
+    /// clamped to the limits. Longer pulses are greater angles unless the servo is inverted.
+    pub fn new(wanted: i64, clamped: i64, inverted: bool) -> Self {
+        let towards_max = match wanted.cmp(&clamped) {
+            core::cmp::Ordering::Equal => return StepResult::Stepped,
+            core::cmp::Ordering::Less => inverted,
+            core::cmp::Ordering::Greater => !inverted,
+        };
+        if towards_max {
+            StepResult::ClampedToMax
+        } else {
+            StepResult::ClampedToMin
+        }
+    }
+
+    pub fn is_clamped(self) -> bool {
+        self != StepResult::Stepped
+    }
+}
+
+/// Servo as seen by the code that moves it, independent of how the pulses are generated.
+///
+/// Lets the arm run with other backends (an I2C PWM expander, a simulation, a mock in tests)
+/// instead of a LEDC channel.
+pub trait ServoDriver {
+    /// Error of the backend, the arm only needs to know what it means for the move.
+    type Error: core::fmt::Debug + Into<ServoError>;
+
+    /// Moves the servo by `step` in the current direction.
+    /// Tells which limit stopped the step, if any.
+    fn step(&mut self, step: f32) -> Result<StepResult, Self::Error>;
+
+    /// Moves the servo by `step` degrees in the current direction.
+    /// Tells which limit stopped the step, if any.
+    fn step_deg(&mut self, step: f32) -> Result<StepResult, Self::Error>;
+
+    /// Moves the servo to the angle in degrees.
+    fn set_angle(&mut self, angle: f64) -> Result<(), Self::Error>;
+
+    /// Returns the commanded angle in degrees.
+    fn get_angle(&self) -> f64;
+
+    /// Returns the commanded pulse width in microseconds, `None` if the backend has no pulses.
+    fn pulse_width_us(&self) -> Option<u32> {
+        None
+    }
+
+    /// Sets the direction of the following steps.
+    fn set_dir(&mut self, dir: Dir);
+
+    /// Restricts the angles the servo can be moved to.
+    fn set_limits(&mut self, min_deg: f64, max_deg: f64) -> Result<(), Self::Error>;
+
+    /// Ramps the speed of steps, `None` applies them right away.
+    /// Fails on a profile that doesn't [validate](MotionProfile::validate).
+    fn set_profile(&mut self, profile: Option<MotionProfile>) -> Result<(), Self::Error>;
+
+    /// Stops the pulses, the servo doesn't hold its position anymore.
+    fn disable(&mut self) -> Result<(), Self::Error>;
+
+    /// Starts the pulses again with the last commanded position.
+    fn enable(&mut self) -> Result<(), Self::Error>;
+}
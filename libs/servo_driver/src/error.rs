@@ -0,0 +1,19 @@
+/// Failure of a servo as far as the code moving it cares, backends map their own errors to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ServoError {
+    /// Angle is out of the soft limits, the servo wasn't moved.
+    LimitReached { limit: f64 },
+    /// Lower limit is above the upper one.
+    InvalidLimits,
+    /// Motion profile has a speed, acceleration or step period that isn't positive and finite.
+    InvalidProfile,
+    /// Position feedback couldn't be read.
+    Feedback,
+    /// Config of the servo can't produce correct pulses.
+    Config,
+    /// Pulse is longer than the PWM period, the servo wasn't moved.
+    PulseOutOfPeriod { pulse_us: u32, period_us: u32 },
+    /// Peripheral generating the pulses failed, e.g. a timer that can't run at the frequency.
+    Backend,
+}
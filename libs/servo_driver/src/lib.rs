@@ -0,0 +1,15 @@
+//! Servo as seen by the code that moves it, independent of the chip and of how the pulses
+//! are generated.
+//!
+//! Backends (the LEDC and MCPWM servos of `ledc_servo`, a simulation, a mock in tests)
+//! implement [`ServoDriver`], the arm logic only talks to the trait, so it builds and runs
+//! on a host too.
+#![no_std]
+
+mod driver;
+mod error;
+mod profile;
+
+pub use driver::{Dir, ServoDriver, StepResult};
+pub use error::ServoError;
+pub use profile::MotionProfile;
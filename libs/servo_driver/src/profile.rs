@@ -0,0 +1,50 @@
+use crate::ServoError;
+
+/// Trapezoidal velocity profile: the speed ramps up with `accel` up to `max_speed`
+/// and ramps down the same way, so sudden commands don't slam the arm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionProfile {
+    /// Degrees per second.
+    pub max_speed: f64,
+    /// Degrees per second squared.
+    pub accel: f64,
+    /// Seconds between relative steps, turns step sizes into speeds.
+    pub step_period: f64,
+}
+
+impl MotionProfile {
+    pub const fn new(max_speed: f64, accel: f64, step_period: f64) -> Self {
+        Self {
+            max_speed,
+            accel,
+            step_period,
+        }
+    }
+
+    /// Checks that all values are finite and positive, others would stall the servo
+    /// or make it jump.
+    pub fn validate(&self) -> Result<(), ServoError> {
+        let valid = |value: f64| value.is_finite() && value > 0.0;
+        if valid(self.max_speed) && valid(self.accel) && valid(self.step_period) {
+            Ok(())
+        } else {
+            Err(ServoError::InvalidProfile)
+        }
+    }
+
+    /// Speed for the next `dt` of a move with `remaining` degrees to go, capped by `speed`.
+    /// Slows down early enough to stop at the target.
+    pub fn next_speed(&self, current: f64, speed: f64, remaining: f64, dt: f64) -> f64 {
+        let max = speed.min(self.max_speed);
+        let delta = self.accel * dt;
+        let stopping_distance = current * current / (2.0 * self.accel);
+        if stopping_distance >= remaining {
+            // keep a crawl, so the last fraction of a degree doesn't take forever
+            (current - delta).max(delta).min(max)
+        } else {
+            (current + delta).min(max)
+        }
+    }
+}
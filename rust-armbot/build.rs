@@ -0,0 +1,8 @@
+fn main() {
+    // linker script of the on-target test harness, only needed for the test binaries
+    println!("cargo::rustc-link-arg-tests=-Tembedded-test.x");
+    // defmt keeps the format strings in a section of its own
+    if std::env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo::rustc-link-arg-bins=-Tdefmt.x");
+    }
+}
@@ -0,0 +1,162 @@
+//! Passive buzzer beeping on events of the arm, for feedback without watching the logs.
+//!
+//! The tone is a square wave of a LEDC channel at the timer frequency, beeps switch the duty
+//! between half and zero. A pitch per pattern would need a timer reconfiguration per beep, the
+//! patterns differ in rhythm instead.
+
+use core::time::Duration;
+
+use esp_hal::ledc::{
+    channel::{Channel, ChannelIFace},
+    LowSpeed,
+};
+use ledc_servo::ServoDriver;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    clock::Instant,
+    error::Error,
+    gamepad::Gamepad,
+};
+
+/// Beeps as on and off times in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeepPattern(pub &'static [(u16, u16)]);
+
+impl BeepPattern {
+    /// Joint reached its limit or the workspace edge.
+    pub const LIMIT: BeepPattern = BeepPattern(&[(30, 0)]);
+    /// Emergency stop engaged.
+    pub const STOP: BeepPattern = BeepPattern(&[(600, 0)]);
+    /// Pose move or queued moves started.
+    pub const PLAYBACK_START: BeepPattern = BeepPattern(&[(60, 60), (60, 0)]);
+    /// Pose move or queued moves finished.
+    pub const PLAYBACK_END: BeepPattern = BeepPattern(&[(60, 60), (60, 60), (150, 0)]);
+}
+
+/// Output that switches the tone on and off.
+pub trait ToneOutput {
+    fn set_tone(&mut self, on: bool) -> Result<(), Error>;
+}
+
+/// LEDC channel driving the buzzer, its timer sets the pitch, e.g. 2.7 kHz.
+pub struct LedcTone<'d> {
+    channel: Channel<'d, LowSpeed>,
+}
+
+impl<'d> LedcTone<'d> {
+    pub fn new(channel: Channel<'d, LowSpeed>) -> Self {
+        Self { channel }
+    }
+}
+
+impl ToneOutput for LedcTone<'_> {
+    fn set_tone(&mut self, on: bool) -> Result<(), Error> {
+        self.channel
+            .set_duty(if on { 50 } else { 0 })
+            .map_err(|_| Error::Other("buzzer duty can't be set"))
+    }
+}
+
+/// Beep being played.
+#[derive(Debug, Clone, Copy)]
+struct Playing {
+    pattern: BeepPattern,
+    /// Index of the beep in the pattern.
+    beep: usize,
+    /// Tone is on.
+    on: bool,
+    /// When the tone is switched next.
+    until: Instant,
+}
+
+/// Events of the arm seen by the last [`Buzzer::watch`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Seen {
+    at_limit: bool,
+    stopped: bool,
+    playing_back: bool,
+}
+
+/// Plays beep patterns without blocking the control loop.
+pub struct Buzzer<T> {
+    output: T,
+    playing: Option<Playing>,
+    seen: Seen,
+}
+
+impl<T: ToneOutput> Buzzer<T> {
+    pub fn new(mut output: T) -> Result<Self, Error> {
+        output.set_tone(false)?;
+        Ok(Self {
+            output,
+            playing: None,
+            seen: Seen::default(),
+        })
+    }
+
+    /// Starts the pattern, a pattern being played is cut off.
+    pub fn beep(&mut self, pattern: BeepPattern, now: Instant) -> Result<(), Error> {
+        let Some(&(on_ms, _)) = pattern.0.first() else {
+            return Ok(());
+        };
+        self.playing = Some(Playing {
+            pattern,
+            beep: 0,
+            on: true,
+            until: now + Duration::from_millis(on_ms as u64),
+        });
+        self.output.set_tone(true)
+    }
+
+    /// Advances the pattern, should be called every control period.
+    pub fn update(&mut self, now: Instant) -> Result<(), Error> {
+        let Some(playing) = &mut self.playing else {
+            return Ok(());
+        };
+        if now < playing.until {
+            return Ok(());
+        }
+        if playing.on {
+            let (_, off_ms) = playing.pattern.0[playing.beep];
+            playing.on = false;
+            playing.until = now + Duration::from_millis(off_ms as u64);
+        } else {
+            playing.beep += 1;
+            let Some(&(on_ms, _)) = playing.pattern.0.get(playing.beep) else {
+                self.playing = None;
+                return Ok(());
+            };
+            playing.on = true;
+            playing.until = now + Duration::from_millis(on_ms as u64);
+        }
+        self.output.set_tone(playing.on)
+    }
+
+    /// Beeps on the events of the arm since the last call: a limit hit, the emergency stop,
+    /// the start and the end of a pose move or queued moves. Call it after the step.
+    pub fn watch<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &ArmBot<G, D, N, B>,
+        now: Instant,
+    ) -> Result<(), Error> {
+        let seen = Seen {
+            at_limit: bot.at_limit(),
+            stopped: bot.is_stopped(),
+            playing_back: bot.is_playing_back(),
+        };
+        let last = core::mem::replace(&mut self.seen, seen);
+        let pattern = if seen.stopped && !last.stopped {
+            BeepPattern::STOP
+        } else if seen.at_limit && !last.at_limit {
+            BeepPattern::LIMIT
+        } else if seen.playing_back && !last.playing_back {
+            BeepPattern::PLAYBACK_START
+        } else if !seen.playing_back && last.playing_back && !seen.stopped {
+            BeepPattern::PLAYBACK_END
+        } else {
+            return self.update(now);
+        };
+        self.beep(pattern, now)
+    }
+}
@@ -0,0 +1,14 @@
+//! System clock of the chip, the clock types are in [`armbot_control::clock`].
+
+pub use armbot_control::clock::*;
+
+/// Clock backed by the system timer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        let since_boot = esp_hal::time::Instant::now().duration_since_epoch();
+        Instant::from_micros(since_boot.as_micros())
+    }
+}
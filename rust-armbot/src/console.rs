@@ -0,0 +1,92 @@
+//! Serial console of the firmware, the commands are in [`armbot_control::protocol`].
+
+use core::fmt::{self, Write};
+
+use armbot_control::protocol::{Command, CommandError, ErrorCode, LineBuffer, Reply};
+use esp_hal::{uart::Uart, Blocking};
+use log::{debug, warn};
+
+#[cfg(feature = "logger")]
+use crate::logger;
+
+/// Command console on the UART shared with the logs.
+pub struct Console<'d> {
+    uart: Uart<'d, Blocking>,
+    line: LineBuffer,
+}
+
+impl<'d> Console<'d> {
+    pub fn new(uart: Uart<'d, Blocking>) -> Self {
+        Self {
+            uart,
+            line: LineBuffer::new(),
+        }
+    }
+
+    /// Runs the commands received since the last poll, doesn't wait for more.
+    ///
+    /// `LOG` commands are run by the console, the rest by `run`: usually
+    /// [`Command::execute`] on the arm, the safe mode runs only a few.
+    pub fn poll<const N: usize>(
+        &mut self,
+        mut run: impl FnMut(Command<'_>) -> Result<Reply<N>, CommandError>,
+    ) {
+        let mut buf = [0; 16];
+        loop {
+            let len = match self.uart.read_buffered(&mut buf) {
+                Ok(0) => return,
+                Ok(len) => len,
+                Err(err) => {
+                    warn!("console read failed: {err:?}");
+                    return;
+                }
+            };
+            for &byte in &buf[..len] {
+                if let Some(line) = self.line.push(byte) {
+                    // replies are best effort, the host retries on a missing one
+                    let _ = Self::handle(&mut self.uart, line, &mut run);
+                }
+            }
+        }
+    }
+
+    fn handle<const N: usize>(
+        uart: &mut Uart<'d, Blocking>,
+        line: Result<&str, ErrorCode>,
+        run: &mut impl FnMut(Command<'_>) -> Result<Reply<N>, CommandError>,
+    ) -> fmt::Result {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => return Ok(()),
+            Ok(line) => line,
+            Err(code) => return writeln!(uart, "{}", CommandError::from(code)),
+        };
+        debug!("console command: {line}");
+
+        let result = Command::parse(line).map_err(CommandError::from);
+        #[cfg(feature = "logger")]
+        let result = match result {
+            Ok(Command::SetLogLevel { module, level }) => {
+                return match logger::Module::from_name(module) {
+                    Some(module) => {
+                        logger::set_level(module, level);
+                        writeln!(uart, "OK")
+                    }
+                    None => writeln!(uart, "{}", CommandError::from(ErrorCode::UnknownModule)),
+                };
+            }
+            Ok(Command::LogLevels) => {
+                write!(uart, "OK")?;
+                for module in logger::Module::ALL {
+                    write!(uart, " {}={}", module.name(), logger::level(module))?;
+                }
+                return writeln!(uart);
+            }
+            result => result,
+        };
+
+        match result.and_then(run) {
+            Ok(reply) => writeln!(uart, "{reply}"),
+            Err(err) => writeln!(uart, "{err}"),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use core::{fmt::Write, panic::PanicInfo, ptr::addr_of_mut};
+
+use esp_hal::{ram, system};
+use esp_println::println;
+use log::warn;
+
+use crate::{
+    armbot::{JointCounters, JOINTS},
+    error::Report,
+};
+
+/// Marks initialized crash log, "CRSH".
+const MAGIC: u32 = u32::from_le_bytes(*b"CRSH");
+/// Max length of stored messages, longer ones are truncated.
+const TEXT_LEN: usize = 96;
+
+/// Survives resets in RTC fast memory, is zeroed on power on.
+#[ram(unstable(rtc_fast, persistent))]
+static mut CRASH_LOG: CrashLog = CrashLog::EMPTY;
+
+/// State of the previous run, printed after reboot.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CrashLog {
+    magic: u32,
+    panic_len: u32,
+    panic_message: [u8; TEXT_LEN],
+    error_len: u32,
+    last_error: [u8; TEXT_LEN],
+    /// Steps, limit hits and errors of each joint.
+    counters: [[u32; 3]; JOINTS],
+}
+
+// SAFETY: contains only integers, any bit pattern is valid.
+unsafe impl esp_hal::Persistable for CrashLog {}
+
+impl CrashLog {
+    const EMPTY: Self = Self {
+        magic: MAGIC,
+        panic_len: 0,
+        panic_message: [0; TEXT_LEN],
+        error_len: 0,
+        last_error: [0; TEXT_LEN],
+        counters: [[0; 3]; JOINTS],
+    };
+
+    fn panic_message(&self) -> &str {
+        text(&self.panic_message, self.panic_len)
+    }
+
+    fn last_error(&self) -> &str {
+        text(&self.last_error, self.error_len)
+    }
+}
+
+/// Returns stored text, lengths are checked since memory may hold garbage after a bad reset.
+fn text(buf: &[u8; TEXT_LEN], len: u32) -> &str {
+    let len = (len as usize).min(TEXT_LEN);
+    core::str::from_utf8(&buf[..len]).unwrap_or("<corrupted>")
+}
+
+fn with_log<R>(f: impl FnOnce(&mut CrashLog) -> R) -> R {
+    critical_section::with(|_| {
+        // SAFETY: the only access to the static, serialized by the critical section
+        let log = unsafe { &mut *addr_of_mut!(CRASH_LOG) };
+        f(log)
+    })
+}
+
+/// Prints the state of the previous run and starts a new log, should be called once at boot.
+pub fn report_and_reset() {
+    let reason = system::reset_reason();
+    with_log(|log| {
+        if log.magic == MAGIC && (log.panic_len > 0 || log.error_len > 0) {
+            warn!("previous run ended with reset reason {reason:?}");
+            if log.panic_len > 0 {
+                warn!("  panic: {}", log.panic_message());
+            }
+            if log.error_len > 0 {
+                warn!("  last error: {}", log.last_error());
+            }
+            warn!(
+                "  joint counters [steps, limit hits, errors]: {:?}",
+                log.counters
+            );
+        }
+        *log = CrashLog::EMPTY;
+    });
+}
+
+/// Remembers the last error of the control loop.
+pub fn record_error(err: &Report) {
+    with_log(|log| {
+        let mut writer = TextWriter::new(&mut log.last_error);
+        let _ = write!(writer, "{err}");
+        log.error_len = writer.len as u32;
+    });
+}
+
+/// Remembers a snapshot of joint counters.
+pub fn record_counters(counters: &[JointCounters; JOINTS]) {
+    with_log(|log| {
+        for (stored, counters) in log.counters.iter_mut().zip(counters) {
+            *stored = [counters.steps, counters.limit_hits, counters.errors];
+        }
+    });
+}
+
+/// Stores the panic message and reboots, so the message is reported on the next boot.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("{info}");
+    with_log(|log| {
+        let mut writer = TextWriter::new(&mut log.panic_message);
+        let _ = write!(writer, "{info}");
+        log.panic_len = writer.len as u32;
+    });
+    system::software_reset()
+}
+
+/// Writes into a fixed buffer, truncating what doesn't fit.
+struct TextWriter<'a> {
+    buf: &'a mut [u8; TEXT_LEN],
+    len: usize,
+}
+
+impl<'a> TextWriter<'a> {
+    fn new(buf: &'a mut [u8; TEXT_LEN]) -> Self {
+        Self { buf, len: 0 }
+    }
+}
+
+impl Write for TextWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for ch in s.chars() {
+            let mut encoded = [0; 4];
+            let encoded = ch.encode_utf8(&mut encoded).as_bytes();
+            if self.len + encoded.len() > TEXT_LEN {
+                break;
+            }
+            self.buf[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+        Ok(())
+    }
+}
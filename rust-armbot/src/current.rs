@@ -0,0 +1,119 @@
+//! Servo current sensing, to catch a stalled or overloaded joint before it burns its servo or
+//! crushes what the gripper holds.
+//!
+//! Current of a servo rail is read by a [`CurrentSensor`], an INA219 on the I2C bus
+//! (`current-sense` feature). A [`RailMonitor`] reports current above the threshold for a while
+//! as a stall, see [`ArmBot::report_stall`]. The joystick pins take ADC1, so analog sensors like
+//! the ACS712 have no pin to go to.
+
+use core::time::Duration;
+
+use ledc_servo::ServoDriver;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    clock::Instant,
+    error::{Error, Report},
+    gamepad::Gamepad,
+    units::Degrees,
+};
+
+/// Source of the current of a servo rail.
+pub trait CurrentSensor {
+    /// Returns the current in milliamps.
+    fn read_ma(&mut self) -> Result<u32, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StallConfig {
+    /// Current above which the servo is considered loaded.
+    pub threshold_ma: u32,
+    /// How long the current must stay above the threshold, starting a servo takes a short peak.
+    pub hold: Duration,
+    /// How far the joint moves back after a stall, `None` holds it where it stalled.
+    pub back_off: Option<Degrees>,
+}
+
+impl Default for StallConfig {
+    /// SG90 stall current is ~650 mA.
+    fn default() -> Self {
+        Self {
+            threshold_ma: 500,
+            hold: Duration::from_millis(200),
+            back_off: Some(Degrees::from_whole(2)),
+        }
+    }
+}
+
+/// Tells a stall from short current peaks.
+#[derive(Debug, Clone)]
+pub struct StallDetector {
+    config: StallConfig,
+    /// Since when the current is above the threshold.
+    above_since: Option<Instant>,
+    stalled: bool,
+}
+
+impl StallDetector {
+    pub fn new(config: StallConfig) -> Self {
+        Self {
+            config,
+            above_since: None,
+            stalled: false,
+        }
+    }
+
+    /// Feeds a reading, returns true once the current stayed above the threshold for the
+    /// hold time. A stall is reported again after the current dropped below the threshold.
+    pub fn update(&mut self, current_ma: u32, now: Instant) -> bool {
+        if current_ma <= self.config.threshold_ma {
+            self.above_since = None;
+            self.stalled = false;
+            return false;
+        }
+        let since = *self.above_since.get_or_insert(now);
+        if self.stalled || now.duration_since(since) < self.config.hold {
+            return false;
+        }
+        self.stalled = true;
+        true
+    }
+
+    pub fn config(&self) -> &StallConfig {
+        &self.config
+    }
+}
+
+/// Current sensor of the servo rail of a joint.
+pub struct RailMonitor<S> {
+    /// Index of the joint.
+    joint: usize,
+    sensor: S,
+    detector: StallDetector,
+}
+
+impl<S: CurrentSensor> RailMonitor<S> {
+    pub fn new(joint: usize, sensor: S, config: StallConfig) -> Self {
+        Self {
+            joint,
+            sensor,
+            detector: StallDetector::new(config),
+        }
+    }
+
+    /// Reads the current and reports a stall to the arm, which fails with
+    /// [`Error::Stalled`]. Returns the current in milliamps.
+    pub fn poll<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &mut ArmBot<G, D, N, B>,
+        now: Instant,
+    ) -> Result<u32, Report> {
+        let current_ma = self.sensor.read_ma()?;
+        if self.detector.update(current_ma, now) {
+            let back_off = self.detector.config().back_off;
+            bot.report_stall(self.joint, back_off)?;
+        }
+        Ok(current_ma)
+    }
+}
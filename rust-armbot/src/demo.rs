@@ -0,0 +1,167 @@
+use core::{ops::Range, time::Duration};
+
+use ledc_servo::ServoDriver;
+use log::info;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint, PoseName},
+    clock::Instant,
+    error::Report,
+    gamepad::Gamepad,
+    units::Degrees,
+};
+
+/// Settings of the idle demo.
+pub struct DemoConfig {
+    /// How long the sticks must stay centered before the demo starts.
+    pub idle_after: Duration,
+    /// Speed of the joint that moves the most, in °/s.
+    pub speed: f32,
+    /// Pause between moves.
+    pub pause: Range<Duration>,
+    /// Random targets tried for one inside the workspace, the arm goes home if none is.
+    pub attempts: u32,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            idle_after: Duration::from_secs(60),
+            speed: 20.0,
+            pause: Duration::from_millis(500)..Duration::from_secs(3),
+            attempts: 10,
+        }
+    }
+}
+
+enum Mode {
+    /// Sticks drive the arm.
+    Passive { idle_since: Instant },
+    /// Arm plays the last demo move.
+    Moving,
+    /// Arm holds still until the next move.
+    Pausing { until: Instant },
+}
+
+/// Makes the arm look alive while nobody is using it.
+///
+/// When the sticks stay centered for [`DemoConfig::idle_after`], the arm goes to the home pose
+/// and then to random targets within the joint ranges and the workspace, as queued moves. Any
+/// stick input cancels the move and stops the demo, the arm stays where it was.
+pub struct Demo {
+    config: DemoConfig,
+    mode: Mode,
+    rng: XorShift,
+}
+
+impl Demo {
+    pub fn new(config: DemoConfig, now: Instant) -> Self {
+        Self {
+            config,
+            mode: Mode::Passive { idle_since: now },
+            rng: XorShift::new(now.as_micros() as u32),
+        }
+    }
+
+    /// Starts, runs or stops the demo, should be called before every step.
+    pub fn poll<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &mut ArmBot<G, D, N, B>,
+        now: Instant,
+    ) -> Result<(), Report> {
+        let input = !bot.gamepad_state().is_center() || bot.is_stopped();
+        match self.mode {
+            Mode::Passive { .. } if input || bot.is_playing_back() => {
+                self.mode = Mode::Passive { idle_since: now };
+            }
+            Mode::Passive { idle_since } => {
+                if now.duration_since(idle_since) >= self.config.idle_after {
+                    info!("arm is idle, starting demo");
+                    self.rng.mix(now.as_micros() as u32);
+                    bot.goto_pose(PoseName::Home)?;
+                    self.mode = Mode::Moving;
+                }
+            }
+            Mode::Moving | Mode::Pausing { .. } if input => {
+                info!("input resumed, demo stopped");
+                self.mode = Mode::Passive { idle_since: now };
+            }
+            Mode::Moving => {
+                if !bot.is_playing_back() {
+                    let pause = self.rng.duration(&self.config.pause);
+                    self.mode = Mode::Pausing { until: now + pause };
+                }
+            }
+            Mode::Pausing { until } => {
+                if now >= until {
+                    self.next_move(bot)?;
+                    self.mode = Mode::Moving;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a move to a random target, or to the home pose if none was in the workspace.
+    fn next_move<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &mut ArmBot<G, D, N, B>,
+    ) -> Result<(), Report> {
+        let config = bot.config();
+        let target = (0..self.config.attempts).find_map(|_| {
+            let targets: [Degrees; N] = core::array::from_fn(|idx| {
+                let range = &config.joints[idx].angle_range;
+                Degrees::new(self.rng.within(range.start.get()..range.end.get()))
+            });
+            config.check_workspace(&targets).is_ok().then_some(targets)
+        });
+        let Some(targets) = target else {
+            return bot.goto_pose(PoseName::Home);
+        };
+        let distance = targets
+            .iter()
+            .zip(bot.joint_angles())
+            .map(|(target, angle)| (*target - angle).get().abs())
+            .fold(0.0, f32::max);
+        bot.queue_move(
+            targets,
+            Duration::from_secs_f32(distance / self.config.speed),
+        )
+    }
+}
+
+/// Small PRNG, demo motion doesn't need anything better.
+struct XorShift(u32);
+
+impl XorShift {
+    fn new(seed: u32) -> Self {
+        // zero state would produce only zeros
+        Self(seed | 1)
+    }
+
+    /// Mixes extra entropy into the state.
+    fn mix(&mut self, val: u32) {
+        self.0 ^= val;
+        if self.0 == 0 {
+            self.0 = 1;
+        }
+    }
+
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Returns a value within the range.
+    fn within(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * (self.next() as f32 / u32::MAX as f32)
+    }
+
+    fn duration(&mut self, range: &Range<Duration>) -> Duration {
+        Duration::from_secs_f32(self.within(range.start.as_secs_f32()..range.end.as_secs_f32()))
+    }
+}
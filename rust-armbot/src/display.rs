@@ -0,0 +1,286 @@
+//! Status on a 128x64 OLED over I2C, SSD1306 or SH1106, for feedback without a serial cable.
+//!
+//! The screen shows 8 lines of 21 characters in a 5x7 font: the mode, the joint angles, the
+//! battery and the last error. Lines are rendered straight into the pages of the controller,
+//! there's no frame buffer and no graphics library. A call redraws one line, so the bus isn't
+//! held for the whole screen between two control steps.
+
+use core::fmt::{self, Write};
+
+use embedded_hal::i2c::I2c;
+use ledc_servo::ServoDriver;
+
+#[cfg(feature = "battery")]
+use crate::power::BatteryStatus;
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    error::{Error, Report},
+    gamepad::Gamepad,
+};
+
+/// Address with the SA0 pin tied to GND, most modules.
+pub const OLED_ADDRESS: u8 = 0x3c;
+
+const WIDTH: usize = 128;
+/// Pages of 8 pixel rows, a text line each.
+const PAGES: usize = 8;
+/// Glyph width plus a column of spacing.
+const CHAR_WIDTH: usize = 6;
+pub const COLUMNS: usize = WIDTH / CHAR_WIDTH;
+
+/// Control bytes of a command and a data transfer.
+const COMMANDS: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+/// 5x7 glyphs from space to `Z`, a byte per column with the top row in the lowest bit.
+/// Lowercase is shown as uppercase.
+const FONT: [[u8; 5]; 59] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3e, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x04, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x7f, 0x20, 0x18, 0x20, 0x7f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+];
+const DEGREE: [u8; 5] = [0x00, 0x06, 0x09, 0x09, 0x06];
+
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '°' => DEGREE,
+        c @ ' '..='Z' => FONT[c as usize - ' ' as usize],
+        _ => FONT['?' as usize - ' ' as usize],
+    }
+}
+
+/// Display controller, they differ in the init and in the RAM columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    Ssd1306,
+    /// Has 132 columns of RAM, the panel shows columns 2 to 129.
+    Sh1106,
+}
+
+impl Controller {
+    fn column_offset(self) -> u8 {
+        match self {
+            Controller::Ssd1306 => 0,
+            Controller::Sh1106 => 2,
+        }
+    }
+}
+
+/// What the arm is doing, the first line of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Driven by the sticks.
+    Teleop,
+    /// Running a pose move or queued moves.
+    Playback,
+    Stopped,
+}
+
+impl Mode {
+    pub fn of<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        bot: &ArmBot<G, D, N, B>,
+    ) -> Self {
+        if bot.is_stopped() {
+            Mode::Stopped
+        } else if bot.is_playing_back() {
+            Mode::Playback
+        } else {
+            Mode::Teleop
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Teleop => "TELEOP",
+            Mode::Playback => "PLAYBACK",
+            Mode::Stopped => "E-STOP",
+        }
+    }
+}
+
+/// Line of text, cut at the width of the screen.
+struct Line {
+    chars: [char; COLUMNS],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Self {
+            chars: [' '; COLUMNS],
+            len: 0,
+        }
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.len < COLUMNS {
+                self.chars[self.len] = c;
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 128x64 OLED with text lines.
+pub struct Oled<I> {
+    i2c: I,
+    address: u8,
+    controller: Controller,
+    /// Page redrawn by the next [`Oled::show_status`].
+    next_page: usize,
+}
+
+impl<I: I2c> Oled<I> {
+    /// Initializes the controller and clears the screen.
+    pub fn new(i2c: I, address: u8, controller: Controller) -> Result<Self, Error> {
+        let mut oled = Self {
+            i2c,
+            address,
+            controller,
+            next_page: 0,
+        };
+        // display off, clock, 64 rows, no offset, start line 0, mirrored to have the origin
+        // top left, COM pins, contrast, precharge, VCOMH, follow RAM, not inverted
+        oled.commands(&[
+            0xae, 0xd5, 0x80, 0xa8, 0x3f, 0xd3, 0x00, 0x40, 0xa1, 0xc8, 0xda, 0x12, 0x81, 0xcf,
+            0xd9, 0xf1, 0xdb, 0x40, 0xa4, 0xa6,
+        ])?;
+        match controller {
+            // charge pump on, page addressing
+            Controller::Ssd1306 => oled.commands(&[0x8d, 0x14, 0x20, 0x02])?,
+            // DC-DC on
+            Controller::Sh1106 => oled.commands(&[0xad, 0x8b])?,
+        }
+        oled.clear()?;
+        oled.commands(&[0xaf])?;
+        Ok(oled)
+    }
+
+    pub fn clear(&mut self) -> Result<(), Error> {
+        for page in 0..PAGES {
+            self.write_page(page, &[0; WIDTH])?;
+        }
+        Ok(())
+    }
+
+    /// Redraws the next line of the status: the mode, the joint angles, the battery if it's
+    /// measured and the last error.
+    pub fn show_status<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &ArmBot<G, D, N, B>,
+        #[cfg(feature = "battery")] battery: &BatteryStatus,
+        last_error: Option<&Report>,
+    ) -> Result<(), Error> {
+        let page = self.next_page;
+        let joints = &bot.config().joints[..N.min(PAGES - 3)];
+        let mut line = Line::new();
+        // Line never fails, it cuts the text
+        let _ = match page {
+            0 => write!(line, "ARMBOT {}", Mode::of(bot).name()),
+            page if page <= joints.len() => {
+                let angle = bot.joint_angles()[page - 1];
+                write!(line, "{:<9}{:>6.1}°", joints[page - 1].name, angle.get())
+            }
+            #[cfg(feature = "battery")]
+            page if page == joints.len() + 1 => {
+                write!(line, "BAT {:.2}V {}%", battery.volts, battery.percent)
+            }
+            page if page == PAGES - 1 => match last_error {
+                Some(err) => write!(line, "ERR {err}"),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        self.next_page = (page + 1) % PAGES;
+        self.draw(page, &line)
+    }
+
+    /// Renders the line into the page.
+    fn draw(&mut self, page: usize, line: &Line) -> Result<(), Error> {
+        let mut pixels = [0; WIDTH];
+        let (cells, _) = pixels.as_chunks_mut::<CHAR_WIDTH>();
+        for (cell, &c) in cells.iter_mut().zip(&line.chars[..line.len]) {
+            cell[..5].copy_from_slice(&glyph(c));
+        }
+        self.write_page(page, &pixels)
+    }
+
+    fn write_page(&mut self, page: usize, pixels: &[u8; WIDTH]) -> Result<(), Error> {
+        let column = self.controller.column_offset();
+        self.commands(&[0xb0 | page as u8, column & 0x0f, 0x10 | column >> 4])?;
+        let mut buf = [0; WIDTH + 1];
+        buf[0] = DATA;
+        buf[1..].copy_from_slice(pixels);
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    fn commands(&mut self, commands: &[u8]) -> Result<(), Error> {
+        let mut buf = [0; 24];
+        let buf = buf
+            .get_mut(..commands.len() + 1)
+            .ok_or(Error::OutOfRange("too many display commands"))?;
+        buf[0] = COMMANDS;
+        buf[1..].copy_from_slice(commands);
+        self.i2c.write(self.address, buf).map_err(|_| Error::I2c)
+    }
+}
@@ -0,0 +1,358 @@
+//! Base rotator driven by a DC motor with a quadrature encoder, held at its angle by a PID loop.
+//!
+//! ESP32-C3 has no PCNT peripheral, so the encoder is decoded in software: the GPIO interrupt
+//! of both channels feeds [`QuadratureDecoder::update`] and the control loop reads the count
+//! through [`SharedDecoder`], see [`start_decoder`]. That keeps up with a few tens of kHz of edges, enough for an
+//! encoder on the motor shaft of a geared base.
+//! The angle is counted from the position at power on, there's no homing switch.
+
+use core::{
+    cell::{Cell, RefCell},
+    ops::Range,
+};
+
+use critical_section::Mutex;
+use esp_hal::{
+    gpio::{Event, Input, Io, Output},
+    handler,
+    ledc::{
+        channel::{Channel, ChannelHW},
+        LowSpeed,
+    },
+};
+use log::{info, warn};
+
+use crate::{
+    armbot::BaseJoint,
+    error::Error,
+    gamepad::Position,
+    units::Degrees,
+    util::pid::{Pid, PidConfig},
+};
+
+/// Count change of a transition from the old to the new `AB` state, indexed by
+/// `old << 2 | new`. Transitions that skip a state can't tell the direction and count zero.
+const TRANSITIONS: [i8; 16] = [0, 1, -1, 0, -1, 0, 0, 1, 1, 0, 0, -1, 0, -1, 1, 0];
+
+/// Decoder of the base encoder, fed by the GPIO interrupt.
+static DECODER: Mutex<Cell<QuadratureDecoder>> = Mutex::new(Cell::new(QuadratureDecoder::new()));
+/// A and B channels of the encoder, shared with the interrupt handler.
+static CHANNELS: Mutex<RefCell<Option<[Input<'static>; 2]>>> = Mutex::new(RefCell::new(None));
+
+/// Counts the edges of both encoder channels, four counts per encoder line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuadratureDecoder {
+    /// Last `AB` state.
+    state: u8,
+    count: i32,
+    /// Transitions that skipped a state, the decoder is too slow for the encoder.
+    missed: u32,
+}
+
+impl QuadratureDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: 0,
+            count: 0,
+            missed: 0,
+        }
+    }
+
+    /// Decoder at zero with the channels at the levels, so the first edge counts.
+    pub fn starting_at(a: bool, b: bool) -> Self {
+        Self {
+            state: (a as u8) << 1 | b as u8,
+            ..Self::new()
+        }
+    }
+
+    /// Feeds the levels of the A and B channels after an edge of either.
+    pub fn update(&mut self, a: bool, b: bool) {
+        let new = (a as u8) << 1 | b as u8;
+        if new == self.state {
+            return;
+        }
+        let delta = TRANSITIONS[(self.state << 2 | new) as usize];
+        if delta == 0 {
+            self.missed = self.missed.saturating_add(1);
+        }
+        self.count = self.count.wrapping_add(delta as i32);
+        self.state = new;
+    }
+
+    pub fn count(&self) -> i32 {
+        self.count
+    }
+
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+/// Position of the motor shaft in encoder counts.
+pub trait Encoder {
+    fn count(&self) -> i32;
+
+    /// Transitions the decoder couldn't count, see [`QuadratureDecoder::missed`].
+    fn missed(&self) -> u32;
+}
+
+impl Encoder for QuadratureDecoder {
+    fn count(&self) -> i32 {
+        self.count
+    }
+
+    fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+/// Decoder updated by the GPIO interrupt and read by the control loop.
+pub struct SharedDecoder(pub &'static Mutex<Cell<QuadratureDecoder>>);
+
+impl SharedDecoder {
+    /// Feeds the channel levels, called from the GPIO interrupt handler.
+    pub fn update(&self, a: bool, b: bool) {
+        critical_section::with(|cs| {
+            let cell = self.0.borrow(cs);
+            let mut decoder = cell.get();
+            decoder.update(a, b);
+            cell.set(decoder);
+        });
+    }
+}
+
+impl Encoder for SharedDecoder {
+    fn count(&self) -> i32 {
+        critical_section::with(|cs| self.0.borrow(cs).get().count())
+    }
+
+    fn missed(&self) -> u32 {
+        critical_section::with(|cs| self.0.borrow(cs).get().missed())
+    }
+}
+
+/// Decodes the channels on every edge of either, returns the decoder the base reads.
+pub fn start_decoder(
+    io: &mut Io<'_>,
+    mut a: Input<'static>,
+    mut b: Input<'static>,
+) -> SharedDecoder {
+    io.set_interrupt_handler(on_edge);
+    critical_section::with(|cs| {
+        let start = QuadratureDecoder::starting_at(a.is_high(), b.is_high());
+        DECODER.borrow(cs).set(start);
+        a.listen(Event::AnyEdge);
+        b.listen(Event::AnyEdge);
+        CHANNELS.borrow_ref_mut(cs).replace([a, b]);
+    });
+    SharedDecoder(&DECODER)
+}
+
+#[handler]
+fn on_edge() {
+    let levels = critical_section::with(|cs| {
+        let mut channels = CHANNELS.borrow_ref_mut(cs);
+        let [a, b] = channels.as_mut()?;
+        a.clear_interrupt();
+        b.clear_interrupt();
+        Some((a.is_high(), b.is_high()))
+    });
+    if let Some((a, b)) = levels {
+        SharedDecoder(&DECODER).update(a, b);
+    }
+}
+
+/// Motor driven with a signed duty.
+pub trait Motor {
+    /// Drives the motor with the duty in `-1.0..=1.0`, positive towards greater angles.
+    fn set_output(&mut self, duty: f32);
+
+    /// Lets the motor coast.
+    fn disable(&mut self);
+
+    fn enable(&mut self);
+}
+
+/// H-bridge with a PWM and a direction input, e.g. DRV8871 or a TB6612 channel.
+pub struct HBridge<'d> {
+    pwm: Channel<'d, LowSpeed>,
+    dir: Output<'d>,
+    /// Duty of the channel at full output, from the duty resolution of its timer.
+    max_duty: u32,
+    enabled: bool,
+}
+
+impl<'d> HBridge<'d> {
+    /// The channel must be configured with a timer of `duty_bits` resolution.
+    pub fn new(pwm: Channel<'d, LowSpeed>, dir: Output<'d>, duty_bits: u32) -> Self {
+        pwm.set_duty_hw(0);
+        Self {
+            pwm,
+            dir,
+            max_duty: (1 << duty_bits) - 1,
+            enabled: true,
+        }
+    }
+}
+
+impl Motor for HBridge<'_> {
+    fn set_output(&mut self, duty: f32) {
+        if !self.enabled {
+            return;
+        }
+        self.dir.set_level((duty >= 0.0).into());
+        let duty = duty.abs().min(1.0) * self.max_duty as f32;
+        self.pwm.set_duty_hw(duty as u32);
+    }
+
+    fn disable(&mut self) {
+        self.pwm.set_duty_hw(0);
+        self.enabled = false;
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderBaseConfig {
+    /// Encoder counts per turn of the base, four per line times the gearing.
+    pub counts_per_rev: u32,
+    /// Allowed angles from the position at power on.
+    pub angle_range: Range<Degrees>,
+    /// Top speed of the setpoint in °/s, the stick speed is clamped to it.
+    pub max_speed: f32,
+    /// Gains from the angle error in degrees to the motor duty.
+    pub pid: PidConfig,
+    /// Error in degrees the base counts as arrived.
+    pub tolerance: f32,
+}
+
+impl EncoderBaseConfig {
+    /// 12 line motor encoder on a 1:100 gearbox, through a 1:3 belt to the base.
+    pub fn n20_geared() -> Self {
+        Self {
+            counts_per_rev: 12 * 4 * 100 * 3,
+            angle_range: Degrees::new(-90.0)..Degrees::new(90.0),
+            max_speed: 60.0,
+            pid: PidConfig {
+                kp: 0.05,
+                ki: 0.02,
+                kd: 0.002,
+                output_limit: 1.0,
+                integral_limit: 0.3,
+                derivative_filter: 0.5,
+            },
+            tolerance: 0.5,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.counts_per_rev == 0 {
+            return Err(Error::OutOfRange("encoder needs counts per turn"));
+        }
+        if self.angle_range.start >= self.angle_range.end {
+            return Err(Error::DegenerateRange);
+        }
+        if !(self.max_speed.is_finite() && self.max_speed > 0.0) {
+            return Err(Error::OutOfRange("base speed must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Base joint of a DC motor with an encoder, the stick moves the setpoint and the PID loop
+/// drives the motor to it.
+pub struct EncoderBase<E, M> {
+    encoder: E,
+    motor: M,
+    pid: Pid,
+    counts_per_deg: f32,
+    range: Range<f32>,
+    max_speed: f32,
+    tolerance: f32,
+    /// Angle the base is driven to.
+    setpoint: f32,
+    /// Length of a control period in seconds.
+    period: f32,
+    /// Missed transitions already reported.
+    missed: u32,
+    enabled: bool,
+}
+
+impl<E: Encoder, M: Motor> EncoderBase<E, M> {
+    pub fn new(config: EncoderBaseConfig, encoder: E, mut motor: M) -> Result<Self, Error> {
+        config.validate()?;
+        motor.enable();
+        info!("encoder base: {} counts per turn", config.counts_per_rev);
+        let mut base = Self {
+            encoder,
+            motor,
+            pid: Pid::new(config.pid),
+            counts_per_deg: config.counts_per_rev as f32 / 360.0,
+            range: config.angle_range.start.get()..config.angle_range.end.get(),
+            max_speed: config.max_speed,
+            tolerance: config.tolerance,
+            setpoint: 0.0,
+            period: crate::CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0,
+            missed: 0,
+            enabled: true,
+        };
+        base.setpoint = base.measured();
+        Ok(base)
+    }
+
+    /// Measured angle in degrees.
+    fn measured(&self) -> f32 {
+        self.encoder.count() as f32 / self.counts_per_deg
+    }
+}
+
+impl<E: Encoder, M: Motor> BaseJoint for EncoderBase<E, M> {
+    fn make_step(&mut self, cmd: &Position) -> Result<(), Error> {
+        if !self.enabled {
+            return Ok(());
+        }
+        // stick steps are hundredths of a degree per control period
+        let max_step = self.max_speed * self.period;
+        let step = match cmd {
+            Position::Center => 0.0,
+            Position::Low(step) => -(*step as f32 / 100.0).min(max_step),
+            Position::High(step) => (*step as f32 / 100.0).min(max_step),
+        };
+        self.setpoint = (self.setpoint + step).clamp(self.range.start, self.range.end);
+
+        let missed = self.encoder.missed();
+        if missed != self.missed {
+            warn!("base encoder skipped {} transitions", missed - self.missed);
+            self.missed = missed;
+        }
+
+        let output = self.pid.update(self.setpoint, self.measured(), self.period);
+        self.motor.set_output(output);
+        Ok(())
+    }
+
+    fn is_moving(&self) -> bool {
+        self.enabled && (self.setpoint - self.measured()).abs() > self.tolerance
+    }
+
+    fn angle(&self) -> Option<Degrees> {
+        Some(Degrees::new(self.measured()))
+    }
+
+    fn disable(&mut self) {
+        self.motor.disable();
+        self.pid.reset();
+        self.enabled = false;
+    }
+
+    /// Holds the base where it coasted to, not where it was before the stop.
+    fn enable(&mut self) {
+        self.setpoint = self.measured();
+        self.motor.enable();
+        self.enabled = true;
+    }
+}
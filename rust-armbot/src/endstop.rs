@@ -0,0 +1,148 @@
+//! Limit switches at the ends of joint ranges.
+//!
+//! A pressed switch hard-stops the joint towards it, see [`ArmBot::update_end_stops`].
+//! [`Homing`] drives a joint to its min switch to find where the mechanical end really is,
+//! instead of trusting that the servo horn was mounted right at its center.
+
+use esp_hal::gpio::{Input, Level};
+use ledc_servo::ServoDriver;
+use log::info;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    clock::Instant,
+    error::{Error, Report},
+    gamepad::Gamepad,
+    units::Degrees,
+    util::debounce::{DebounceMode, Debouncer},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct EndStopConfig {
+    /// Level of the input while the switch is pressed.
+    pub active_level: Level,
+    pub debounce: DebounceMode,
+}
+
+impl Default for EndStopConfig {
+    /// Normally open switch to GND with the input pulled up.
+    fn default() -> Self {
+        Self {
+            active_level: Level::Low,
+            debounce: DebounceMode::Count(3),
+        }
+    }
+}
+
+/// Debounced limit switch.
+pub struct LimitSwitch<'d> {
+    input: Input<'d>,
+    active_level: Level,
+    debouncer: Debouncer,
+}
+
+impl<'d> LimitSwitch<'d> {
+    /// The input must be pulled away from the active level.
+    pub fn new(input: Input<'d>, config: EndStopConfig) -> Self {
+        let pressed = input.level() == config.active_level;
+        Self {
+            input,
+            active_level: config.active_level,
+            debouncer: Debouncer::new(config.debounce, pressed),
+        }
+    }
+
+    /// Reads the switch, returns the debounced state.
+    pub fn update(&mut self, now: Instant) -> bool {
+        let pressed = self.input.level() == self.active_level;
+        self.debouncer.update(pressed, now);
+        self.is_pressed()
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.debouncer.is_high()
+    }
+}
+
+/// Limit switches of a joint, for now only at the lower end, the board has no pins for more.
+pub struct JointEndStops<'d> {
+    /// Index of the joint.
+    joint: usize,
+    min: Option<LimitSwitch<'d>>,
+}
+
+impl<'d> JointEndStops<'d> {
+    pub fn new(joint: usize) -> Self {
+        Self { joint, min: None }
+    }
+
+    /// Switch at the lower end of the angle range.
+    pub fn with_min(mut self, switch: LimitSwitch<'d>) -> Self {
+        self.min = Some(switch);
+        self
+    }
+
+    /// Reads the switches and feeds them to the arm, should be called before every step.
+    pub fn poll<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &mut ArmBot<G, D, N, B>,
+        now: Instant,
+    ) -> Result<(), Error> {
+        let min = self.min.as_mut().is_some_and(|switch| switch.update(now));
+        bot.update_end_stops(self.joint, min, false)
+    }
+
+    pub fn is_min_pressed(&self) -> bool {
+        self.min.as_ref().is_some_and(LimitSwitch::is_pressed)
+    }
+}
+
+/// Finds the angle the min switch of a joint trips at.
+///
+/// The joint moves down a step every poll until the switch is pressed. The difference from
+/// where the switch is expected is the error of the servo mounting, to correct by a trim.
+pub struct Homing {
+    /// Index of the joint.
+    joint: usize,
+    /// Angle the switch should trip at.
+    expected: Degrees,
+    step: Degrees,
+}
+
+impl Homing {
+    pub fn new(joint: usize, expected: Degrees, step: Degrees) -> Self {
+        Self {
+            joint,
+            expected,
+            step,
+        }
+    }
+
+    /// Moves the joint towards the switch, call it after [`JointEndStops::poll`].
+    /// Returns how far past the expected angle the switch tripped once it's pressed,
+    /// negative if it tripped early. Fails if the joint reaches the end of its range first.
+    pub fn poll<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        stops: &JointEndStops,
+        bot: &mut ArmBot<G, D, N, B>,
+    ) -> Result<Option<Degrees>, Report> {
+        let angle = *bot
+            .joint_angles()
+            .get(self.joint)
+            .ok_or(Error::OutOfRange("no such joint"))?;
+        if stops.is_min_pressed() {
+            let offset = Degrees::new(self.expected.get() - angle.get());
+            info!("end stop of joint {} tripped at {angle}", self.joint);
+            return Ok(Some(offset));
+        }
+        let min = bot.config().joints[self.joint].angle_range.start;
+        if angle.get() <= min.get() {
+            return Err(
+                Error::Other("joint reached its range without hitting the end stop").into(),
+            );
+        }
+        let next = (angle.get() - self.step.get()).max(min.get());
+        bot.move_joint(self.joint, Degrees::new(next))?;
+        Ok(None)
+    }
+}
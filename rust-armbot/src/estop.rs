@@ -0,0 +1,25 @@
+//! Emergency stop switch on a dedicated GPIO.
+//!
+//! The switch is normally closed to GND with the input pulled up, so pressing it and a broken
+//! wire both trip the stop.
+
+use esp_hal::gpio::Input;
+
+/// Normally closed stop switch, tripped while the input is high.
+pub struct StopSwitch<'d> {
+    input: Input<'d>,
+}
+
+impl<'d> StopSwitch<'d> {
+    /// The input must be pulled up.
+    pub fn new(input: Input<'d>) -> Self {
+        Self { input }
+    }
+
+    /// Returns true while the switch is pressed or disconnected.
+    ///
+    /// Not debounced: a single high reading stops the arm, releasing the stop is up to the user.
+    pub fn is_tripped(&self) -> bool {
+        self.input.is_high()
+    }
+}
@@ -1,137 +1,129 @@
-use core::ops::Range;
+//! Gamepads on the pins of the chip, the gamepad types are in [`armbot_control::gamepad`].
 
+#[cfg(not(feature = "i2c-gamepad"))]
+use core::{ops::Range, time::Duration};
+
+pub use armbot_control::gamepad::*;
+#[cfg(not(feature = "nunchuk"))]
+use esp_hal::gpio::Input;
+#[cfg(not(feature = "i2c-gamepad"))]
 use esp_hal::{
     analog::adc::{Adc, AdcChannel, AdcConfig, AdcPin, Attenuation, RegisterAccess},
+    delay::Delay,
     gpio::AnalogPin,
     Blocking,
 };
-use log::{debug, info};
-
-use crate::{error::Error, util};
-
-pub struct GamepadConfig {
-    /// Min value of joystick.
-    pub joystick_min_value: u32,
-    /// Max value of joystick.
-    pub joystick_max_value: u32,
-
-    /// Defines center of joystick as offsets from middle point.
-    /// `min value = center - center_offset`
-    /// `max value = center + center_offset`
-    pub center_offset: u32,
+#[cfg(not(feature = "i2c-gamepad"))]
+use log::trace;
+
+#[cfg(not(feature = "i2c-gamepad"))]
+use crate::error::Error;
+#[cfg(not(feature = "nunchuk"))]
+use crate::{
+    clock::{Clock, SystemClock},
+    util::debounce::{DebounceMode, Debouncer},
+};
 
-    /// If set to true, then real center position will be read from the joystick at the start.
-    pub use_real_center: bool,
+/// Sticks on the ADC1 pins, centers with some play and the samples smoothed.
+#[cfg(not(feature = "i2c-gamepad"))]
+pub fn default_config() -> GamepadConfig {
+    let axis_config = AxisConfig {
+        center_offset: 100,
+        ..AxisConfig::default()
+    };
+    GamepadConfig {
+        axes: [axis_config; AXES],
+        oversampling: Oversampling::Median(5),
+        ema_alpha: Some(0.5),
+        ..GamepadConfig::default()
+    }
 }
 
-impl GamepadConfig {
-    /// Sets offset `[center-offset, center+offset]` that will be considered as center.
-    fn center_range(&self, offset: u32) -> Range<u32> {
-        offset - self.center_offset..offset + self.center_offset
-    }
+/// Debounced buttons wired to GPIOs, shared by the gamepads.
+#[cfg(not(feature = "nunchuk"))]
+pub(crate) struct Buttons<'d> {
+    /// Inputs indexed by [`Button`], pressed when low.
+    inputs: [Option<Input<'d>>; BUTTONS],
+    debouncers: [Debouncer; BUTTONS],
 }
 
-impl Default for GamepadConfig {
-    fn default() -> Self {
+#[cfg(not(feature = "nunchuk"))]
+impl<'d> Buttons<'d> {
+    pub(crate) fn new(mode: DebounceMode) -> Self {
         Self {
-            joystick_min_value: 10,
-            joystick_max_value: 2757,
-            center_offset: 50,
-            use_real_center: true,
+            inputs: Default::default(),
+            debouncers: core::array::from_fn(|_| Debouncer::new(mode, false)),
         }
     }
-}
-
-pub trait Gamepad {
-    /// Returns raw values of joystick.
-    fn read_raw_state(&mut self) -> Result<RawState, Error>;
-
-    /// Returns state of joystick mapped to the specified output range.
-    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error>;
-}
 
-#[derive(Debug, Clone, Default)]
-pub struct RawState {
-    pub base_rotator: u32,
-    pub shoulder: u32,
-    pub elbow: u32,
-    pub gripper: u32,
-}
-
-#[derive(Debug, Clone, PartialEq, Default)]
-pub struct State {
-    pub base_rotator: Position,
-    pub shoulder: Position,
-    pub elbow: Position,
-    pub gripper: Position,
-}
-
-impl State {
-    pub fn is_center(&self) -> bool {
-        self.base_rotator == Position::Center
-            && self.shoulder == Position::Center
-            && self.elbow == Position::Center
-            && self.gripper == Position::Center
+    /// The input must be pulled up and shorted to ground when pressed.
+    pub(crate) fn attach(&mut self, button: Button, input: Input<'d>) {
+        let idx = button as usize;
+        self.debouncers[idx].reset(input.is_low());
+        self.inputs[idx] = Some(input);
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Default)]
-pub enum Position {
-    Low(u32),
-    #[default]
-    Center,
-    High(u32),
-}
-
-impl Position {
-    fn new(
-        val: u32,
-        config: &GamepadConfig,
-        center_range: &Range<u32>,
-        output: &Range<u32>,
-    ) -> Self {
-        if center_range.contains(&val) {
-            Position::Center
-        } else if val < center_range.start {
-            let val = util::map(
-                val,
-                config.joystick_min_value,
-                center_range.start,
-                output.start,
-                output.end,
-                true,
-            );
-            Position::Low(val)
-        } else {
-            let val = util::map(
-                val,
-                center_range.end,
-                config.joystick_max_value,
-                output.start,
-                output.end,
-                false,
-            );
-            Position::High(val)
-        }
+    /// Reads all buttons through the debouncers, unconnected ones are never pressed.
+    pub(crate) fn read(&mut self) -> [bool; BUTTONS] {
+        let now = SystemClock.now();
+        core::array::from_fn(|idx| match &self.inputs[idx] {
+            Some(input) => {
+                self.debouncers[idx].update(input.is_low(), now);
+                self.debouncers[idx].is_high()
+            }
+            None => false,
+        })
     }
 }
 
-pub struct GamepadImpl<'d, ADC: RegisterAccess + 'd, P0, P1, P2, P3> {
-    config: GamepadConfig,
-
+/// ADC1 channels of the sticks.
+#[cfg(not(feature = "i2c-gamepad"))]
+struct AdcChannels<'d, ADC: RegisterAccess + 'd, P0, P1, P2, P3> {
     /// ADC driver in blocking mode.
     adc: Adc<'d, ADC, Blocking>,
     base_rotator_pin: AdcPin<P0, ADC>,
     shoulder_pin: AdcPin<P1, ADC>,
     elbow_pin: AdcPin<P2, ADC>,
     gripper_pin: AdcPin<P3, ADC>,
+}
 
-    base_rotator_center: Range<u32>,
-    shoulder_center: Range<u32>,
-    elbow_center: Range<u32>,
-    gripper_center: Range<u32>,
+#[cfg(not(feature = "i2c-gamepad"))]
+impl<'d, ADC, P0, P1, P2, P3> AdcChannels<'d, ADC, P0, P1, P2, P3>
+where
+    ADC: RegisterAccess + 'd,
+    P0: AnalogPin + AdcChannel,
+    P1: AnalogPin + AdcChannel,
+    P2: AnalogPin + AdcChannel,
+    P3: AnalogPin + AdcChannel,
+{
+    /// Takes a single ADC sample of every axis.
+    fn sample(&mut self) -> Result<[u32; AXES], Error> {
+        Ok([
+            self.adc
+                .read_oneshot(&mut self.base_rotator_pin)
+                .map_err(|_| Error::Adc)? as u32,
+            self.adc
+                .read_oneshot(&mut self.shoulder_pin)
+                .map_err(|_| Error::Adc)? as u32,
+            self.adc
+                .read_oneshot(&mut self.elbow_pin)
+                .map_err(|_| Error::Adc)? as u32,
+            self.adc
+                .read_oneshot(&mut self.gripper_pin)
+                .map_err(|_| Error::Adc)? as u32,
+        ])
+    }
 }
 
+/// Gamepad with the sticks read by ADC1 of the chip.
+#[cfg(not(feature = "i2c-gamepad"))]
+pub struct GamepadImpl<'d, ADC: RegisterAccess + 'd, P0, P1, P2, P3> {
+    channels: AdcChannels<'d, ADC, P0, P1, P2, P3>,
+    axes: AxisReader,
+    buttons: Buttons<'d>,
+}
+
+#[cfg(not(feature = "i2c-gamepad"))]
 impl<'d, ADC, P0, P1, P2, P3> GamepadImpl<'d, ADC, P0, P1, P2, P3>
 where
     ADC: RegisterAccess + 'd,
@@ -155,37 +147,33 @@ where
         let gripper_pin = adc_config.enable_pin(gripper_pin, Attenuation::_11dB);
         let adc = Adc::new(adc, adc_config);
 
-        let default_center_range = config.center_range(config.joystick_max_value / 2);
-        let mut gamepad = Self {
-            config,
+        let mut channels = AdcChannels {
             adc,
             base_rotator_pin,
             shoulder_pin,
             elbow_pin,
             gripper_pin,
-            base_rotator_center: default_center_range.clone(),
-            shoulder_center: default_center_range.clone(),
-            elbow_center: default_center_range.clone(),
-            gripper_center: default_center_range.clone(),
         };
+        let mut axes = AxisReader::new(config)?;
+        axes.init_centers(|| channels.sample())?;
+        let buttons = Buttons::new(axes.config().button_debounce);
+
+        Ok(Self {
+            channels,
+            axes,
+            buttons,
+        })
+    }
 
-        if gamepad.config.use_real_center {
-            // read and store center position
-            let real_positions = gamepad.read_raw_state()?;
-            gamepad.base_rotator_center = gamepad.config.center_range(real_positions.base_rotator);
-            gamepad.shoulder_center = gamepad.config.center_range(real_positions.shoulder);
-            gamepad.elbow_center = gamepad.config.center_range(real_positions.elbow);
-            gamepad.gripper_center = gamepad.config.center_range(real_positions.gripper);
-        }
-        info!("base_rotator center={:?}", gamepad.base_rotator_center);
-        info!("shoulder center={:?}", gamepad.shoulder_center);
-        info!("elbow center={:?}", gamepad.elbow_center);
-        info!("gripper center={:?}", gamepad.gripper_center);
-
-        Ok(gamepad)
+    /// Reads the button from the input, the input must be pulled up and shorted to ground
+    /// when pressed.
+    pub fn with_button(mut self, button: Button, input: Input<'d>) -> Self {
+        self.buttons.attach(button, input);
+        self
     }
 }
 
+#[cfg(not(feature = "i2c-gamepad"))]
 impl<'d, ADC, P0, P1, P2, P3> Gamepad for GamepadImpl<'d, ADC, P0, P1, P2, P3>
 where
     ADC: RegisterAccess + 'd,
@@ -195,52 +183,33 @@ where
     P3: AnalogPin + AdcChannel,
 {
     fn read_raw_state(&mut self) -> Result<RawState, Error> {
-        let base_rotator_angle = self
-            .adc
-            .read_oneshot(&mut self.base_rotator_pin)
-            .map_err(|_| Error::Adc)? as u32;
-        let shoulder_angle = self
-            .adc
-            .read_oneshot(&mut self.shoulder_pin)
-            .map_err(|_| Error::Adc)? as u32;
-        let elbow_angle = self
-            .adc
-            .read_oneshot(&mut self.elbow_pin)
-            .map_err(|_| Error::Adc)? as u32;
-        let gripper_angle = self
-            .adc
-            .read_oneshot(&mut self.gripper_pin)
-            .map_err(|_| Error::Adc)? as u32;
-
-        fn normalize_value(val: u32, config: &GamepadConfig) -> u32 {
-            val.min(config.joystick_max_value)
-                .max(config.joystick_min_value)
-        }
-
         let state = RawState {
-            base_rotator: normalize_value(base_rotator_angle, &self.config),
-            shoulder: normalize_value(shoulder_angle, &self.config),
-            elbow: normalize_value(elbow_angle, &self.config),
-            gripper: normalize_value(gripper_angle, &self.config),
+            axes: self.axes.read(|| self.channels.sample())?,
+            buttons: self.buttons.read(),
         };
-        debug!("raw state = {:?}", state);
+        trace!("raw state = {:?}", state);
         Ok(state)
     }
 
     fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error> {
         let state = self.read_raw_state()?;
-        let state = State {
-            base_rotator: Position::new(
-                state.base_rotator,
-                &self.config,
-                &self.base_rotator_center,
-                output,
-            ),
-            shoulder: Position::new(state.shoulder, &self.config, &self.shoulder_center, output),
-            elbow: Position::new(state.elbow, &self.config, &self.elbow_center, output),
-            gripper: Position::new(state.gripper, &self.config, &self.gripper_center, output),
-        };
-        debug!("state = {:?}", state);
+        let state = self.axes.state(&state, output)?;
+        trace!("state = {:?}", state);
         Ok(state)
     }
+
+    fn calibrate(
+        &mut self,
+        clock: &impl Clock,
+        hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        let delay = Delay::new();
+        self.axes.calibrate(clock, hold, || {
+            delay.delay_millis(CALIBRATION_SAMPLE_PERIOD_MS);
+            self.channels.sample()
+        })
+    }
 }
+
+/// Interval between samples of the calibration, ADC reads are much faster than sticks move.
+pub(crate) const CALIBRATION_SAMPLE_PERIOD_MS: u32 = 10;
@@ -0,0 +1,130 @@
+//! Force-limited gripping, so closing on an object doesn't strip the gears of the servo.
+//!
+//! [`Gripper::grip`] closes the gripper step by step while watching the current of its servo.
+//! Once the current goes over the limit, the gripper opens slightly and holds there: a hobby
+//! servo pushes with a force growing with its position error, so backing off lowers the duty it
+//! drives the motor with while the object stays gripped.
+
+use ledc_servo::ServoDriver;
+use log::info;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    current::CurrentSensor,
+    error::{Error, Report},
+    gamepad::{Gamepad, Position},
+    units::Degrees,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GripConfig {
+    /// Index of the gripper joint.
+    pub joint: usize,
+    /// Angle of the fully closed gripper.
+    pub closed: Degrees,
+    /// How far the gripper closes every poll.
+    pub step: Degrees,
+    /// How far the gripper opens from where the current went over the limit.
+    pub relax: Degrees,
+}
+
+impl Default for GripConfig {
+    /// Gripper of the default arm config, it closes towards the lower end of its range.
+    fn default() -> Self {
+        Self {
+            joint: 2,
+            closed: Degrees::from_whole(20),
+            step: Degrees::new(0.5),
+            relax: Degrees::new(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GripState {
+    /// Not gripping, the gripper is left to the sticks.
+    Idle,
+    /// Closing until the current goes over the limit in milliamps.
+    Closing { force_limit_ma: u32 },
+    /// Holding an object at the angle.
+    Holding { force_limit_ma: u32, angle: Degrees },
+}
+
+/// Gripper joint with a current sensor on its servo rail.
+pub struct Gripper<S> {
+    config: GripConfig,
+    sensor: S,
+    state: GripState,
+}
+
+impl<S: CurrentSensor> Gripper<S> {
+    pub fn new(config: GripConfig, sensor: S) -> Self {
+        Self {
+            config,
+            sensor,
+            state: GripState::Idle,
+        }
+    }
+
+    /// Starts closing the gripper, [`Gripper::poll`] closes it until the current of the servo
+    /// goes over `force_limit_ma`.
+    pub fn grip(&mut self, force_limit_ma: u32) {
+        self.state = GripState::Closing { force_limit_ma };
+    }
+
+    /// Stops gripping, the gripper stays where it is.
+    pub fn release(&mut self) {
+        self.state = GripState::Idle;
+    }
+
+    /// Advances the grip, should be called every control period after the step of the arm.
+    /// Moving the gripper stick takes the gripper back.
+    pub fn poll<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        &mut self,
+        bot: &mut ArmBot<G, D, N, B>,
+    ) -> Result<(), Report> {
+        let (force_limit_ma, holding) = match self.state {
+            GripState::Idle => return Ok(()),
+            GripState::Closing { force_limit_ma } => (force_limit_ma, false),
+            GripState::Holding { force_limit_ma, .. } => (force_limit_ma, true),
+        };
+        let joint = self.config.joint;
+        let axis = bot
+            .config()
+            .joints
+            .get(joint)
+            .ok_or(Error::OutOfRange("no such joint"))?
+            .axis;
+        if bot.stick(axis) != Position::Center || bot.is_stopped() {
+            info!("grip cancelled");
+            self.state = GripState::Idle;
+            return Ok(());
+        }
+
+        let angle = bot.joint_angles()[joint];
+        let current_ma = self.sensor.read_ma()?;
+        if current_ma > force_limit_ma {
+            // an object that settled in the gripper may push it again while holding
+            let angle = Degrees::new(angle.get() + self.config.relax.get());
+            if !holding {
+                info!("gripped at {current_ma} mA, holding at {angle}");
+            }
+            self.state = GripState::Holding {
+                force_limit_ma,
+                angle,
+            };
+            return bot.move_joint(joint, angle);
+        }
+        if holding {
+            return Ok(());
+        }
+        if angle.get() <= self.config.closed.get() {
+            info!("gripper closed without resistance");
+            self.state = GripState::Idle;
+            return Ok(());
+        }
+        let next = (angle.get() - self.config.step.get()).max(self.config.closed.get());
+        bot.move_joint(joint, Degrees::new(next))
+    }
+}
@@ -0,0 +1,155 @@
+//! Gamepad read by an ADS1115 ADC expander over I2C, keeps ADC1 pins of the chip free.
+
+use core::{ops::Range, time::Duration};
+
+use embedded_hal::i2c::I2c;
+use esp_hal::{delay::Delay, gpio::Input};
+use log::trace;
+
+use crate::{
+    clock::Clock,
+    error::Error,
+    gamepad::{
+        AxisConfig, AxisReader, Button, Buttons, Gamepad, GamepadConfig, RawState, State, AXES,
+        CALIBRATION_SAMPLE_PERIOD_MS,
+    },
+};
+
+/// Address with the ADDR pin tied to GND.
+pub const ADS1115_ADDRESS: u8 = 0x48;
+
+/// Reading of 3.3 V with the ±4.096 V range, the end of a stick powered by 3.3 V.
+pub const FULL_SCALE_3V3: u32 = 26_400;
+
+const REG_CONVERSION: u8 = 0x00;
+const REG_CONFIG: u8 = 0x01;
+
+/// Single-shot conversion without the channel: start, ±4.096 V range, single-shot mode,
+/// 860 samples per second, comparator disabled.
+const CONFIG_SINGLE_SHOT: u16 = 1 << 15 | 0b001 << 9 | 1 << 8 | 0b111 << 5 | 0b11;
+/// Bit of the config register, set when no conversion is running.
+const CONFIG_IDLE: u16 = 1 << 15;
+/// Polls of a conversion before giving up, a conversion takes ~1.2 ms.
+const MAX_POLLS: u32 = 100;
+
+/// Sticks powered by 3.3 V. A read takes too long for oversampling, the EMA filter smooths
+/// the noise instead.
+pub fn default_config() -> GamepadConfig {
+    let axis_config = AxisConfig {
+        min_value: 100,
+        max_value: FULL_SCALE_3V3,
+        center_offset: 500,
+        ..AxisConfig::default()
+    };
+    GamepadConfig {
+        axes: [axis_config; AXES],
+        ema_alpha: Some(0.5),
+        ..GamepadConfig::default()
+    }
+}
+
+/// ADS1115 with the axes on AIN0..AIN3 in the order of [`crate::gamepad::Axis`].
+struct Ads1115<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Ads1115<I> {
+    /// Converts every channel one by one.
+    fn sample(&mut self) -> Result<[u32; AXES], Error> {
+        let mut values = [0; AXES];
+        for (channel, value) in values.iter_mut().enumerate() {
+            *value = self.convert(channel as u16)?;
+        }
+        Ok(values)
+    }
+
+    fn convert(&mut self, channel: u16) -> Result<u32, Error> {
+        // AINx against GND
+        let mux = (0b100 | channel) << 12;
+        self.write_config(CONFIG_SINGLE_SHOT | mux)?;
+        let mut polls = 0;
+        while self.read_register(REG_CONFIG)? & CONFIG_IDLE == 0 {
+            polls += 1;
+            if polls >= MAX_POLLS {
+                return Err(Error::Other("ADS1115 conversion timed out"));
+            }
+        }
+        // single-ended readings are only negative because of the offset around GND
+        let value = self.read_register(REG_CONVERSION)? as i16;
+        Ok(value.max(0) as u32)
+    }
+
+    fn write_config(&mut self, config: u16) -> Result<(), Error> {
+        let [hi, lo] = config.to_be_bytes();
+        self.i2c
+            .write(self.address, &[REG_CONFIG, hi, lo])
+            .map_err(|_| Error::I2c)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, Error> {
+        let mut buf = [0; 2];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .map_err(|_| Error::I2c)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+/// Gamepad with the sticks read by an ADS1115 over I2C.
+///
+/// A read converts the four channels one by one and takes ~5 ms, so oversampling of the config
+/// doesn't fit the control period, see [`default_config`].
+pub struct Ads1115Gamepad<'d, I> {
+    ads: Ads1115<I>,
+    axes: AxisReader,
+    buttons: Buttons<'d>,
+}
+
+impl<'d, I: I2c> Ads1115Gamepad<'d, I> {
+    /// Axes of the config are raw readings, see [`default_config`].
+    pub fn new(config: GamepadConfig, i2c: I, address: u8) -> Result<Self, Error> {
+        let mut ads = Ads1115 { i2c, address };
+        let mut axes = AxisReader::new(config)?;
+        axes.init_centers(|| ads.sample())?;
+        let buttons = Buttons::new(axes.config().button_debounce);
+        Ok(Self { ads, axes, buttons })
+    }
+
+    /// Reads the button from the input, the input must be pulled up and shorted to ground
+    /// when pressed.
+    pub fn with_button(mut self, button: Button, input: Input<'d>) -> Self {
+        self.buttons.attach(button, input);
+        self
+    }
+}
+
+impl<I: I2c> Gamepad for Ads1115Gamepad<'_, I> {
+    fn read_raw_state(&mut self) -> Result<RawState, Error> {
+        let state = RawState {
+            axes: self.axes.read(|| self.ads.sample())?,
+            buttons: self.buttons.read(),
+        };
+        trace!("raw state = {:?}", state);
+        Ok(state)
+    }
+
+    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error> {
+        let state = self.read_raw_state()?;
+        let state = self.axes.state(&state, output)?;
+        trace!("state = {:?}", state);
+        Ok(state)
+    }
+
+    fn calibrate(
+        &mut self,
+        clock: &impl Clock,
+        hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        let delay = Delay::new();
+        self.axes.calibrate(clock, hold, || {
+            delay.delay_millis(CALIBRATION_SAMPLE_PERIOD_MS);
+            self.ads.sample()
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! IMU on the forearm over I2C, measures the pitch of the gripper for the level hold, see
+//! [`crate::armbot::ArmBot::set_level_hold`].
+//!
+//! The IMU is mounted with X along the forearm towards the gripper and Z up while the forearm
+//! is level. [`PitchFilter`] fuses the gyro rate, smooth but drifting, with the gravity seen by
+//! the accelerometer, noisy and shaken by the motion but without drift.
+
+use embedded_hal::i2c::I2c;
+use libm::{atan2f, sqrtf};
+use log::info;
+
+use crate::{error::Error, units::Degrees};
+
+/// Address with AD0 tied to GND, the same for both supported IMUs.
+pub const IMU_ADDRESS: u8 = 0x68;
+
+const REG_WHO_AM_I: u8 = 0x75;
+
+/// Accelerations in g and rotation rates in °/s, along and around X, Y and Z.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImuSample {
+    pub accel: [f32; 3],
+    pub gyro: [f32; 3],
+}
+
+/// Source of IMU samples.
+pub trait Imu {
+    fn read(&mut self) -> Result<ImuSample, Error>;
+}
+
+/// Registers and scales of an IMU, at its default ranges.
+struct Registers {
+    who_am_i: u8,
+    /// Register and value that wake the sensor up.
+    power: (u8, u8),
+    /// First of the accel X, Y, Z registers, big endian.
+    accel: u8,
+    /// First of the gyro X, Y, Z registers, big endian.
+    gyro: u8,
+    lsb_per_g: f32,
+    lsb_per_dps: f32,
+}
+
+/// ±2 g and ±250 °/s.
+const MPU6050: Registers = Registers {
+    who_am_i: 0x68,
+    power: (0x6b, 0x00),
+    accel: 0x3b,
+    gyro: 0x43,
+    lsb_per_g: 16384.0,
+    lsb_per_dps: 131.0,
+};
+
+/// ±16 g and ±2000 °/s, accel and gyro in low noise mode.
+const ICM42688: Registers = Registers {
+    who_am_i: 0x47,
+    power: (0x4e, 0x0f),
+    accel: 0x1f,
+    gyro: 0x25,
+    lsb_per_g: 2048.0,
+    lsb_per_dps: 16.4,
+};
+
+/// MPU6050 or ICM-42688, told apart by their ID.
+pub struct I2cImu<I> {
+    i2c: I,
+    address: u8,
+    regs: &'static Registers,
+}
+
+impl<I: I2c> I2cImu<I> {
+    /// Detects the IMU and wakes it up.
+    pub fn new(mut i2c: I, address: u8) -> Result<Self, Error> {
+        let mut id = [0];
+        i2c.write_read(address, &[REG_WHO_AM_I], &mut id)
+            .map_err(|_| Error::I2c)?;
+        let (regs, name) = match id[0] {
+            id if id == MPU6050.who_am_i => (&MPU6050, "MPU6050"),
+            id if id == ICM42688.who_am_i => (&ICM42688, "ICM-42688"),
+            _ => return Err(Error::Other("unknown IMU")),
+        };
+        let (reg, value) = regs.power;
+        i2c.write(address, &[reg, value]).map_err(|_| Error::I2c)?;
+        info!("{name} IMU found");
+        Ok(Self { i2c, address, regs })
+    }
+
+    fn read_axes(&mut self, first: u8, lsb: f32) -> Result<[f32; 3], Error> {
+        let mut buf = [0; 6];
+        self.i2c
+            .write_read(self.address, &[first], &mut buf)
+            .map_err(|_| Error::I2c)?;
+        Ok(core::array::from_fn(|idx| {
+            i16::from_be_bytes([buf[2 * idx], buf[2 * idx + 1]]) as f32 / lsb
+        }))
+    }
+}
+
+impl<I: I2c> Imu for I2cImu<I> {
+    fn read(&mut self) -> Result<ImuSample, Error> {
+        Ok(ImuSample {
+            accel: self.read_axes(self.regs.accel, self.regs.lsb_per_g)?,
+            gyro: self.read_axes(self.regs.gyro, self.regs.lsb_per_dps)?,
+        })
+    }
+}
+
+/// Complementary filter of the pitch, positive when the gripper points up.
+#[derive(Debug, Clone)]
+pub struct PitchFilter {
+    /// Weight of the integrated gyro rate, the rest is the accel pitch.
+    alpha: f32,
+    pitch: Option<f32>,
+}
+
+impl PitchFilter {
+    /// `alpha` close to 1 trusts the gyro more, 0.98 at 100 Hz corrects drift in about
+    /// half a second.
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, pitch: None }
+    }
+
+    /// Feeds a sample taken `dt` seconds after the previous one, returns the pitch.
+    pub fn update(&mut self, sample: &ImuSample, dt: f32) -> Degrees {
+        let [x, y, z] = sample.accel;
+        let accel_pitch = atan2f(x, sqrtf(y * y + z * z)).to_degrees();
+        // Y points left, rotating the gripper up around it is negative
+        let rate = -sample.gyro[1];
+        let pitch = match self.pitch {
+            Some(pitch) => self.alpha * (pitch + rate * dt) + (1.0 - self.alpha) * accel_pitch,
+            None => accel_pitch,
+        };
+        self.pitch = Some(pitch);
+        Degrees::new(pitch)
+    }
+
+    pub fn reset(&mut self) {
+        self.pitch = None;
+    }
+}
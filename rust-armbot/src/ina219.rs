@@ -0,0 +1,53 @@
+//! INA219 current monitor over I2C, measures a servo rail through a shunt resistor.
+
+use embedded_hal::i2c::I2c;
+
+use crate::{current::CurrentSensor, error::Error};
+
+/// Address with A0 and A1 tied to GND.
+pub const INA219_ADDRESS: u8 = 0x40;
+
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+/// Shunt voltage LSB in microvolts.
+const SHUNT_LSB_UV: i32 = 10;
+
+/// INA219 in its power-on configuration: ±320 mV shunt range, continuous conversions.
+///
+/// Current is computed from the shunt voltage, so the calibration register isn't used.
+pub struct Ina219<I> {
+    i2c: I,
+    address: u8,
+    shunt_milliohms: u32,
+}
+
+impl<I: I2c> Ina219<I> {
+    /// Breakout boards have a 100 mΩ shunt, it measures up to 3.2 A.
+    pub fn new(i2c: I, address: u8, shunt_milliohms: u32) -> Result<Self, Error> {
+        if shunt_milliohms == 0 {
+            return Err(Error::OutOfRange("shunt resistance must not be zero"));
+        }
+        Ok(Self {
+            i2c,
+            address,
+            shunt_milliohms,
+        })
+    }
+
+    /// Returns the shunt voltage in microvolts.
+    fn shunt_voltage_uv(&mut self) -> Result<i32, Error> {
+        let mut buf = [0; 2];
+        self.i2c
+            .write_read(self.address, &[REG_SHUNT_VOLTAGE], &mut buf)
+            .map_err(|_| Error::I2c)?;
+        Ok(i16::from_be_bytes(buf) as i32 * SHUNT_LSB_UV)
+    }
+}
+
+impl<I: I2c> CurrentSensor for Ina219<I> {
+    /// Current flowing back to the supply reads as zero.
+    fn read_ma(&mut self) -> Result<u32, Error> {
+        // µV / mΩ = mA
+        let uv = self.shunt_voltage_uv()?.max(0) as u32;
+        Ok(uv / self.shunt_milliohms)
+    }
+}
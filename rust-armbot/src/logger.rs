@@ -0,0 +1,120 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use esp_println::println;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Modules with separately adjustable verbosity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Module {
+    Servo,
+    Gamepad,
+    ArmBot,
+    /// Everything else.
+    Other,
+}
+
+impl Module {
+    const COUNT: usize = 4;
+
+    pub const ALL: [Module; Module::COUNT] = [
+        Module::Servo,
+        Module::Gamepad,
+        Module::ArmBot,
+        Module::Other,
+    ];
+
+    /// Name of the module in console commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            Module::Servo => "servo",
+            Module::Gamepad => "gamepad",
+            Module::ArmBot => "armbot",
+            Module::Other => "other",
+        }
+    }
+
+    /// Parses a module name as it's used in console commands, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|module| module.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Maps a log target (module path by default) to the module.
+    fn from_target(target: &str) -> Self {
+        if target.starts_with("ledc_servo") || target.starts_with("rust_armbot::servo") {
+            Module::Servo
+        } else if target.starts_with("rust_armbot::gamepad")
+            || target.starts_with("armbot_control::gamepad")
+        {
+            Module::Gamepad
+        } else if target.starts_with("armbot_control::armbot") {
+            Module::ArmBot
+        } else {
+            Module::Other
+        }
+    }
+}
+
+/// Current level of each module, indexed by `Module as usize`.
+static LEVELS: [AtomicU8; Module::COUNT] = [
+    AtomicU8::new(LevelFilter::Info as u8),
+    AtomicU8::new(LevelFilter::Info as u8),
+    AtomicU8::new(LevelFilter::Info as u8),
+    AtomicU8::new(LevelFilter::Info as u8),
+];
+
+static LOGGER: Logger = Logger;
+
+/// Logger printing to the serial port with per-module levels that can be changed at runtime.
+struct Logger;
+
+/// Installs the logger, should be called once at boot.
+pub fn init() {
+    // SAFETY: called once from main before any interrupt handler that could log is bound.
+    unsafe {
+        log::set_logger_racy(&LOGGER).expect("logger is already set");
+        // filtering happens in the logger itself
+        log::set_max_level_racy(LevelFilter::Trace);
+    }
+}
+
+/// Sets verbosity of the module.
+pub fn set_level(module: Module, level: LevelFilter) {
+    LEVELS[module as usize].store(level as u8, Ordering::Relaxed);
+}
+
+/// Returns verbosity of the module.
+pub fn level(module: Module) -> LevelFilter {
+    match LEVELS[module as usize].load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= level(Module::from_target(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = match record.level() {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN ",
+            Level::Info => "INFO ",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        println!("{} {}: {}", level, record.target(), record.args());
+    }
+
+    fn flush(&self) {}
+}
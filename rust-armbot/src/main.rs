@@ -1,100 +1,924 @@
 #![no_std]
 #![no_main]
 
-use esp_backtrace as _;
+#[cfg(feature = "i2c")]
+use core::cell::RefCell;
+
+#[cfg(any(feature = "current-sense", feature = "end-stops"))]
+use armbot_control::protocol::ErrorCode;
+#[cfg(feature = "ota")]
+use armbot_control::protocol::{decode_hex, MAX_CHUNK_LEN};
+use armbot_control::{
+    armbot, error,
+    protocol::{Command, CommandError, Reply},
+    script, settings, units, util,
+};
+#[cfg(feature = "buzzer")]
+use buzzer::{Buzzer, LedcTone};
+#[cfg(feature = "current-sense")]
+use current::{RailMonitor, StallConfig};
+#[cfg(feature = "demo")]
+use demo::{Demo, DemoConfig};
+#[cfg(feature = "display")]
+use display::{Controller, Oled, OLED_ADDRESS};
+#[cfg(feature = "i2c")]
+use embedded_hal_bus::i2c::RefCellDevice;
+#[cfg(feature = "encoder-base")]
+use encoder::{EncoderBase, EncoderBaseConfig, HBridge};
+#[cfg(feature = "end-stops")]
+use endstop::{EndStopConfig, Homing, JointEndStops, LimitSwitch};
+#[cfg(feature = "encoder-base")]
+use esp_hal::gpio::Io;
+#[cfg(any(feature = "stepper-base", feature = "encoder-base"))]
+use esp_hal::gpio::{Level, Output, OutputConfig};
+#[cfg(feature = "i2c")]
+use esp_hal::i2c::{self, master::I2c};
+#[cfg(not(feature = "i2c-gamepad"))]
+use esp_hal::peripherals::{ADC1, GPIO0, GPIO1, GPIO2, GPIO3};
+#[cfg(feature = "status-led")]
+use esp_hal::rmt::{Rmt, TxChannelConfig, TxChannelCreator};
+#[cfg(any(feature = "buzzer", feature = "encoder-base"))]
 use esp_hal::{
-    delay::Delay,
-    ledc::{channel, timer, timer::config::Duty, Ledc},
-    peripherals::{ADC1, GPIO0, GPIO1, GPIO2, GPIO3},
+    gpio::DriveMode,
+    ledc::{channel::ChannelIFace, timer::TimerIFace},
+};
+use esp_hal::{
+    gpio::{Input, InputConfig, Pin, Pull},
+    ledc::{channel, timer, timer::config::Duty, Ledc, LowSpeed},
+    time::{Duration, Rate},
+    timer::timg::TimerGroup,
+    uart::{self, Uart},
     Config,
 };
-use esp_hal_servo::{Servo, ServoConfig};
+#[cfg(not(feature = "nunchuk"))]
+use gamepad::Button;
+#[cfg(not(feature = "i2c-gamepad"))]
+use gamepad::GamepadImpl;
+#[cfg(feature = "current-sense")]
+use gripper::{GripConfig, Gripper};
+#[cfg(feature = "imu")]
+use imu::{I2cImu, Imu, PitchFilter, IMU_ADDRESS};
+#[cfg(feature = "current-sense")]
+use ina219::{Ina219, INA219_ADDRESS};
+use ledc_servo::{Servo, ServoConfig};
+#[cfg(feature = "battery")]
+use power::{BatteryAdc, BatteryMonitor, PowerConfig};
+#[cfg(feature = "status-led")]
+use status_led::{LedClock, LedState, StatusLed, StatusLedConfig, Ws2812};
+#[cfg(feature = "stepper-base")]
+use stepper::{Stepper, StepperConfig, A4988};
 
 use crate::{
-    armbot::{ArmBot, ArmBotConfig},
-    gamepad::{GamepadConfig, GamepadImpl},
+    armbot::{ArmBot, ArmBotConfig, JOINTS},
+    clock::{Clock, SystemClock},
+    console::Console,
+    error::{Error, Report},
+    estop::StopSwitch,
+    gamepad::Gamepad,
+    pins::{PinAssignment, PinRole},
+    scheduler::Scheduler,
+    settings::Settings,
+    storage::FlashStore,
+    telemetry::{LogSink, LoopTiming, Sink, Snapshot},
+    ticker::{LoopStats, Ticker},
+    watchdog::{Watchdog, WatchdogConfig},
 };
 
-mod armbot;
-mod error;
+#[cfg(feature = "buzzer")]
+mod buzzer;
+mod clock;
+mod console;
+mod crash_log;
+#[cfg(feature = "current-sense")]
+mod current;
+#[cfg(feature = "demo")]
+mod demo;
+#[cfg(feature = "display")]
+mod display;
+#[cfg(feature = "encoder-base")]
+mod encoder;
+#[cfg(feature = "end-stops")]
+mod endstop;
+mod estop;
 mod gamepad;
-mod util;
+#[cfg(feature = "current-sense")]
+mod gripper;
+#[cfg(all(feature = "i2c-gamepad", not(feature = "nunchuk")))]
+mod i2c_gamepad;
+#[cfg(feature = "imu")]
+mod imu;
+#[cfg(feature = "current-sense")]
+mod ina219;
+#[cfg(feature = "logger")]
+mod logger;
+#[cfg(feature = "nunchuk")]
+mod nunchuk;
+#[cfg(feature = "ota")]
+mod ota;
+mod pins;
+#[cfg(feature = "battery")]
+mod power;
+mod safe_mode;
+mod scheduler;
+#[cfg(feature = "status-led")]
+mod status_led;
+#[cfg(feature = "stepper-base")]
+mod stepper;
+mod storage;
+mod telemetry;
+mod ticker;
+mod watchdog;
+
+/// Period of the control loop, joint speeds and motion profiles are scaled to it.
+const CONTROL_PERIOD: Duration =
+    Duration::from_micros(armbot_control::CONTROL_PERIOD.as_micros() as u64);
+/// Rate of the control loop.
+const CONTROL_RATE: Rate = Rate::from_hz((1_000_000 / CONTROL_PERIOD.as_micros()) as u32);
+/// How often loop statistics are reported.
+const REPORT_PERIOD: Duration = Duration::from_secs(1);
+const _: () = assert!(
+    REPORT_PERIOD
+        .as_micros()
+        .is_multiple_of(CONTROL_PERIOD.as_micros()),
+    "the report period must be a whole number of control periods"
+);
+
+/// How long every step of the gamepad calibration takes.
+const CALIBRATION_HOLD: core::time::Duration = core::time::Duration::from_secs(3);
+
+/// LEDC channels of the servos in the joint order, ESP32-C3 has six.
+const SERVO_CHANNELS: [channel::Number; 6] = [
+    channel::Number::Channel0,
+    channel::Number::Channel1,
+    channel::Number::Channel2,
+    channel::Number::Channel3,
+    channel::Number::Channel4,
+    channel::Number::Channel5,
+];
+const _: () = assert!(
+    JOINTS <= SERVO_CHANNELS.len(),
+    "every joint needs a LEDC channel"
+);
+
+/// Pitch of the buzzer, near the resonance of the common 12 mm buzzers.
+#[cfg(feature = "buzzer")]
+const BUZZER_TONE: Rate = Rate::from_hz(2_700);
+/// LEDC channel of the buzzer, the last one, after the servo channels.
+#[cfg(feature = "buzzer")]
+const BUZZER_CHANNEL: channel::Number = channel::Number::Channel5;
+#[cfg(feature = "buzzer")]
+const _: () = assert!(
+    JOINTS < SERVO_CHANNELS.len(),
+    "the buzzer needs a LEDC channel left by the servos"
+);
+
+/// Joint of the gripper, its servo rail is measured.
+#[cfg(feature = "current-sense")]
+const GRIPPER_JOINT: usize = 2;
+/// Shunt of the INA219 breakout boards.
+#[cfg(feature = "current-sense")]
+const INA219_SHUNT_MILLIOHMS: u32 = 100;
+
+/// PWM frequency of the base motor, above hearing.
+#[cfg(feature = "encoder-base")]
+const MOTOR_PWM: Rate = Rate::from_khz(20);
+/// Duty resolution of the base motor PWM, 80 MHz / 20 kHz leaves 11 bits.
+#[cfg(feature = "encoder-base")]
+const MOTOR_DUTY: Duty = Duty::Duty10Bit;
+/// LEDC channel of the base motor, after the servo channels.
+#[cfg(feature = "encoder-base")]
+const MOTOR_CHANNEL: channel::Number = channel::Number::Channel4;
+#[cfg(feature = "encoder-base")]
+const _: () = assert!(
+    JOINTS < 5,
+    "the base motor needs a LEDC channel left by the servos"
+);
+
+/// Gyro weight of the forearm pitch filter.
+#[cfg(feature = "imu")]
+const PITCH_ALPHA: f32 = 0.98;
+
+/// Controller of the OLED, most 0.96" modules have an SSD1306, 1.3" ones an SH1106.
+#[cfg(feature = "display")]
+const OLED_CONTROLLER: Controller = if cfg!(feature = "sh1106") {
+    Controller::Sh1106
+} else {
+    Controller::Ssd1306
+};
+/// How often a line of the OLED is redrawn, the screen takes 8 of them.
+#[cfg(feature = "display")]
+const DISPLAY_PERIOD: Duration = Duration::from_millis(50);
+
+/// Joints with a min end stop, the shoulder on GPIO18 and the elbow on GPIO19.
+#[cfg(feature = "end-stops")]
+const END_STOP_JOINTS: [usize; 2] = [0, 1];
+/// How far inside the angle range the end stops trip, so homing reaches them before the end.
+#[cfg(feature = "end-stops")]
+const END_STOP_MARGIN: f32 = 5.0;
+/// Homing step per control period, 20 °/s.
+#[cfg(feature = "end-stops")]
+const HOMING_STEP: units::Degrees = units::Degrees::new(0.2);
+
+/// Tasks of the main loop, indices into the scheduler periods.
+const CONTROL_TASK: usize = 0;
+const REPORT_TASK: usize = 1;
+#[cfg(feature = "display")]
+const DISPLAY_TASK: usize = 2;
 
 esp_bootloader_esp_idf::esp_app_desc!();
 
+// global defmt logger, probe-rs reads it over the USB-JTAG
+#[cfg(feature = "defmt")]
+use defmt_rtt as _;
+#[cfg(all(feature = "defmt", feature = "i2c"))]
+compile_error!("the I2C bus takes the USB pins, defmt can't be read over them");
+#[cfg(all(feature = "status-led", not(feature = "i2c-gamepad")))]
+compile_error!("the status LED is on GPIO1, enable i2c-gamepad to free it");
+#[cfg(all(feature = "battery", not(feature = "i2c-gamepad")))]
+compile_error!("the battery divider is on GPIO0, enable i2c-gamepad to free it");
+#[cfg(all(feature = "buzzer", not(feature = "i2c-gamepad")))]
+compile_error!("the buzzer is on GPIO3, enable i2c-gamepad to free it");
+#[cfg(all(feature = "stepper-base", not(feature = "i2c-gamepad")))]
+compile_error!("the stepper driver is on GPIO2 and GPIO3, enable i2c-gamepad to free them");
+#[cfg(all(feature = "stepper-base", feature = "buzzer"))]
+compile_error!("the stepper driver and the buzzer both need GPIO3");
+#[cfg(all(feature = "encoder-base", not(feature = "i2c-gamepad")))]
+compile_error!("the encoder base is on GPIO0-GPIO3, enable i2c-gamepad to free them");
+#[cfg(all(
+    feature = "encoder-base",
+    any(
+        feature = "battery",
+        feature = "status-led",
+        feature = "buzzer",
+        feature = "stepper-base"
+    )
+))]
+compile_error!(
+    "the encoder base takes GPIO0-GPIO3, it doesn't go with battery, status-led, buzzer or stepper-base"
+);
+#[cfg(all(feature = "end-stops", any(feature = "i2c", feature = "defmt")))]
+compile_error!("the end stops take the USB pins, they don't go with the I2C bus or defmt");
+
 #[riscv_rt::entry]
 fn main() -> ! {
+    #[cfg(feature = "logger")]
+    logger::init();
     let peripherals = esp_hal::init(Config::default());
+    crash_log::report_and_reset();
 
+    pins::validate(&[
+        PinAssignment::new("shoulder servo", peripherals.GPIO5.number(), PinRole::Pwm),
+        PinAssignment::new("elbow servo", peripherals.GPIO6.number(), PinRole::Pwm),
+        PinAssignment::new("gripper servo", peripherals.GPIO7.number(), PinRole::Pwm),
+        #[cfg(not(feature = "i2c-gamepad"))]
+        PinAssignment::new("joystick 1 X", peripherals.GPIO0.number(), PinRole::Adc),
+        #[cfg(not(feature = "i2c-gamepad"))]
+        PinAssignment::new("joystick 1 Y", peripherals.GPIO1.number(), PinRole::Adc),
+        #[cfg(not(feature = "i2c-gamepad"))]
+        PinAssignment::new("joystick 2 X", peripherals.GPIO2.number(), PinRole::Adc),
+        #[cfg(not(feature = "i2c-gamepad"))]
+        PinAssignment::new("joystick 2 Y", peripherals.GPIO3.number(), PinRole::Adc),
+        #[cfg(feature = "battery")]
+        PinAssignment::new("battery divider", peripherals.GPIO0.number(), PinRole::Adc),
+        #[cfg(feature = "status-led")]
+        PinAssignment::new("status LED", peripherals.GPIO1.number(), PinRole::Output),
+        #[cfg(feature = "buzzer")]
+        PinAssignment::new("buzzer", peripherals.GPIO3.number(), PinRole::Pwm),
+        #[cfg(feature = "stepper-base")]
+        PinAssignment::new("stepper STEP", peripherals.GPIO2.number(), PinRole::Output),
+        #[cfg(feature = "stepper-base")]
+        PinAssignment::new("stepper DIR", peripherals.GPIO3.number(), PinRole::Output),
+        #[cfg(feature = "encoder-base")]
+        PinAssignment::new("base motor PWM", peripherals.GPIO1.number(), PinRole::Pwm),
+        #[cfg(feature = "encoder-base")]
+        PinAssignment::new(
+            "base motor DIR",
+            peripherals.GPIO2.number(),
+            PinRole::Output,
+        ),
+        #[cfg(feature = "encoder-base")]
+        PinAssignment::new("base encoder A", peripherals.GPIO0.number(), PinRole::Input),
+        #[cfg(feature = "encoder-base")]
+        PinAssignment::new("base encoder B", peripherals.GPIO3.number(), PinRole::Input),
+        #[cfg(feature = "end-stops")]
+        PinAssignment::new(
+            "shoulder end stop",
+            peripherals.GPIO18.number(),
+            PinRole::Input,
+        ),
+        #[cfg(feature = "end-stops")]
+        PinAssignment::new(
+            "elbow end stop",
+            peripherals.GPIO19.number(),
+            PinRole::Input,
+        ),
+        #[cfg(feature = "i2c")]
+        PinAssignment::new("I2C SDA", peripherals.GPIO18.number(), PinRole::I2c),
+        #[cfg(feature = "i2c")]
+        PinAssignment::new("I2C SCL", peripherals.GPIO19.number(), PinRole::I2c),
+        #[cfg(not(feature = "nunchuk"))]
+        PinAssignment::new(
+            "joystick 1 switch",
+            peripherals.GPIO4.number(),
+            PinRole::Input,
+        ),
+        #[cfg(not(feature = "nunchuk"))]
+        PinAssignment::new(
+            "joystick 2 switch",
+            peripherals.GPIO10.number(),
+            PinRole::Input,
+        ),
+        PinAssignment::new("stop switch", peripherals.GPIO8.number(), PinRole::Input),
+        PinAssignment::new(
+            "safe mode button",
+            peripherals.GPIO9.number(),
+            PinRole::Input,
+        ),
+    ])
+    .expect("invalid pin assignment, see the log above");
+
+    // BOOT button of the board, pressing it right after reset starts the safe mode,
+    // holding it during reset enters the ROM download mode.
+    // Later holding it starts the gamepad calibration.
+    let safe_mode_button = Input::new(
+        peripherals.GPIO9,
+        InputConfig::default().with_pull(Pull::Up),
+    );
+    let button_pressed = safe_mode::button_pressed(&safe_mode_button);
+
+    #[cfg(not(feature = "i2c-gamepad"))]
+    let mut gamepad_config = gamepad::default_config();
+    #[cfg(all(feature = "i2c-gamepad", not(feature = "nunchuk")))]
+    let mut gamepad_config = i2c_gamepad::default_config();
+    #[cfg(feature = "nunchuk")]
+    let mut gamepad_config = nunchuk::default_config();
+    let mut arm_config = ArmBotConfig::default();
     let servo_cfg = ServoConfig::sg90(Duty::Duty14Bit);
-    let mut ledc = Ledc::new(peripherals.LEDC);
-    let timer = servo_cfg
-        .configure_timer(
-            &mut ledc,
-            timer::Number::Timer0,
-            timer::LSClockSource::APBClk,
-        )
-        .expect("failed to configure timer");
+    let mut servo_cfgs: [ServoConfig; JOINTS] = core::array::from_fn(|_| servo_cfg.clone());
+    let defaults = Settings::new(&gamepad_config, &arm_config, [servo_cfg.trim_us; JOINTS]);
 
-    let shoulder_servo = Servo::new(
-        "shoulder",
-        servo_cfg.clone(),
-        &mut ledc,
-        &timer,
-        channel::Number::Channel0,
-        peripherals.GPIO5,
-    )
-    .expect("shoulder init failed");
-
-    let elbow_servo = Servo::new(
-        "elbow",
-        servo_cfg.clone(),
-        &mut ledc,
-        &timer,
-        channel::Number::Channel0,
-        peripherals.GPIO6,
-    )
-    .expect("elbow init failed");
-
-    let gripper_servo = Servo::new(
-        "gripper",
-        servo_cfg,
-        &mut ledc,
-        &timer,
-        channel::Number::Channel0,
-        peripherals.GPIO7,
-    )
-    .expect("gripper init failed");
+    let mut store = FlashStore::new(peripherals.FLASH);
+    match store.load_settings() {
+        Ok(settings) => {
+            log::info!("using stored settings");
+            settings.apply(&mut gamepad_config, &mut arm_config);
+            for (servo, trim_us) in servo_cfgs.iter_mut().zip(settings.trims_us) {
+                servo.trim_us = trim_us;
+            }
+        }
+        Err(err) => log::warn!("using default settings: {err}"),
+    }
+    let trims_us = core::array::from_fn(|idx| servo_cfgs[idx].trim_us);
+    let mut settings = Settings::new(&gamepad_config, &arm_config, trims_us);
+    let safe_mode_reason = match settings.validate() {
+        _ if button_pressed => Some(safe_mode::Reason::ButtonPressed),
+        Ok(()) => None,
+        Err(err) => Some(safe_mode::Reason::InvalidConfig(err)),
+    };
+    if safe_mode_reason.is_some() {
+        // the stored calibration may be what's broken
+        defaults.apply(&mut gamepad_config, &mut arm_config);
+    }
+
+    // commands share UART0 with the logs, the pins are the console ones
+    let uart = Uart::new(peripherals.UART0, uart::Config::default())
+        .expect("console UART init failed")
+        .with_rx(peripherals.GPIO20)
+        .with_tx(peripherals.GPIO21);
+    let mut console = Console::new(uart);
+
+    // the I2C parts share the bus, each through its own device, at 400 kHz a line of the
+    // OLED takes about 3 ms
+    #[cfg(feature = "i2c")]
+    let i2c_bus = RefCell::new(
+        I2c::new(
+            peripherals.I2C0,
+            i2c::master::Config::default().with_frequency(Rate::from_khz(400)),
+        )
+        .expect("I2C init failed")
+        .with_sda(peripherals.GPIO18)
+        .with_scl(peripherals.GPIO19),
+    );
 
+    #[cfg(not(feature = "i2c-gamepad"))]
     let gamepad: GamepadImpl<ADC1, GPIO0, GPIO1, GPIO2, GPIO3> = GamepadImpl::new(
-        GamepadConfig {
-            center_offset: 100,
-            ..GamepadConfig::default()
-        },
+        gamepad_config,
         peripherals.ADC1,
         peripherals.GPIO0,
         peripherals.GPIO1,
         peripherals.GPIO2,
         peripherals.GPIO3,
     )
+    .expect("gamepad init failed")
+    .with_button(
+        Button::Stick1,
+        Input::new(
+            peripherals.GPIO4,
+            InputConfig::default().with_pull(Pull::Up),
+        ),
+    )
+    .with_button(
+        Button::Stick2,
+        Input::new(
+            peripherals.GPIO10,
+            InputConfig::default().with_pull(Pull::Up),
+        ),
+    );
+    #[cfg(all(feature = "i2c-gamepad", not(feature = "nunchuk")))]
+    let gamepad = i2c_gamepad::Ads1115Gamepad::new(
+        gamepad_config,
+        RefCellDevice::new(&i2c_bus),
+        i2c_gamepad::ADS1115_ADDRESS,
+    )
+    .expect("gamepad init failed")
+    .with_button(
+        Button::Stick1,
+        Input::new(
+            peripherals.GPIO4,
+            InputConfig::default().with_pull(Pull::Up),
+        ),
+    )
+    .with_button(
+        Button::Stick2,
+        Input::new(
+            peripherals.GPIO10,
+            InputConfig::default().with_pull(Pull::Up),
+        ),
+    );
+    #[cfg(feature = "nunchuk")]
+    let gamepad = nunchuk::NunchukGamepad::new(
+        gamepad_config,
+        nunchuk::NunchukMapping::default(),
+        RefCellDevice::new(&i2c_bus),
+    )
     .expect("gamepad init failed");
 
-    let mut bot = ArmBot::new(
-        ArmBotConfig::default(),
-        gamepad,
-        shoulder_servo,
-        elbow_servo,
-        gripper_servo,
-    )
-    .expect("ArmBot init failed");
+    if let Some(reason) = safe_mode_reason {
+        safe_mode::run(reason, console, gamepad, store, defaults);
+    }
+
+    // trims don't change the timer, servos share it
+    let ledc = Ledc::new(peripherals.LEDC);
+    let timer = servo_cfg
+        .configure_timer::<LowSpeed>(&ledc, timer::Number::Timer0, timer::LSClockSource::APBClk)
+        .expect("failed to configure timer");
+
+    // every servo needs its own channel, they share the timer
+    let servo_pins = [
+        peripherals.GPIO5.degrade(),
+        peripherals.GPIO6.degrade(),
+        peripherals.GPIO7.degrade(),
+    ];
+    let mut servo_cfgs = servo_cfgs.into_iter();
+    let mut servo_pins = servo_pins.into_iter();
+    let servos: [Servo<'_, LowSpeed>; JOINTS] = core::array::from_fn(|idx| {
+        let name = arm_config.joints[idx].name;
+        let config = servo_cfgs.next().expect("a config for every joint");
+        let pin = servo_pins.next().expect("a pin for every joint");
+        Servo::new(name, config, &ledc, &timer, SERVO_CHANNELS[idx], pin)
+            .unwrap_or_else(|err| panic!("{name} servo init failed: {err:?}"))
+    });
+
+    // the buzzer has its own timer, the pitch is far from the servo frequency
+    #[cfg(feature = "buzzer")]
+    let buzzer_timer = {
+        let mut timer = ledc.timer::<LowSpeed>(timer::Number::Timer1);
+        timer
+            .configure(timer::config::Config {
+                duty: Duty::Duty10Bit,
+                clock_source: timer::LSClockSource::APBClk,
+                frequency: BUZZER_TONE,
+            })
+            .expect("failed to configure the buzzer timer");
+        timer
+    };
+    #[cfg(feature = "buzzer")]
+    let mut buzzer = {
+        let mut channel = ledc.channel(BUZZER_CHANNEL, peripherals.GPIO3);
+        channel
+            .configure(channel::config::Config {
+                timer: &buzzer_timer,
+                duty_pct: 0,
+                drive_mode: DriveMode::PushPull,
+            })
+            .expect("buzzer init failed");
+        Buzzer::new(LedcTone::new(channel)).expect("buzzer init failed")
+    };
+
+    let bot = ArmBot::new(arm_config, gamepad, servos).expect("ArmBot init failed");
+    #[cfg(feature = "stepper-base")]
+    let bot = {
+        let step = Output::new(peripherals.GPIO2, Level::Low, OutputConfig::default());
+        let dir = Output::new(peripherals.GPIO3, Level::Low, OutputConfig::default());
+        let stepper = Stepper::new(StepperConfig::drv8825_28byj48(), A4988::new(step, dir))
+            .expect("stepper init failed");
+        bot.with_base(stepper)
+    };
+    // the timer and the channel of the motor live as long as the arm
+    #[cfg(feature = "encoder-base")]
+    let motor_timer = {
+        let mut timer = ledc.timer::<LowSpeed>(timer::Number::Timer2);
+        timer
+            .configure(timer::config::Config {
+                duty: MOTOR_DUTY,
+                clock_source: timer::LSClockSource::APBClk,
+                frequency: MOTOR_PWM,
+            })
+            .expect("failed to configure the base motor timer");
+        timer
+    };
+    #[cfg(feature = "encoder-base")]
+    let bot = {
+        let mut pwm = ledc.channel(MOTOR_CHANNEL, peripherals.GPIO1);
+        pwm.configure(channel::config::Config {
+            timer: &motor_timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })
+        .expect("base motor init failed");
+        let dir = Output::new(peripherals.GPIO2, Level::Low, OutputConfig::default());
+        let motor = HBridge::new(pwm, dir, MOTOR_DUTY as u32);
+
+        let mut io = Io::new(peripherals.IO_MUX);
+        let channel = |pin| Input::new(pin, InputConfig::default().with_pull(Pull::Up));
+        let encoder = encoder::start_decoder(
+            &mut io,
+            channel(peripherals.GPIO0.degrade()),
+            channel(peripherals.GPIO3.degrade()),
+        );
+        let base = EncoderBase::new(EncoderBaseConfig::n20_geared(), encoder, motor)
+            .expect("encoder base init failed");
+        bot.with_base(base)
+    };
+    let mut bot = bot;
+    match store.load_script() {
+        Ok(Some(script)) => {
+            log::info!("script loaded, {} steps", script.steps().len());
+            bot.load_script(script);
+        }
+        Ok(None) => log::info!("no script stored"),
+        Err(err) => log::warn!("script not loaded: {err}"),
+    }
+
+    #[cfg(feature = "battery")]
+    let mut battery_adc = BatteryAdc::new(peripherals.ADC1, peripherals.GPIO0);
+    #[cfg(feature = "battery")]
+    let mut battery = BatteryMonitor::new(PowerConfig::default()).expect("invalid battery config");
+
+    // the gripper rail, stalls there crush what's gripped or burn the servo
+    #[cfg(feature = "current-sense")]
+    let mut gripper_rail = {
+        let sensor = Ina219::new(
+            RefCellDevice::new(&i2c_bus),
+            INA219_ADDRESS,
+            INA219_SHUNT_MILLIOHMS,
+        )
+        .expect("INA219 init failed");
+        RailMonitor::new(GRIPPER_JOINT, sensor, StallConfig::default())
+    };
+    // reads the same INA219 through its own device, for the GRIP command
+    #[cfg(feature = "current-sense")]
+    let mut gripper = {
+        let sensor = Ina219::new(
+            RefCellDevice::new(&i2c_bus),
+            INA219_ADDRESS,
+            INA219_SHUNT_MILLIOHMS,
+        )
+        .expect("INA219 init failed");
+        let config = GripConfig {
+            joint: GRIPPER_JOINT,
+            ..GripConfig::default()
+        };
+        Gripper::new(config, sensor)
+    };
+
+    // pitch of the forearm for the level hold, held only if the config has a wrist
+    #[cfg(feature = "imu")]
+    let mut forearm_imu =
+        I2cImu::new(RefCellDevice::new(&i2c_bus), IMU_ADDRESS).expect("IMU init failed");
+    #[cfg(feature = "imu")]
+    let mut pitch_filter = PitchFilter::new(PITCH_ALPHA);
+    #[cfg(feature = "imu")]
+    if bot.config().level_hold.is_some() {
+        bot.set_level_hold(true).expect("level hold init failed");
+    }
+
+    #[cfg(feature = "display")]
+    let mut oled = Oled::new(RefCellDevice::new(&i2c_bus), OLED_ADDRESS, OLED_CONTROLLER)
+        .expect("OLED init failed");
+
+    #[cfg(feature = "status-led")]
+    let mut status_led = {
+        let rmt = Rmt::new(peripherals.RMT, Rate::from_mhz(80)).expect("RMT init failed");
+        let config = TxChannelConfig::default()
+            .with_clk_divider(1)
+            .with_idle_output(true);
+        let channel = rmt
+            .channel0
+            .configure_tx(&config)
+            .expect("status LED init failed")
+            .with_pin(peripherals.GPIO1);
+        StatusLed::new(Ws2812::new(channel), StatusLedConfig::default())
+    };
+
+    #[cfg(feature = "demo")]
+    let mut demo = Demo::new(DemoConfig::default(), SystemClock.now());
+
+    #[cfg(feature = "end-stops")]
+    let mut end_stops = {
+        let switch = |pin| {
+            let input = Input::new(pin, InputConfig::default().with_pull(Pull::Up));
+            LimitSwitch::new(input, EndStopConfig::default())
+        };
+        [
+            JointEndStops::new(END_STOP_JOINTS[0]).with_min(switch(peripherals.GPIO18.degrade())),
+            JointEndStops::new(END_STOP_JOINTS[1]).with_min(switch(peripherals.GPIO19.degrade())),
+        ]
+    };
+    // index into the end stops of the joint being homed, started by the HOME command
+    #[cfg(feature = "end-stops")]
+    let mut homing: Option<(usize, Homing)> = None;
+
+    let stop_switch = StopSwitch::new(Input::new(
+        peripherals.GPIO8,
+        InputConfig::default().with_pull(Pull::Up),
+    ));
 
     log::info!("Arm bot initialized");
 
-    let delay = Delay::new();
+    let timg0 = TimerGroup::new(peripherals.TIMG0);
+    let mut ticker = Ticker::start(timg0.timer0, CONTROL_PERIOD).expect("ticker init failed");
+    #[cfg(not(feature = "display"))]
+    let mut scheduler = Scheduler::new(CONTROL_PERIOD, [CONTROL_PERIOD, REPORT_PERIOD])
+        .expect("invalid task periods");
+    #[cfg(feature = "display")]
+    let mut scheduler = Scheduler::new(
+        CONTROL_PERIOD,
+        [CONTROL_PERIOD, REPORT_PERIOD, DISPLAY_PERIOD],
+    )
+    .expect("invalid task periods");
+    let ticks = scheduler.period(REPORT_TASK);
+    let mut watchdog = Watchdog::start(timg0.wdt, WatchdogConfig::default());
+
+    log::info!("control loop at {} Hz", CONTROL_RATE.as_hz());
+
+    let mut missed = 0;
+    let mut failed = 0;
+    let mut last_error = None;
+    let mut loop_stats = LoopStats::default();
+    let mut telemetry = LogSink;
+    // last error reported, stays on the OLED until the next one
+    #[cfg(feature = "display")]
+    let mut shown_error = None;
+    // an updated image is kept once the loop ran on it for a while, see ota.rs
+    #[cfg(feature = "ota")]
+    let mut image_confirmed = false;
+    // firmware image coming in over the console
+    #[cfg(feature = "ota")]
+    let mut ota_update: Option<ota::OtaUpdate> = None;
     loop {
-        if let Err(e) = bot.do_step() {
-            log::error!("step failed: {:?}", e);
+        missed += ticker.wait();
+        let due = scheduler.tick();
+
+        if due.contains(CONTROL_TASK) {
+            let started = SystemClock.now();
+            bot.update_stop_switch(stop_switch.is_tripped());
+            #[cfg(feature = "end-stops")]
+            for stops in &mut end_stops {
+                if let Err(err) = stops.poll(&mut bot, started) {
+                    last_error = Some(Report::from(err));
+                }
+            }
+            #[cfg(feature = "demo")]
+            if let Err(err) = demo.poll(&mut bot, started) {
+                last_error = Some(err);
+            }
+            #[cfg(feature = "end-stops")]
+            if let Some((idx, run)) = &mut homing {
+                let joint = END_STOP_JOINTS[*idx];
+                match run.poll(&end_stops[*idx], &mut bot) {
+                    Ok(None) => {}
+                    Ok(Some(offset)) => {
+                        log::info!("end stop of joint {} is {offset} off", joint + 1);
+                        homing = None;
+                    }
+                    Err(err) => {
+                        last_error = Some(err);
+                        homing = None;
+                    }
+                }
+            }
+            #[cfg(feature = "imu")]
+            match forearm_imu.read() {
+                Ok(sample) => {
+                    let dt = CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0;
+                    bot.set_pitch(Some(pitch_filter.update(&sample, dt)));
+                }
+                Err(err) => {
+                    bot.set_pitch(None);
+                    pitch_filter.reset();
+                    last_error = Some(Report::from(err));
+                }
+            }
+            watchdog.step_started();
+            let result = bot.do_step();
+            watchdog.step_done();
+            #[cfg(feature = "current-sense")]
+            if let Err(err) = gripper_rail.poll(&mut bot, started) {
+                last_error = Some(err);
+            }
+            #[cfg(feature = "current-sense")]
+            if let Err(err) = gripper.poll(&mut bot) {
+                last_error = Some(err);
+            }
+            #[cfg(feature = "battery")]
+            match battery_adc.read_mv() {
+                Ok(pin_mv) => {
+                    if let Some(action) = battery.update(pin_mv) {
+                        let volts = battery.status().volts;
+                        if let Err(err) = power::apply(action, volts, &mut bot) {
+                            last_error = Some(err);
+                        }
+                    }
+                }
+                Err(err) => log::warn!("battery read failed: {err}"),
+            }
+            #[cfg(feature = "status-led")]
+            if let Err(err) = status_led.update(LedState::of(&bot, result.is_err()), started) {
+                log::warn!("status LED failed: {err}");
+            }
+            #[cfg(feature = "buzzer")]
+            if let Err(err) = buzzer.watch(&bot, started) {
+                log::warn!("buzzer failed: {err}");
+            }
+            if let Err(e) = result {
+                failed += 1;
+                last_error = Some(e);
+            }
+            console.poll(|cmd| match cmd {
+                Command::Calibrate => {
+                    #[cfg(feature = "status-led")]
+                    let clock = LedClock::new(&mut status_led, LedState::Calibrating);
+                    #[cfg(not(feature = "status-led"))]
+                    let clock = SystemClock;
+                    watchdog.pause();
+                    let result =
+                        calibrate_gamepad(bot.gamepad_mut(), &clock, &mut settings, &mut store);
+                    watchdog.resume();
+                    result
+                        .map(|()| Reply::Done)
+                        .map_err(|err| CommandError::from(Report::from(err)))
+                }
+                Command::ResetSettings => {
+                    let result = store.save_settings(&defaults);
+                    result
+                        .map(|()| Reply::Done)
+                        .map_err(|err| CommandError::from(Report::from(err)))
+                }
+                #[cfg(feature = "current-sense")]
+                Command::Grip(_) if bot.is_stopped() => Err(ErrorCode::Stopped.into()),
+                #[cfg(feature = "current-sense")]
+                Command::Grip(limit) => {
+                    match limit {
+                        Some(force_limit_ma) => gripper.grip(force_limit_ma),
+                        None => gripper.release(),
+                    }
+                    Ok(Reply::Done)
+                }
+                #[cfg(feature = "end-stops")]
+                Command::Home(_) if bot.is_stopped() => Err(ErrorCode::Stopped.into()),
+                #[cfg(feature = "end-stops")]
+                Command::Home(joint) => {
+                    let idx = END_STOP_JOINTS
+                        .iter()
+                        .position(|&stop_joint| stop_joint == joint)
+                        .ok_or(Report::from(Error::Other("joint has no end stop")))?;
+                    let min = bot.config().joints[joint].angle_range.start;
+                    let expected = units::Degrees::new(min.get() + END_STOP_MARGIN);
+                    homing = Some((idx, Homing::new(joint, expected, HOMING_STEP)));
+                    Ok(Reply::Done)
+                }
+                // a flash sector takes longer than a control step to write, so the arm is stopped
+                #[cfg(feature = "ota")]
+                Command::OtaBegin { .. } | Command::OtaChunk { .. } | Command::OtaEnd
+                    if !bot.is_stopped() =>
+                {
+                    Err(Report::from(Error::Other("stop the arm before a firmware update")).into())
+                }
+                #[cfg(feature = "ota")]
+                Command::OtaBegin { size, crc } => {
+                    ota_update = Some(ota::OtaUpdate::begin(size, crc).map_err(Report::from)?);
+                    Ok(Reply::Done)
+                }
+                #[cfg(feature = "ota")]
+                Command::OtaChunk { offset, data } => {
+                    let update = ota_update
+                        .as_mut()
+                        .ok_or(Report::from(Error::Other("no firmware update started")))?;
+                    let mut buf = [0; MAX_CHUNK_LEN];
+                    let chunk = decode_hex(data, &mut buf)?;
+                    watchdog.pause();
+                    let result = update.write(store.flash(), offset, chunk);
+                    watchdog.resume();
+                    result.map_err(Report::from)?;
+                    Ok(Reply::Done)
+                }
+                #[cfg(feature = "ota")]
+                Command::OtaEnd => {
+                    let update = ota_update
+                        .take()
+                        .ok_or(Report::from(Error::Other("no firmware update started")))?;
+                    watchdog.pause();
+                    let result = update.finish(store.flash());
+                    watchdog.resume();
+                    result.map_err(Report::from)?;
+                    Ok(Reply::Done)
+                }
+                #[cfg(feature = "ota")]
+                Command::OtaReceived => Ok(Reply::Received(
+                    ota_update.as_ref().map_or(0, ota::OtaUpdate::received),
+                )),
+                cmd => cmd.execute(&mut bot),
+            });
+            loop_stats.record(SystemClock.now().duration_since(started), CONTROL_PERIOD);
+        }
+
+        #[cfg(feature = "display")]
+        if due.contains(DISPLAY_TASK) {
+            let result = oled.show_status(
+                &bot,
+                #[cfg(feature = "battery")]
+                &battery.status(),
+                shown_error.as_ref(),
+            );
+            if let Err(err) = result {
+                log::warn!("OLED failed: {err}");
+            }
+        }
+
+        // report after the step, so logging doesn't shift the control period
+        if due.contains(REPORT_TASK) {
+            let stats = loop_stats.take();
+            if missed > 0 || stats.overruns > 0 {
+                log::warn!(
+                    "last {ticks} ticks: missed={missed} overruns={}, longest step {:?}",
+                    stats.overruns,
+                    stats.max
+                );
+            }
+            let timing = LoopTiming::new(&stats, missed);
+            let snapshot = Snapshot::capture(&bot, SystemClock.now(), timing);
+            #[cfg(feature = "battery")]
+            let snapshot = snapshot.with_battery(battery.status());
+            crash_log::record_counters(&snapshot.counters);
+            if let Err(err) = telemetry.send(&snapshot) {
+                log::warn!("telemetry failed: {err}");
+            }
+            if bot.has_faults() {
+                log::warn!("arm is running with faulted joints");
+            }
+            #[cfg(feature = "ota")]
+            if !image_confirmed && SystemClock.now().as_micros() >= ota::TRIAL.as_micros() as u64 {
+                image_confirmed = true;
+                if let Err(err) = ota::confirm(store.flash()) {
+                    log::warn!("firmware image not confirmed: {err}");
+                }
+            }
+            if let Some(e) = last_error.take() {
+                crash_log::record_error(&e);
+                log::error!("last {ticks} ticks: failed steps={failed}, last error: {e}");
+                #[cfg(feature = "display")]
+                {
+                    shown_error = Some(e);
+                }
+            }
+            missed = 0;
+            failed = 0;
+
+            if safe_mode_button.is_low() {
+                #[cfg(feature = "status-led")]
+                let clock = LedClock::new(&mut status_led, LedState::Calibrating);
+                #[cfg(not(feature = "status-led"))]
+                let clock = SystemClock;
+                watchdog.pause();
+                let result =
+                    calibrate_gamepad(bot.gamepad_mut(), &clock, &mut settings, &mut store);
+                watchdog.resume();
+                if let Err(err) = result {
+                    log::error!("gamepad calibration failed: {err}");
+                }
+            }
         }
-        delay.delay_millis(10); // todo remove
     }
 }
+
+/// Calibrates the sticks and stores the calibration in the settings.
+fn calibrate_gamepad(
+    gamepad: &mut impl Gamepad,
+    clock: &impl Clock,
+    settings: &mut Settings,
+    store: &mut FlashStore<'_>,
+) -> Result<(), Error> {
+    settings.axes = gamepad.calibrate(clock, CALIBRATION_HOLD)?;
+    store.save_settings(settings)
+}
@@ -0,0 +1,213 @@
+//! Wii Nunchuk over I2C: a 2-axis stick, C and Z buttons and an accelerometer,
+//! enough to drive the arm with one hand.
+
+use core::{ops::Range, time::Duration};
+
+use embedded_hal::i2c::I2c;
+use esp_hal::delay::Delay;
+use log::{info, trace};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Error,
+    gamepad::{
+        Axis, AxisConfig, AxisReader, Button, Gamepad, GamepadConfig, Position, RawState, State,
+        AXES, BUTTONS, CALIBRATION_SAMPLE_PERIOD_MS,
+    },
+    util::debounce::Debouncer,
+};
+
+/// Fixed address of the Nunchuk.
+const ADDRESS: u8 = 0x52;
+/// Time the Nunchuk needs to prepare a report after it was requested.
+const REPORT_DELAY_US: u32 = 200;
+
+/// Analog channel of the Nunchuk, its value is an index in the raw state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    StickX = 0,
+    StickY = 1,
+    /// Tilt left and right.
+    AccelX = 2,
+    /// Tilt forward and backward.
+    AccelY = 3,
+}
+
+/// Which axes the channels drive, indexed by [`Channel`].
+#[derive(Debug, Clone)]
+pub struct NunchukMapping {
+    /// Channels while C isn't held.
+    pub normal: [Option<Axis>; AXES],
+    /// Channels while C is held, C switches between two pairs of joints.
+    pub shifted: [Option<Axis>; AXES],
+    /// Button reported while Z is held.
+    pub z: Option<Button>,
+}
+
+impl Default for NunchukMapping {
+    fn default() -> Self {
+        Self {
+            normal: [Some(Axis::BaseRotator), Some(Axis::Shoulder), None, None],
+            shifted: [Some(Axis::Gripper), Some(Axis::Elbow), None, None],
+            z: Some(Button::Stick1),
+        }
+    }
+}
+
+/// Ranges of the Nunchuk channels, axes of the config are indexed by [`Channel`].
+pub fn default_config() -> GamepadConfig {
+    let stick = AxisConfig {
+        min_value: 30,
+        max_value: 225,
+        center_offset: 8,
+        ..AxisConfig::default()
+    };
+    // ±1 g is about ±200 around the middle of 10 bits
+    let accel = AxisConfig {
+        min_value: 312,
+        max_value: 712,
+        center_offset: 40,
+        ..AxisConfig::default()
+    };
+    GamepadConfig {
+        axes: [stick, stick, accel, accel],
+        ..GamepadConfig::default()
+    }
+}
+
+/// Report of the Nunchuk.
+struct Nunchuk<I> {
+    i2c: I,
+    delay: Delay,
+    /// C and Z of the last report, true while pressed.
+    c: bool,
+    z: bool,
+}
+
+impl<I: I2c> Nunchuk<I> {
+    /// Initializes without encryption, works with clones too.
+    fn init(i2c: I) -> Result<Self, Error> {
+        let mut nunchuk = Self {
+            i2c,
+            delay: Delay::new(),
+            c: false,
+            z: false,
+        };
+        nunchuk.write(&[0xF0, 0x55])?;
+        nunchuk.delay.delay_millis(1);
+        nunchuk.write(&[0xFB, 0x00])?;
+        nunchuk.delay.delay_millis(1);
+        Ok(nunchuk)
+    }
+
+    /// Reads a report, returns the channels and keeps the buttons.
+    fn sample(&mut self) -> Result<[u32; AXES], Error> {
+        self.write(&[0x00])?;
+        self.delay.delay_micros(REPORT_DELAY_US);
+        let mut report = [0; 6];
+        self.i2c
+            .read(ADDRESS, &mut report)
+            .map_err(|_| Error::I2c)?;
+
+        // buttons are low while pressed, low bits of the accelerometer share the last byte
+        let rest = report[5];
+        self.z = rest & 0x01 == 0;
+        self.c = rest & 0x02 == 0;
+        let accel_x = ((report[2] as u32) << 2) | ((rest as u32 >> 2) & 0x03);
+        let accel_y = ((report[3] as u32) << 2) | ((rest as u32 >> 4) & 0x03);
+        let mut channels = [0; AXES];
+        channels[Channel::StickX as usize] = report[0] as u32;
+        channels[Channel::StickY as usize] = report[1] as u32;
+        channels[Channel::AccelX as usize] = accel_x;
+        channels[Channel::AccelY as usize] = accel_y;
+        Ok(channels)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.i2c.write(ADDRESS, bytes).map_err(|_| Error::I2c)
+    }
+}
+
+/// Gamepad of a Nunchuk, channels are mapped to the axes by [`NunchukMapping`].
+///
+/// Raw states are indexed by [`Channel`], states by [`Axis`].
+pub struct NunchukGamepad<I> {
+    nunchuk: Nunchuk<I>,
+    channels: AxisReader,
+    mapping: NunchukMapping,
+    c: Debouncer,
+    z: Debouncer,
+}
+
+impl<I: I2c> NunchukGamepad<I> {
+    /// Axes of the config are indexed by [`Channel`], see [`default_config`].
+    pub fn new(config: GamepadConfig, mapping: NunchukMapping, i2c: I) -> Result<Self, Error> {
+        let mut nunchuk = Nunchuk::init(i2c)?;
+        let mut channels = AxisReader::new(config)?;
+        channels.init_centers(|| nunchuk.sample())?;
+        let debounce = channels.config().button_debounce;
+        info!("nunchuk initialized");
+        Ok(Self {
+            nunchuk,
+            channels,
+            mapping,
+            c: Debouncer::new(debounce, false),
+            z: Debouncer::new(debounce, false),
+        })
+    }
+}
+
+impl<I: I2c> Gamepad for NunchukGamepad<I> {
+    fn read_raw_state(&mut self) -> Result<RawState, Error> {
+        let axes = self.channels.read(|| self.nunchuk.sample())?;
+        let now = SystemClock.now();
+        self.c.update(self.nunchuk.c, now);
+        self.z.update(self.nunchuk.z, now);
+
+        let mut buttons = [false; BUTTONS];
+        if let Some(button) = self.mapping.z {
+            buttons[button as usize] = self.z.is_high();
+        }
+        let state = RawState { axes, buttons };
+        trace!("raw state = {:?}", state);
+        Ok(state)
+    }
+
+    fn read_state(&mut self, output: &Range<u32>) -> Result<State, Error> {
+        let raw = self.read_raw_state()?;
+        let channels = self.channels.state(&raw, output)?;
+        let mapping = if self.c.is_high() {
+            &self.mapping.shifted
+        } else {
+            &self.mapping.normal
+        };
+
+        // axes without a channel stay centered
+        let mut state = State {
+            buttons: raw.buttons,
+            ..State::default()
+        };
+        for (position, axis) in channels.axes.into_iter().zip(mapping) {
+            if let Some(axis) = axis {
+                if position != Position::Center {
+                    state.axes[*axis as usize] = position;
+                }
+            }
+        }
+        trace!("state = {:?}", state);
+        Ok(state)
+    }
+
+    fn calibrate(
+        &mut self,
+        clock: &impl Clock,
+        hold: Duration,
+    ) -> Result<[AxisConfig; AXES], Error> {
+        self.channels.calibrate(clock, hold, || {
+            self.nunchuk
+                .delay
+                .delay_millis(CALIBRATION_SAMPLE_PERIOD_MS);
+            self.nunchuk.sample()
+        })
+    }
+}
@@ -0,0 +1,166 @@
+//! Firmware updates into the OTA app slots, with rollback of an image that doesn't come up.
+//!
+//! The image arrives in chunks over the console, see the `OTA` commands of
+//! [`armbot_control::protocol`], and is written to the slot that isn't running. After a reset the
+//! bootloader boots it on trial, [`confirm`] marks it valid once the control loop ran for
+//! [`TRIAL`]. An image that panics or hangs before is rolled back to the previous one at the next
+//! reset.
+//!
+//! Needs the OTA slots of `partitions.csv` flashed once over USB, and a bootloader built with
+//! `CONFIG_BOOTLOADER_APP_ROLLBACK_ENABLE`, without it a bad image keeps booting.
+
+use core::time::Duration;
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::{
+    ota::OtaImageState,
+    ota_updater::OtaUpdater,
+    partitions::{self, PARTITION_TABLE_MAX_LEN},
+};
+use log::info;
+
+use crate::error::Error;
+
+/// How long the control loop runs on a new image before it's kept.
+pub const TRIAL: Duration = Duration::from_secs(30);
+
+/// First byte of an ESP app image.
+const IMAGE_MAGIC: u8 = 0xe9;
+/// Flash is written a sector at a time, so it's erased once per sector.
+const SECTOR: usize = 4096;
+
+/// Image being received.
+pub struct OtaUpdate {
+    size: u32,
+    /// CRC-32 the image should have.
+    crc: u32,
+    /// CRC-32 of the received bytes, before the final inversion.
+    received_crc: u32,
+    received: u32,
+    /// Received bytes of the sector being filled.
+    sector: [u8; SECTOR],
+}
+
+impl OtaUpdate {
+    /// Starts receiving an image of `size` bytes with the CRC-32 (IEEE) of all its bytes.
+    pub fn begin(size: u32, crc: u32) -> Result<Self, Error> {
+        if size == 0 {
+            return Err(Error::OutOfRange("firmware image is empty"));
+        }
+        info!("receiving firmware image, {size} bytes");
+        Ok(Self {
+            size,
+            crc,
+            received_crc: !0,
+            received: 0,
+            sector: [0xff; SECTOR],
+        })
+    }
+
+    /// Bytes received so far, the offset of the next chunk.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Takes the chunk at the offset. Chunks must come in order, a chunk sent again is skipped.
+    pub fn write<F: Storage>(
+        &mut self,
+        flash: &mut F,
+        offset: u32,
+        chunk: &[u8],
+    ) -> Result<(), Error> {
+        let end = offset.saturating_add(chunk.len() as u32);
+        if end <= self.received && offset < self.received {
+            return Ok(());
+        }
+        if offset != self.received {
+            return Err(Error::Other("firmware chunk out of order"));
+        }
+        if end > self.size {
+            return Err(Error::OutOfRange("firmware image is longer than announced"));
+        }
+        if offset == 0 && chunk.first() != Some(&IMAGE_MAGIC) {
+            return Err(Error::Other("not a firmware image"));
+        }
+
+        let mut rest = chunk;
+        while !rest.is_empty() {
+            let pos = self.received as usize % SECTOR;
+            let len = rest.len().min(SECTOR - pos);
+            self.sector[pos..pos + len].copy_from_slice(&rest[..len]);
+            self.received_crc = crc32_update(self.received_crc, &rest[..len]);
+            self.received += len as u32;
+            rest = &rest[len..];
+            if pos + len == SECTOR {
+                self.flush(flash)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the whole image and selects it to boot on trial after a reset.
+    pub fn finish<F: Storage>(mut self, flash: &mut F) -> Result<(), Error> {
+        if self.received != self.size {
+            return Err(Error::Other("firmware image is incomplete"));
+        }
+        if !self.received_crc != self.crc {
+            return Err(Error::Other("firmware image checksum mismatch"));
+        }
+        if !(self.received as usize).is_multiple_of(SECTOR) {
+            self.flush(flash)?;
+        }
+        let mut buf = [0; PARTITION_TABLE_MAX_LEN];
+        let mut ota = OtaUpdater::new(flash, &mut buf).map_err(ota_error)?;
+        ota.activate_next_partition().map_err(ota_error)?;
+        ota.set_current_ota_state(OtaImageState::New)
+            .map_err(ota_error)?;
+        info!("firmware image written, it boots on trial after a reset");
+        Ok(())
+    }
+
+    /// Writes the sector being filled to the slot that isn't running.
+    fn flush<F: Storage>(&mut self, flash: &mut F) -> Result<(), Error> {
+        let filled = match self.received as usize % SECTOR {
+            0 => SECTOR,
+            filled => filled,
+        };
+        let start = self.received - filled as u32;
+        let mut buf = [0; PARTITION_TABLE_MAX_LEN];
+        let mut ota = OtaUpdater::new(flash, &mut buf).map_err(ota_error)?;
+        let (mut slot, _) = ota.next_partition().map_err(ota_error)?;
+        if self.size as usize > slot.capacity() {
+            return Err(Error::OutOfRange("firmware image is larger than its slot"));
+        }
+        slot.write(start, &self.sector[..filled])
+            .map_err(|_| Error::Storage)?;
+        self.sector.fill(0xff);
+        Ok(())
+    }
+}
+
+/// Keeps the running image if it was booted on trial, so it isn't rolled back.
+pub fn confirm<F: Storage>(flash: &mut F) -> Result<(), Error> {
+    let mut buf = [0; PARTITION_TABLE_MAX_LEN];
+    let mut ota = OtaUpdater::new(flash, &mut buf).map_err(ota_error)?;
+    if let Ok(OtaImageState::New | OtaImageState::PendingVerify) = ota.current_ota_state() {
+        ota.set_current_ota_state(OtaImageState::Valid)
+            .map_err(ota_error)?;
+        info!("firmware image confirmed");
+    }
+    Ok(())
+}
+
+fn ota_error(_: partitions::Error) -> Error {
+    Error::Other("OTA slots can't be accessed")
+}
+
+/// CRC-32 (IEEE) of the data, continued from `crc`, bit by bit to keep the table out of flash.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
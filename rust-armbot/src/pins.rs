@@ -0,0 +1,103 @@
+use log::{error, warn};
+
+use crate::error::Error;
+
+/// What the firmware uses a pin for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinRole {
+    /// Analog input, read by ADC1.
+    Adc,
+    /// PWM output driven by LEDC.
+    Pwm,
+    /// Digital input.
+    Input,
+    /// Digital output, driven by the GPIO or a peripheral like RMT.
+    #[cfg(any(
+        feature = "status-led",
+        feature = "stepper-base",
+        feature = "encoder-base"
+    ))]
+    Output,
+    /// SDA or SCL of the I2C bus.
+    #[cfg(feature = "i2c")]
+    I2c,
+}
+
+/// Pin configured in the wiring.
+#[derive(Debug, Clone, Copy)]
+pub struct PinAssignment {
+    /// What's connected to the pin, used in error messages.
+    pub name: &'static str,
+    pub gpio: u8,
+    pub role: PinRole,
+}
+
+impl PinAssignment {
+    pub const fn new(name: &'static str, gpio: u8, role: PinRole) -> Self {
+        Self { name, gpio, role }
+    }
+}
+
+/// Number of GPIOs of ESP32-C3.
+const GPIO_COUNT: u8 = 22;
+/// Pins connected to ADC1, ADC2 isn't usable on ESP32-C3.
+const ADC1_PINS: [u8; 5] = [0, 1, 2, 3, 4];
+/// Pins sampled at reset to select the boot mode.
+const STRAPPING_PINS: [u8; 3] = [2, 8, 9];
+/// Pins connected to the SPI flash.
+const FLASH_PINS: [u8; 6] = [12, 13, 14, 15, 16, 17];
+/// Pins of UART0, used for logs and commands.
+const CONSOLE_PINS: [u8; 2] = [20, 21];
+/// Pins of USB-Serial-JTAG, free when the board is flashed in the download mode.
+const USB_PINS: [u8; 2] = [18, 19];
+
+/// Checks that the pins match ESP32-C3 capabilities.
+///
+/// Every problem is logged with the name of the connected part, the first one is returned.
+/// Strapping and USB pins are only reported, since they work once the chip has booted.
+pub fn validate(pins: &[PinAssignment]) -> Result<(), Error> {
+    let mut result = Ok(());
+    for (idx, pin) in pins.iter().enumerate() {
+        if let Err(reason) = check(pin, &pins[..idx]) {
+            error!("{} can't use GPIO{}: {}", pin.name, pin.gpio, reason);
+            if result.is_ok() {
+                result = Err(Error::InvalidPin {
+                    name: pin.name,
+                    gpio: pin.gpio,
+                    reason,
+                });
+            }
+        } else if STRAPPING_PINS.contains(&pin.gpio) {
+            warn!(
+                "{} uses strapping pin GPIO{}, it must not be pulled low or high at reset",
+                pin.name, pin.gpio
+            );
+        } else if USB_PINS.contains(&pin.gpio) {
+            warn!(
+                "{} uses USB pin GPIO{}, flash the board by holding BOOT during reset",
+                pin.name, pin.gpio
+            );
+        }
+    }
+    result
+}
+
+/// Checks a single pin against the chip and against the pins assigned before it.
+fn check(pin: &PinAssignment, assigned: &[PinAssignment]) -> Result<(), &'static str> {
+    if pin.gpio >= GPIO_COUNT {
+        return Err("no such pin");
+    }
+    if FLASH_PINS.contains(&pin.gpio) {
+        return Err("pin is connected to the flash");
+    }
+    if CONSOLE_PINS.contains(&pin.gpio) {
+        return Err("pin is used by the UART console");
+    }
+    if pin.role == PinRole::Adc && !ADC1_PINS.contains(&pin.gpio) {
+        return Err("pin isn't connected to ADC1, use GPIO0-GPIO4");
+    }
+    if assigned.iter().any(|other| other.gpio == pin.gpio) {
+        return Err("pin is already assigned");
+    }
+    Ok(())
+}
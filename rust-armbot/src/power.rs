@@ -0,0 +1,246 @@
+//! Battery voltage monitoring, so the arm slows down and detaches before a brown-out resets
+//! the board mid-motion.
+//!
+//! The battery is measured through a resistor divider on GPIO0, read by [`BatteryAdc`] and fed
+//! to [`BatteryMonitor::update`]. The joysticks take all ADC1 pins, so the `battery` feature
+//! needs `i2c-gamepad`.
+
+use esp_hal::{
+    analog::adc::{Adc, AdcCalCurve, AdcConfig, AdcPin, Attenuation},
+    peripherals::{ADC1, GPIO0},
+    Blocking,
+};
+use ledc_servo::ServoDriver;
+use log::{error, info, warn};
+
+use crate::{
+    armbot::{Action, ArmBot, BaseJoint, SpeedMode},
+    error::{Error, Report},
+    gamepad::Gamepad,
+    util::{
+        filter::Ema,
+        table::{Point, Table},
+    },
+};
+
+/// Breakpoints of the charge curve.
+pub const CHARGE_POINTS: usize = 5;
+
+/// What happens when the battery drops below a threshold, from the mildest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerAction {
+    /// Only logs a warning.
+    Warn,
+    /// Switches the sticks to [`SpeedMode::Precision`], slow moves draw less current.
+    SlowDown,
+    /// Detaches the servos as the emergency stop does.
+    Detach,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Threshold {
+    pub volts: f32,
+    pub action: PowerAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerConfig {
+    /// Ratio of the battery voltage to the voltage at the ADC pin.
+    pub divider: f32,
+    /// Charge in percent by the battery voltage, a LiPo holds its voltage through the middle
+    /// of the discharge and drops fast at the end.
+    pub charge_curve: [Point; CHARGE_POINTS],
+    /// Thresholds from the highest voltage, the actions must get more severe.
+    pub thresholds: [Option<Threshold>; 3],
+    /// How much the voltage must recover above a threshold to leave it, in volts.
+    /// Servos pull the voltage down while moving, it rises back once they stop.
+    pub hysteresis: f32,
+    /// Weight of a new sample, see [`Ema`].
+    pub ema_alpha: f32,
+}
+
+impl Default for PowerConfig {
+    /// 2S LiPo through a 100k/33k divider.
+    fn default() -> Self {
+        Self {
+            divider: 133.0 / 33.0,
+            charge_curve: [
+                Point::new(6.6, 0.0),
+                Point::new(7.2, 15.0),
+                Point::new(7.5, 40.0),
+                Point::new(7.7, 60.0),
+                Point::new(8.4, 100.0),
+            ],
+            thresholds: [
+                Some(Threshold {
+                    volts: 7.0,
+                    action: PowerAction::Warn,
+                }),
+                Some(Threshold {
+                    volts: 6.8,
+                    action: PowerAction::SlowDown,
+                }),
+                Some(Threshold {
+                    volts: 6.4,
+                    action: PowerAction::Detach,
+                }),
+            ],
+            hysteresis: 0.2,
+            ema_alpha: 0.1,
+        }
+    }
+}
+
+impl PowerConfig {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.divider < 1.0 {
+            return Err(Error::OutOfRange("divider must be at least 1"));
+        }
+        Table::new_monotonic(self.charge_curve)?;
+        if self.hysteresis < 0.0 {
+            return Err(Error::OutOfRange("hysteresis must not be negative"));
+        }
+        if !(0.0..=1.0).contains(&self.ema_alpha) {
+            return Err(Error::OutOfRange("EMA alpha must be in 0..=1"));
+        }
+        let mut last: Option<Threshold> = None;
+        for threshold in self.thresholds.iter().flatten() {
+            if let Some(last) = last {
+                if threshold.volts >= last.volts || threshold.action <= last.action {
+                    return Err(Error::Other(
+                        "thresholds must go down in voltage and up in severity",
+                    ));
+                }
+            }
+            last = Some(*threshold);
+        }
+        Ok(())
+    }
+}
+
+/// Battery state for telemetry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatteryStatus {
+    pub volts: f32,
+    /// Charge read off [`PowerConfig::charge_curve`].
+    pub percent: u8,
+    /// Action of the lowest threshold the battery is below.
+    pub action: Option<PowerAction>,
+}
+
+/// Divider output on GPIO0, read by ADC1 with the curve calibration of the chip.
+pub struct BatteryAdc<'d> {
+    adc: Adc<'d, ADC1<'d>, Blocking>,
+    pin: AdcPin<GPIO0<'d>, ADC1<'d>, AdcCalCurve<ADC1<'d>>>,
+}
+
+impl<'d> BatteryAdc<'d> {
+    pub fn new(adc: ADC1<'d>, pin: GPIO0<'d>) -> Self {
+        let mut config = AdcConfig::new();
+        let pin = config.enable_pin_with_cal(pin, Attenuation::_11dB);
+        Self {
+            adc: Adc::new(adc, config),
+            pin,
+        }
+    }
+
+    /// Voltage at the pin in millivolts.
+    pub fn read_mv(&mut self) -> Result<u32, Error> {
+        nb::block!(self.adc.read_oneshot(&mut self.pin))
+            .map(u32::from)
+            .map_err(|_| Error::Adc)
+    }
+}
+
+/// Filters battery readings and tracks the thresholds they cross.
+#[derive(Debug, Clone)]
+pub struct BatteryMonitor {
+    config: PowerConfig,
+    charge: Table<CHARGE_POINTS>,
+    filter: Ema,
+    /// Index of the lowest threshold the battery is below.
+    level: Option<usize>,
+    volts: f32,
+}
+
+impl BatteryMonitor {
+    pub fn new(config: PowerConfig) -> Result<Self, Error> {
+        config.validate()?;
+        Ok(Self {
+            charge: Table::new_monotonic(config.charge_curve)?,
+            filter: Ema::new(config.ema_alpha),
+            config,
+            level: None,
+            volts: 0.0,
+        })
+    }
+
+    /// Feeds a reading of the ADC pin in millivolts.
+    /// Returns the action of a threshold the battery just dropped below.
+    pub fn update(&mut self, pin_mv: u32) -> Option<PowerAction> {
+        let pin_mv = self.filter.push(pin_mv);
+        self.volts = pin_mv as f32 / 1000.0 * self.config.divider;
+
+        let below = self
+            .config
+            .thresholds
+            .iter()
+            .rposition(|threshold| threshold.is_some_and(|t| self.volts < t.volts));
+        match (self.level, below) {
+            (Some(level), _) if below.is_none_or(|below| below < level) => {
+                // leave the level only once the voltage recovered
+                let threshold = self.config.thresholds[level].expect("level has a threshold");
+                if self.volts >= threshold.volts + self.config.hysteresis {
+                    self.level = below;
+                    info!("battery recovered to {:.2} V", self.volts);
+                }
+                None
+            }
+            (level, Some(below)) if level.is_none_or(|level| below > level) => {
+                self.level = Some(below);
+                self.config.thresholds[below].map(|t| t.action)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> BatteryStatus {
+        BatteryStatus {
+            volts: self.volts,
+            percent: (self.charge.eval(self.volts).clamp(0.0, 100.0) + 0.5) as u8,
+            action: self
+                .level
+                .and_then(|level| self.config.thresholds[level])
+                .map(|t| t.action),
+        }
+    }
+}
+
+/// Runs the action of a crossed threshold on the arm.
+/// The speed mode and the stop are left as they are when the battery recovers.
+pub fn apply<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+    action: PowerAction,
+    volts: f32,
+    bot: &mut ArmBot<G, D, N, B>,
+) -> Result<(), Report> {
+    match action {
+        PowerAction::Warn => {
+            warn!("battery low: {volts:.2} V");
+            Ok(())
+        }
+        PowerAction::SlowDown => {
+            warn!("battery low: {volts:.2} V, slowing down");
+            bot.set_speed_mode(SpeedMode::Precision);
+            Ok(())
+        }
+        PowerAction::Detach => {
+            error!("battery empty: {volts:.2} V, detaching servos");
+            bot.run_action(Action::EmergencyStop)
+        }
+    }
+}
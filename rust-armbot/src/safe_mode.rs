@@ -0,0 +1,82 @@
+use armbot_control::protocol::{Command, CommandError, ErrorCode, Reply};
+use esp_hal::{delay::Delay, gpio::Input};
+use log::{info, warn};
+
+use crate::{
+    armbot::JOINTS,
+    calibrate_gamepad,
+    clock::SystemClock,
+    console::Console,
+    error::{Error, Report},
+    gamepad::Gamepad,
+    settings::Settings,
+    storage::FlashStore,
+};
+
+/// How long the button is watched after boot, in milliseconds.
+const BUTTON_WINDOW_MS: u32 = 1500;
+
+/// Why the firmware started in the safe mode.
+#[derive(Debug)]
+pub enum Reason {
+    /// Safe mode button was pressed right after boot.
+    ButtonPressed,
+    /// Configuration failed validation.
+    InvalidConfig(Error),
+}
+
+/// Watches the button for a while after boot, returns true once it's pressed.
+///
+/// The button can't be held through reset, the BOOT button selects the ROM download mode then.
+pub fn button_pressed(button: &Input<'_>) -> bool {
+    info!("press BOOT within {BUTTON_WINDOW_MS} ms to start in safe mode");
+    let delay = Delay::new();
+    for _ in 0..BUTTON_WINDOW_MS / 10 {
+        if button.is_low() {
+            return true;
+        }
+        delay.delay_millis(10);
+    }
+    false
+}
+
+/// Runs the firmware without touching servos.
+///
+/// Servos aren't initialized, so they get no PWM signal and stay limp. The console takes only
+/// `CALIBRATE`, `DEFAULTS` and `LOG`, so a bad configuration can be fixed without reflashing.
+pub fn run(
+    reason: Reason,
+    mut console: Console<'_>,
+    mut gamepad: impl Gamepad,
+    mut store: FlashStore<'_>,
+    defaults: Settings,
+) -> ! {
+    match &reason {
+        Reason::ButtonPressed => warn!("starting in safe mode: button pressed"),
+        Reason::InvalidConfig(err) => warn!("starting in safe mode: invalid config: {err}"),
+    }
+    warn!(
+        "servos are detached, fix the configuration with CALIBRATE or DEFAULTS and reset the board"
+    );
+
+    let mut settings = defaults.clone();
+    let delay = Delay::new();
+    loop {
+        console.poll(|cmd| {
+            let result = match cmd {
+                Command::Calibrate => {
+                    calibrate_gamepad(&mut gamepad, &SystemClock, &mut settings, &mut store)
+                }
+                Command::ResetSettings => {
+                    settings = defaults.clone();
+                    store.save_settings(&settings)
+                }
+                _ => return Err(ErrorCode::Unavailable.into()),
+            };
+            result
+                .map(|()| Reply::<JOINTS>::Done)
+                .map_err(|err| CommandError::from(Report::from(err)))
+        });
+        delay.delay_millis(10);
+    }
+}
@@ -0,0 +1,71 @@
+use esp_hal::time::Duration;
+
+use crate::error::Error;
+
+/// Runs tasks at different rates from a single fixed-rate tick.
+///
+/// Every task has a period that is a multiple of the tick, [`Scheduler::tick`] returns which
+/// of them are due, so the loop body reads as a list of tasks instead of modulo counters.
+pub struct Scheduler<const N: usize> {
+    /// Period of each task, in ticks.
+    periods: [u32; N],
+    /// Ticks left until each task is due.
+    countdown: [u32; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    /// Creates the scheduler, `periods` must be non-zero multiples of `tick`.
+    /// Task index in `periods` is the index passed to [`Due::contains`].
+    pub fn new(tick: Duration, periods: [Duration; N]) -> Result<Self, Error> {
+        if N > u32::BITS as usize {
+            return Err(Error::Other("too many scheduler tasks"));
+        }
+        let tick = tick.as_micros();
+        if tick == 0 {
+            return Err(Error::Other("scheduler tick must be non zero"));
+        }
+
+        let mut ticks = [0; N];
+        for (ticks, period) in ticks.iter_mut().zip(periods) {
+            let period = period.as_micros();
+            if period == 0 || period % tick != 0 {
+                return Err(Error::Other("task period must be a multiple of the tick"));
+            }
+            *ticks = u32::try_from(period / tick)
+                .map_err(|_| Error::Other("task period is too long"))?;
+        }
+
+        Ok(Self {
+            periods: ticks,
+            countdown: ticks,
+        })
+    }
+
+    /// Advances by one tick and returns the tasks that are due.
+    pub fn tick(&mut self) -> Due {
+        let mut due = Due(0);
+        for (idx, (left, period)) in self.countdown.iter_mut().zip(self.periods).enumerate() {
+            *left -= 1;
+            if *left == 0 {
+                *left = period;
+                due.0 |= 1 << idx;
+            }
+        }
+        due
+    }
+
+    /// Returns the period of the task in ticks.
+    pub fn period(&self, task: usize) -> u32 {
+        self.periods[task]
+    }
+}
+
+/// Set of tasks that are due on a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Due(u32);
+
+impl Due {
+    pub fn contains(&self, task: usize) -> bool {
+        task < u32::BITS as usize && self.0 & (1 << task) != 0
+    }
+}
@@ -0,0 +1,257 @@
+//! State of the arm on an RGB LED, e.g. the WS2812 of the ESP32-C3 devkit.
+//!
+//! | State       | Pattern          |
+//! |-------------|------------------|
+//! | Idle        | green            |
+//! | Moving      | blue             |
+//! | Limit hit   | yellow           |
+//! | Error, stop | blinking red     |
+//! | Calibrating | breathing purple |
+//!
+//! The devkit LED is on GPIO8, which the stop switch takes on this board, so the LED goes on
+//! GPIO1, freed by the `i2c-gamepad` feature.
+
+use core::{cell::RefCell, time::Duration};
+
+use esp_hal::{
+    gpio::Level,
+    rmt::{Channel, PulseCode, Tx},
+    Blocking,
+};
+use ledc_servo::ServoDriver;
+
+use crate::{
+    armbot::{ArmBot, BaseJoint},
+    clock::{Clock, Instant, SystemClock},
+    error::Error,
+    gamepad::Gamepad,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const OFF: Color = Color::new(0, 0, 0);
+    pub const GREEN: Color = Color::new(0, 255, 0);
+    pub const BLUE: Color = Color::new(0, 0, 255);
+    pub const YELLOW: Color = Color::new(255, 160, 0);
+    pub const RED: Color = Color::new(255, 0, 0);
+    pub const PURPLE: Color = Color::new(160, 0, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Scales the color by `level` in `0.0..=1.0`.
+    pub fn dimmed(self, level: f32) -> Self {
+        let scale = |c: u8| (c as f32 * level.clamp(0.0, 1.0) + 0.5) as u8;
+        Self::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pattern {
+    Solid(Color),
+    /// Fades in and out over the period.
+    Breathing(Color, Duration),
+    /// On for half of the period.
+    Blink(Color, Duration),
+}
+
+impl Pattern {
+    /// Color of the pattern at the time.
+    pub fn color(&self, now: Instant) -> Color {
+        let phase = |period: &Duration| {
+            let period = period.as_micros().max(1) as u64;
+            (now.as_micros() % period) as f32 / period as f32
+        };
+        match self {
+            Pattern::Solid(color) => *color,
+            Pattern::Breathing(color, period) => {
+                // triangle wave, up in the first half and down in the second
+                let phase = phase(period);
+                color.dimmed(1.0 - (2.0 * phase - 1.0).abs())
+            }
+            Pattern::Blink(color, period) if phase(period) < 0.5 => *color,
+            Pattern::Blink(..) => Color::OFF,
+        }
+    }
+}
+
+/// Number of [`LedState`] variants.
+pub const LED_STATES: usize = 5;
+
+/// State shown by the LED, from the least important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedState {
+    Idle = 0,
+    Moving = 1,
+    LimitHit = 2,
+    /// Failed step, emergency stop or lost gamepad input.
+    Error = 3,
+    Calibrating = 4,
+}
+
+impl LedState {
+    /// State of the arm after a step, `failed` if the step returned an error.
+    pub fn of<G: Gamepad, D: ServoDriver, const N: usize, B: BaseJoint>(
+        bot: &ArmBot<G, D, N, B>,
+        failed: bool,
+    ) -> Self {
+        if failed || bot.is_stopped() || bot.has_faults() || bot.is_failsafe() {
+            LedState::Error
+        } else if bot.at_limit() {
+            LedState::LimitHit
+        } else if bot.is_moving() {
+            LedState::Moving
+        } else {
+            LedState::Idle
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusLedConfig {
+    /// Patterns of the states, indexed by [`LedState`].
+    pub patterns: [Pattern; LED_STATES],
+    /// Scale of all colors, a WS2812 at full power is blinding.
+    pub brightness: f32,
+}
+
+impl Default for StatusLedConfig {
+    fn default() -> Self {
+        Self {
+            patterns: [
+                Pattern::Solid(Color::GREEN),
+                Pattern::Solid(Color::BLUE),
+                Pattern::Solid(Color::YELLOW),
+                Pattern::Blink(Color::RED, Duration::from_secs(1)),
+                Pattern::Breathing(Color::PURPLE, Duration::from_secs(2)),
+            ],
+            brightness: 0.1,
+        }
+    }
+}
+
+/// Single RGB LED.
+pub trait RgbLed {
+    fn set_color(&mut self, color: Color) -> Result<(), Error>;
+}
+
+/// RMT ticks of the WS2812 bit timings at 80 MHz, high and low time of a 0 and a 1.
+const T0H: u16 = 32;
+const T0L: u16 = 68;
+const T1H: u16 = 64;
+const T1L: u16 = 36;
+
+/// WS2812 driven by an RMT channel.
+pub struct Ws2812<'d> {
+    /// Taken while a transmission runs.
+    channel: Option<Channel<'d, Blocking, Tx>>,
+}
+
+impl<'d> Ws2812<'d> {
+    /// The channel must run at 80 MHz, with a clock divider of 1.
+    pub fn new(channel: Channel<'d, Blocking, Tx>) -> Self {
+        Self {
+            channel: Some(channel),
+        }
+    }
+}
+
+impl RgbLed for Ws2812<'_> {
+    fn set_color(&mut self, color: Color) -> Result<(), Error> {
+        let channel = self
+            .channel
+            .take()
+            .ok_or(Error::Other("LED channel was lost"))?;
+        // 24 bits in GRB order, MSB first, and the end marker
+        let grb = (color.g as u32) << 16 | (color.r as u32) << 8 | color.b as u32;
+        let mut codes = [PulseCode::end_marker(); 25];
+        for (bit, code) in codes[..24].iter_mut().enumerate() {
+            let (high, low) = if grb & 1 << (23 - bit) != 0 {
+                (T1H, T1L)
+            } else {
+                (T0H, T0L)
+            };
+            *code = PulseCode::new(Level::High, high, Level::Low, low);
+        }
+        let transaction = channel
+            .transmit(&codes)
+            .map_err(|_| Error::Other("LED transmission failed"))?;
+        match transaction.wait() {
+            Ok(channel) => {
+                self.channel = Some(channel);
+                Ok(())
+            }
+            Err((_, channel)) => {
+                self.channel = Some(channel);
+                Err(Error::Other("LED transmission failed"))
+            }
+        }
+    }
+}
+
+/// Shows the state of the arm on the LED.
+pub struct StatusLed<L> {
+    led: L,
+    config: StatusLedConfig,
+    /// Last color sent, so the LED is only written on a change.
+    color: Option<Color>,
+}
+
+impl<L: RgbLed> StatusLed<L> {
+    pub fn new(led: L, config: StatusLedConfig) -> Self {
+        Self {
+            led,
+            config,
+            color: None,
+        }
+    }
+
+    /// Shows the state, should be called every control period for the patterns to animate.
+    pub fn update(&mut self, state: LedState, now: Instant) -> Result<(), Error> {
+        let color = self.config.patterns[state as usize]
+            .color(now)
+            .dimmed(self.config.brightness);
+        if self.color == Some(color) {
+            return Ok(());
+        }
+        self.color = Some(color);
+        self.led.set_color(color)
+    }
+}
+
+/// System clock that animates the LED whenever it's read.
+///
+/// The gamepad calibration blocks the control loop for seconds but reads the clock all along,
+/// so the LED keeps breathing through it.
+pub struct LedClock<'a, L> {
+    led: RefCell<&'a mut StatusLed<L>>,
+    state: LedState,
+}
+
+impl<'a, L: RgbLed> LedClock<'a, L> {
+    pub fn new(led: &'a mut StatusLed<L>, state: LedState) -> Self {
+        Self {
+            led: RefCell::new(led),
+            state,
+        }
+    }
+}
+
+impl<L: RgbLed> Clock for LedClock<'_, L> {
+    fn now(&self) -> Instant {
+        let now = SystemClock.now();
+        // a failed LED must not break what's being timed
+        let _ = self.led.borrow_mut().update(self.state, now);
+        now
+    }
+}
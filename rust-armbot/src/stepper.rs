@@ -0,0 +1,205 @@
+//! Base rotator driven by a stepper motor through an A4988, DRV8825 or another step/dir driver.
+//!
+//! Steps of a control period are emitted in a burst at the start of the period, spaced by
+//! [`StepperConfig::step_gap_us`]. Microstepping drivers smooth that out.
+//! The angle is counted from the position at power on, there's no homing switch.
+
+use core::ops::Range;
+
+use esp_hal::{delay::Delay, gpio::Output};
+use log::info;
+
+use crate::{
+    armbot::BaseJoint, error::Error, gamepad::Position, units::Degrees, util::slew::SlewLimiter,
+};
+
+/// Single steps in either direction.
+pub trait StepDir {
+    /// Moves by one step, towards greater angles if `forward`.
+    fn step(&mut self, forward: bool);
+
+    /// Cuts the coil current, the motor doesn't hold its position anymore.
+    fn disable(&mut self);
+
+    /// Powers the coils again.
+    fn enable(&mut self);
+}
+
+/// A4988, DRV8825 and other step/dir drivers.
+///
+/// The board has no pin left for ENABLE, it's tied to GND and the coils are always powered:
+/// the emergency stop stops the steps, the motor keeps holding.
+pub struct A4988<'d> {
+    step: Output<'d>,
+    dir: Output<'d>,
+    delay: Delay,
+}
+
+impl<'d> A4988<'d> {
+    pub fn new(step: Output<'d>, dir: Output<'d>) -> Self {
+        Self {
+            step,
+            dir,
+            delay: Delay::new(),
+        }
+    }
+}
+
+impl StepDir for A4988<'_> {
+    fn step(&mut self, forward: bool) {
+        self.dir.set_level(forward.into());
+        // both the dir setup time and the min pulse width are under a microsecond
+        self.delay.delay_micros(1);
+        self.step.set_high();
+        self.delay.delay_micros(2);
+        self.step.set_low();
+    }
+
+    fn disable(&mut self) {}
+
+    fn enable(&mut self) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct StepperConfig {
+    /// Steps per turn of the base, including microsteps and gearing.
+    pub steps_per_rev: u32,
+    /// Allowed angles from the position at power on.
+    pub angle_range: Range<Degrees>,
+    /// Top speed in °/s, the stick speed is clamped to it.
+    pub max_speed: f32,
+    /// Acceleration and deceleration in °/s².
+    pub accel: f32,
+    /// Pause after every step of a burst, in microseconds.
+    pub step_gap_us: u32,
+}
+
+impl StepperConfig {
+    /// 28BYJ-48 rewired to bipolar on a DRV8825 at 1/16 microstepping, 2048 full steps per turn
+    /// of its gearbox. It stalls above ~15 rpm, the speed stays well below.
+    pub fn drv8825_28byj48() -> Self {
+        Self {
+            steps_per_rev: 2048 * 16,
+            angle_range: Degrees::new(-90.0)..Degrees::new(90.0),
+            max_speed: 30.0,
+            accel: 60.0,
+            step_gap_us: 5,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.steps_per_rev == 0 {
+            return Err(Error::OutOfRange("stepper needs steps per turn"));
+        }
+        if self.angle_range.start >= self.angle_range.end {
+            return Err(Error::DegenerateRange);
+        }
+        let positive = |val: f32| val.is_finite() && val > 0.0;
+        if !positive(self.max_speed) || !positive(self.accel) {
+            return Err(Error::OutOfRange(
+                "stepper speed and accel must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Base joint of a stepper, the stick sets its speed and the speed is ramped by the accel.
+pub struct Stepper<M> {
+    motor: M,
+    delay: Delay,
+    gap_us: u32,
+    steps_per_deg: f32,
+    /// Allowed positions, in steps.
+    range: Range<i32>,
+    /// Position from power on, in steps.
+    position: i32,
+    /// Speed in steps per second, limited in how fast it changes.
+    speed: SlewLimiter,
+    max_speed: f32,
+    /// Fraction of a step carried over to the next period.
+    remainder: f32,
+    /// Length of a control period in seconds.
+    period: f32,
+    enabled: bool,
+}
+
+impl<M: StepDir> Stepper<M> {
+    pub fn new(config: StepperConfig, mut motor: M) -> Result<Self, Error> {
+        config.validate()?;
+        let steps_per_deg = config.steps_per_rev as f32 / 360.0;
+        let to_steps = |angle: Degrees| (angle.get() * steps_per_deg) as i32;
+        let period = crate::CONTROL_PERIOD.as_micros() as f32 / 1_000_000.0;
+        let max_accel = config.accel * steps_per_deg * period;
+        motor.enable();
+        info!("stepper base: {} steps per turn", config.steps_per_rev);
+        Ok(Self {
+            motor,
+            delay: Delay::new(),
+            gap_us: config.step_gap_us,
+            steps_per_deg,
+            range: to_steps(config.angle_range.start)..to_steps(config.angle_range.end),
+            position: 0,
+            speed: SlewLimiter::symmetric(max_accel, 0.0),
+            max_speed: config.max_speed * steps_per_deg,
+            remainder: 0.0,
+            period,
+            enabled: true,
+        })
+    }
+}
+
+impl<M: StepDir> BaseJoint for Stepper<M> {
+    fn make_step(&mut self, cmd: &Position) -> Result<(), Error> {
+        if !self.enabled {
+            return Ok(());
+        }
+        // stick steps are hundredths of a degree per control period
+        let per_second = |step: u32| step as f32 / 100.0 * self.steps_per_deg / self.period;
+        let target = match cmd {
+            Position::Center => 0.0,
+            Position::Low(step) => -per_second(*step),
+            Position::High(step) => per_second(*step),
+        };
+        let speed = self
+            .speed
+            .update(target.clamp(-self.max_speed, self.max_speed));
+
+        self.remainder += speed * self.period;
+        let steps = self.remainder as i32;
+        self.remainder -= steps as f32;
+        for _ in 0..steps.unsigned_abs() {
+            let next = self.position + steps.signum();
+            if !self.range.contains(&next) {
+                // stop at the limit instead of pushing against it
+                self.speed.reset(0.0);
+                self.remainder = 0.0;
+                break;
+            }
+            self.motor.step(steps > 0);
+            self.position = next;
+            self.delay.delay_micros(self.gap_us);
+        }
+        Ok(())
+    }
+
+    fn is_moving(&self) -> bool {
+        self.speed.value() != 0.0
+    }
+
+    fn angle(&self) -> Option<Degrees> {
+        Some(Degrees::new(self.position as f32 / self.steps_per_deg))
+    }
+
+    fn disable(&mut self) {
+        self.motor.disable();
+        self.speed.reset(0.0);
+        self.remainder = 0.0;
+        self.enabled = false;
+    }
+
+    fn enable(&mut self) {
+        self.motor.enable();
+        self.enabled = true;
+    }
+}
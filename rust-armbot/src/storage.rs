@@ -0,0 +1,75 @@
+//! Settings and the script in the `nvs` partition, see [`armbot_core::storage`] for the layout
+//! of the files, the sequence slots are left to host tools.
+
+use armbot_core::storage::{File, Storage};
+use esp_hal::peripherals::FLASH;
+use esp_storage::FlashStorage;
+use log::info;
+
+use crate::{
+    error::Error,
+    script::{self, Script},
+    settings::{self, Settings},
+};
+
+/// Start of the `nvs` partition of the default partition table.
+///
+/// The firmware doesn't use the esp-idf NVS format, the partition only holds the files.
+const NVS_OFFSET: u32 = 0x9000;
+
+/// Files persisted in flash, so calibration and routines survive power cycles.
+pub struct FlashStore<'d> {
+    storage: Storage<FlashStorage<'d>>,
+}
+
+impl<'d> FlashStore<'d> {
+    pub fn new(flash: FLASH<'d>) -> Self {
+        Self {
+            storage: Storage::new(FlashStorage::new(flash), NVS_OFFSET),
+        }
+    }
+
+    /// Whole flash, for writing firmware updates.
+    #[cfg(feature = "ota")]
+    pub fn flash(&mut self) -> &mut FlashStorage<'d> {
+        self.storage.flash()
+    }
+
+    /// Reads stored settings, fails with [`settings::SettingsError::BadMagic`]
+    /// if nothing was stored yet.
+    pub fn load_settings(&mut self) -> Result<Settings, Error> {
+        let mut buf = [0; settings::MAX_LEN];
+        let bytes = self.storage.read_raw(File::Settings, &mut buf)?;
+        let settings = Settings::decode(bytes)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Validates and stores the settings.
+    pub fn save_settings(&mut self, settings: &Settings) -> Result<(), Error> {
+        settings.validate()?;
+        let mut buf = [0; settings::MAX_LEN];
+        let len = settings.encode(&mut buf);
+        self.storage.write_raw(File::Settings, &buf[..len])?;
+        info!("settings saved, {len} bytes");
+        Ok(())
+    }
+
+    /// Reads and parses the script, flashed with `espflash write-bin 0xa000 script.txt`.
+    /// Returns `None` if nothing was written.
+    pub fn load_script<const N: usize>(&mut self) -> Result<Option<Script<N>>, Error> {
+        let mut buf = [0; script::MAX_LEN];
+        let bytes = self.storage.read_raw(File::Script, &mut buf)?;
+        // erased flash reads as 0xff, the text ends at the first erased byte
+        let len = bytes
+            .iter()
+            .position(|&byte| byte == 0xff || byte == 0)
+            .unwrap_or(bytes.len());
+        if len == 0 {
+            return Ok(None);
+        }
+        let text =
+            core::str::from_utf8(&bytes[..len]).map_err(|_| Error::Other("script isn't UTF-8"))?;
+        Script::parse(text).map(Some)
+    }
+}
@@ -0,0 +1,147 @@
+//! Periodic snapshots of the arm state, for debugging motion without sprinkling logs.
+//!
+//! Snapshots go to a [`Sink`]. [`LogSink`] prints them to the log, sinks that send them
+//! somewhere else (UART, UDP) serialize them with serde, postcard or JSON, under the `serde`
+//! feature.
+
+use ledc_servo::ServoDriver;
+use log::debug;
+
+#[cfg(feature = "battery")]
+use crate::power::BatteryStatus;
+use crate::{
+    armbot::{ArmBot, BaseJoint, JointCounters},
+    clock::Instant,
+    error::Error,
+    gamepad::{Gamepad, State},
+    ticker::LoopStats,
+    units::Degrees,
+};
+
+/// Control loop timing over the last report period.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LoopTiming {
+    pub avg_us: u32,
+    pub max_us: u32,
+    /// Steps that took longer than the control period.
+    pub overruns: u32,
+    /// Ticks that came before the previous step was done.
+    pub missed: u32,
+}
+
+impl LoopTiming {
+    pub fn new(stats: &LoopStats, missed: u32) -> Self {
+        Self {
+            avg_us: stats.average().as_micros() as u32,
+            max_us: stats.max.as_micros() as u32,
+            overruns: stats.overruns,
+            missed,
+        }
+    }
+}
+
+/// State of the arm at a point in time.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Snapshot<const N: usize> {
+    /// Milliseconds since boot.
+    pub time_ms: u64,
+    /// Commanded angles of the joints.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_array"))]
+    pub angles: [Degrees; N],
+    /// Commanded pulse widths of the servos in microseconds.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_array"))]
+    pub pulses_us: [Option<u32>; N],
+    /// Gamepad input of the last step.
+    pub gamepad: State,
+    pub timing: LoopTiming,
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::serde_array"))]
+    pub counters: [JointCounters; N],
+    pub stopped: bool,
+    pub faults: bool,
+    /// Set with [`Snapshot::with_battery`].
+    #[cfg(feature = "battery")]
+    pub battery: Option<BatteryStatus>,
+}
+
+impl<const N: usize> Snapshot<N> {
+    pub fn capture<G: Gamepad, D: ServoDriver, B: BaseJoint>(
+        bot: &ArmBot<G, D, N, B>,
+        now: Instant,
+        timing: LoopTiming,
+    ) -> Self {
+        Self {
+            time_ms: now.as_micros() / 1000,
+            angles: bot.joint_angles(),
+            pulses_us: bot.pulse_widths(),
+            gamepad: bot.gamepad_state().clone(),
+            timing,
+            counters: bot.counters(),
+            stopped: bot.is_stopped(),
+            faults: bot.has_faults(),
+            #[cfg(feature = "battery")]
+            battery: None,
+        }
+    }
+
+    #[cfg(feature = "battery")]
+    pub fn with_battery(mut self, battery: BatteryStatus) -> Self {
+        self.battery = Some(battery);
+        self
+    }
+}
+
+/// Where snapshots go.
+pub trait Sink<const N: usize> {
+    fn send(&mut self, snapshot: &Snapshot<N>) -> Result<(), Error>;
+}
+
+/// Prints snapshots to the log at the debug level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl<const N: usize> Sink<N> for LogSink {
+    fn send(&mut self, snapshot: &Snapshot<N>) -> Result<(), Error> {
+        let timing = &snapshot.timing;
+        debug!(
+            "telemetry at {} ms: stopped={} faults={}, loop avg={} us max={} us overruns={} missed={}",
+            snapshot.time_ms,
+            snapshot.stopped as u8,
+            snapshot.faults as u8,
+            timing.avg_us,
+            timing.max_us,
+            timing.overruns,
+            timing.missed,
+        );
+        for (joint, ((angle, pulse), counters)) in snapshot
+            .angles
+            .iter()
+            .zip(&snapshot.pulses_us)
+            .zip(&snapshot.counters)
+            .enumerate()
+        {
+            debug!(
+                "  J{}: {angle} pulse={pulse:?} us steps={} limit hits={} errors={} stalls={}",
+                joint + 1,
+                counters.steps,
+                counters.limit_hits,
+                counters.errors,
+                counters.stalls,
+            );
+        }
+        let gamepad = &snapshot.gamepad;
+        debug!(
+            "  gamepad: axes={:?} buttons={:?}",
+            gamepad.axes, gamepad.buttons
+        );
+        #[cfg(feature = "battery")]
+        if let Some(battery) = &snapshot.battery {
+            debug!(
+                "  battery: {:.2} V {}% action={:?}",
+                battery.volts, battery.percent, battery.action
+            );
+        }
+        Ok(())
+    }
+}
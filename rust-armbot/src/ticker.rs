@@ -0,0 +1,119 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration as StdDuration,
+};
+
+use critical_section::Mutex;
+use esp_hal::{
+    handler,
+    interrupt::Priority,
+    time::Duration,
+    timer::{timg::Timer, PeriodicTimer},
+    Blocking,
+};
+
+use crate::error::Error;
+
+/// Periodic timer that drives the control loop, shared with the interrupt handler.
+static TIMER: Mutex<RefCell<Option<PeriodicTimer<'static, Blocking>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Number of timer periods elapsed since the ticker was started.
+static TICKS: AtomicU32 = AtomicU32::new(0);
+
+/// Fixed-rate tick source for the control loop.
+///
+/// The hardware timer auto-reloads, so the period doesn't drift with the time spent in
+/// `do_step` or with logging. The interrupt handler only bumps a counter and checks the step
+/// deadline of [`crate::watchdog`], all the work happens in the main loop after
+/// [`Ticker::wait`] returns.
+pub struct Ticker {
+    /// Tick number that was handled last.
+    last_tick: u32,
+}
+
+impl Ticker {
+    /// Starts the timer with the specified period and binds the tick interrupt.
+    pub fn start(timer: Timer<'static>, period: Duration) -> Result<Self, Error> {
+        let mut periodic = PeriodicTimer::new(timer);
+        periodic.set_interrupt_handler(on_tick);
+        periodic
+            .start(period)
+            .map_err(|_| Error::Other("failed to start the tick timer"))?;
+        periodic.listen();
+
+        critical_section::with(|cs| TIMER.borrow_ref_mut(cs).replace(periodic));
+
+        Ok(Self {
+            last_tick: TICKS.load(Ordering::Acquire),
+        })
+    }
+
+    /// Blocks until the next tick.
+    /// Returns the number of ticks that were missed since the previous call,
+    /// non zero value means that the loop body doesn't fit into the period.
+    pub fn wait(&mut self) -> u32 {
+        let mut tick = TICKS.load(Ordering::Acquire);
+        while tick == self.last_tick {
+            core::hint::spin_loop();
+            tick = TICKS.load(Ordering::Acquire);
+        }
+
+        let missed = tick.wrapping_sub(self.last_tick) - 1;
+        self.last_tick = tick;
+        missed
+    }
+}
+
+/// Time the loop body takes, collected over a report period.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoopStats {
+    /// Number of measured loop bodies.
+    pub count: u32,
+    /// Sum of the body times in microseconds.
+    total_us: u64,
+    pub max: StdDuration,
+    /// Bodies that took longer than the tick period, the next tick is late or missed.
+    pub overruns: u32,
+}
+
+impl LoopStats {
+    /// Adds the time of a loop body, returns true if it didn't fit into the period.
+    pub fn record(&mut self, elapsed: StdDuration, period: Duration) -> bool {
+        self.count += 1;
+        self.total_us += elapsed.as_micros() as u64;
+        self.max = self.max.max(elapsed);
+        let overrun = elapsed.as_micros() as u64 > period.as_micros();
+        if overrun {
+            self.overruns += 1;
+        }
+        overrun
+    }
+
+    pub fn average(&self) -> StdDuration {
+        if self.count == 0 {
+            return StdDuration::ZERO;
+        }
+        StdDuration::from_micros(self.total_us / self.count as u64)
+    }
+
+    /// Returns the stats so far and starts over.
+    pub fn take(&mut self) -> Self {
+        core::mem::take(self)
+    }
+}
+
+#[handler(priority = Priority::Priority2)]
+fn on_tick() {
+    critical_section::with(|cs| {
+        if let Some(timer) = TIMER.borrow_ref_mut(cs).as_mut() {
+            timer.clear_interrupt();
+        }
+        // no atomic RMW on riscv32imc, the handler is the only writer
+        let tick = TICKS.load(Ordering::Relaxed).wrapping_add(1);
+        TICKS.store(tick, Ordering::Release);
+    });
+    // outside of the critical section, a hung step panics from here
+    crate::watchdog::check_step();
+}
@@ -0,0 +1,107 @@
+//! Watchdogs of the control loop, a hung step resets the board instead of leaving the servos
+//! holding whatever pulse they got last.
+//!
+//! Two checks of different reach:
+//! - a software deadline of the step, checked by the tick interrupt of [`crate::ticker`]. A
+//!   step that runs past it, e.g. stuck in an ADC read, panics from the interrupt, the panic
+//!   is kept in the crash log and the board resets.
+//! - the MWDT of timer group 0, fed after every step. It resets the board if the loop hangs
+//!   with interrupts disabled, where the tick interrupt can't run.
+//!
+//! A reset stops the LEDC, the servo pins get no pulses and the servos go limp, as detached.
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+
+use esp_hal::{
+    peripherals::TIMG0,
+    timer::timg::{MwdtStage, Wdt},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// Start of the running step, the low 32 bits of the boot time in microseconds.
+static STEP_STARTED: AtomicU32 = AtomicU32::new(0);
+/// A step is running.
+static IN_STEP: AtomicBool = AtomicBool::new(false);
+/// Step deadline in microseconds, 0 until the watchdog is started.
+static DEADLINE_US: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// How long a step may take, a few control periods.
+    pub step_deadline: Duration,
+    /// How long the loop may go without a step, it must cover the report and flash writes.
+    pub timeout: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            step_deadline: Duration::from_millis(50),
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Watches the steps of the control loop.
+pub struct Watchdog {
+    wdt: Wdt<TIMG0<'static>>,
+}
+
+impl Watchdog {
+    /// Arms both checks, the loop must start stepping within the timeout.
+    pub fn start(mut wdt: Wdt<TIMG0<'static>>, config: WatchdogConfig) -> Self {
+        let deadline = config.step_deadline.as_micros().min(u32::MAX as u128) as u32;
+        DEADLINE_US.store(deadline, Ordering::Release);
+        wdt.set_timeout(
+            MwdtStage::Stage0,
+            esp_hal::time::Duration::from_micros(config.timeout.as_micros() as u64),
+        );
+        // stage 0 resets the system once enabled
+        wdt.enable();
+        Self { wdt }
+    }
+
+    /// Marks the start of a step, it must end with [`Watchdog::step_done`] within the deadline.
+    pub fn step_started(&self) {
+        STEP_STARTED.store(now_us(), Ordering::Relaxed);
+        IN_STEP.store(true, Ordering::Release);
+    }
+
+    /// Marks the end of the step and feeds the hardware watchdog.
+    pub fn step_done(&mut self) {
+        IN_STEP.store(false, Ordering::Release);
+        self.wdt.feed();
+    }
+
+    /// Stops the hardware watchdog for work that blocks the loop on purpose, e.g. the gamepad
+    /// calibration. Steps aren't checked outside of them anyway.
+    pub fn pause(&mut self) {
+        self.wdt.disable();
+    }
+
+    pub fn resume(&mut self) {
+        self.wdt.feed();
+        self.wdt.enable();
+    }
+}
+
+/// Panics if the running step is past its deadline, called by the tick interrupt.
+pub fn check_step() {
+    let deadline = DEADLINE_US.load(Ordering::Acquire);
+    if deadline == 0 || !IN_STEP.load(Ordering::Acquire) {
+        return;
+    }
+    let elapsed = now_us().wrapping_sub(STEP_STARTED.load(Ordering::Relaxed));
+    if elapsed > deadline {
+        panic!("control step hung for {elapsed} us, deadline {deadline} us");
+    }
+}
+
+/// Wraps after 71 minutes, the wrapping difference stays right for short spans.
+fn now_us() -> u32 {
+    SystemClock.now().as_micros() as u32
+}
@@ -0,0 +1,174 @@
+//! On-target tests, flashed and run on the board with probe-rs:
+//!
+//! ```sh
+//! CARGO_TARGET_RISCV32IMC_UNKNOWN_NONE_ELF_RUNNER="probe-rs run --chip esp32c3" \
+//!     cargo test -p rust-armbot --test hil
+//! ```
+//!
+//! They check the HAL integration the firmware relies on, so breakage after esp-hal upgrades
+//! shows up here rather than as a twitching arm.
+#![no_std]
+#![no_main]
+
+use defmt_rtt as _;
+use esp_backtrace as _;
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+// test and panic logs go out over RTT, probe-rs prints them with the time since boot
+defmt::timestamp!(
+    "{=u64:us}",
+    esp_hal::time::Instant::now()
+        .duration_since_epoch()
+        .as_micros()
+);
+
+#[cfg(test)]
+#[embedded_test::tests(default_timeout = 5)]
+mod tests {
+    use esp_hal::{
+        analog::adc::{Adc, AdcConfig, Attenuation},
+        ledc::{channel, timer, timer::config::Duty, Ledc, LowSpeed},
+        peripherals::{Peripherals, LEDC},
+        time::{Duration, Instant},
+        timer::{timg::TimerGroup, PeriodicTimer},
+        Config,
+    };
+    use ledc_servo::{Dir, Servo, ServoConfig};
+
+    /// Duty counts of a 14 bit timer at 50Hz for 0.5ms and 2.5ms pulses.
+    const MIN_SERVO_DUTY: u32 = 409;
+    const MAX_SERVO_DUTY: u32 = 2048;
+
+    #[init]
+    fn init() -> Peripherals {
+        esp_hal::init(Config::default())
+    }
+
+    /// Reads the duty that is currently applied by the LEDC channel.
+    fn channel_duty(number: channel::Number) -> u32 {
+        // the low 4 bits of the register are the fractional part
+        LEDC::regs()
+            .ch(number as usize)
+            .duty_r()
+            .read()
+            .duty_r()
+            .bits()
+            >> 4
+    }
+
+    #[test]
+    fn servo_duty_is_written_to_ledc(p: Peripherals) {
+        let servo_cfg = ServoConfig::sg90(Duty::Duty14Bit);
+        let ledc = Ledc::new(p.LEDC);
+        let timer = servo_cfg
+            .configure_timer::<LowSpeed>(&ledc, timer::Number::Timer0, timer::LSClockSource::APBClk)
+            .unwrap();
+        let mut servo = Servo::new(
+            "test",
+            servo_cfg,
+            &ledc,
+            &timer,
+            channel::Number::Channel0,
+            p.GPIO5,
+        )
+        .unwrap();
+
+        let initial = channel_duty(channel::Number::Channel0);
+        assert!((MIN_SERVO_DUTY..=MAX_SERVO_DUTY).contains(&initial));
+
+        servo.set_dir(Dir::CCW);
+        servo.step(10.0).unwrap();
+        let stepped = channel_duty(channel::Number::Channel0);
+        assert!((MIN_SERVO_DUTY..=MAX_SERVO_DUTY).contains(&stepped));
+        assert_ne!(initial, stepped);
+    }
+
+    #[test]
+    fn servo_angle_maps_to_pulse_range(p: Peripherals) {
+        let servo_cfg = ServoConfig::sg90(Duty::Duty14Bit);
+        let ledc = Ledc::new(p.LEDC);
+        let timer = servo_cfg
+            .configure_timer::<LowSpeed>(&ledc, timer::Number::Timer0, timer::LSClockSource::APBClk)
+            .unwrap();
+        let mut servo = Servo::new(
+            "test",
+            servo_cfg,
+            &ledc,
+            &timer,
+            channel::Number::Channel0,
+            p.GPIO5,
+        )
+        .unwrap();
+
+        servo.set_angle(0.0).unwrap();
+        assert_eq!(channel_duty(channel::Number::Channel0), MIN_SERVO_DUTY);
+        servo.set_angle(180.0).unwrap();
+        assert_eq!(channel_duty(channel::Number::Channel0), MAX_SERVO_DUTY);
+        // out of range angles are rejected without moving
+        assert!(servo.set_angle(-30.0).is_err());
+        assert_eq!(channel_duty(channel::Number::Channel0), MAX_SERVO_DUTY);
+
+        servo.center();
+        let center = channel_duty(channel::Number::Channel0);
+        assert_eq!(center, (MIN_SERVO_DUTY + MAX_SERVO_DUTY) / 2);
+    }
+
+    #[test]
+    fn detached_servo_has_no_pulses(p: Peripherals) {
+        let servo_cfg = ServoConfig::sg90(Duty::Duty14Bit);
+        let ledc = Ledc::new(p.LEDC);
+        let timer = servo_cfg
+            .configure_timer::<LowSpeed>(&ledc, timer::Number::Timer0, timer::LSClockSource::APBClk)
+            .unwrap();
+        let mut servo = Servo::new(
+            "test",
+            servo_cfg,
+            &ledc,
+            &timer,
+            channel::Number::Channel0,
+            p.GPIO5,
+        )
+        .unwrap();
+
+        servo.set_angle(0.0).unwrap();
+        servo.detach();
+        assert_eq!(channel_duty(channel::Number::Channel0), 0);
+        // moves are remembered and applied on attach
+        servo.set_angle(180.0).unwrap();
+        assert_eq!(channel_duty(channel::Number::Channel0), 0);
+        servo.attach();
+        assert_eq!(channel_duty(channel::Number::Channel0), MAX_SERVO_DUTY);
+    }
+
+    #[test]
+    fn adc_reads_joystick_pins(p: Peripherals) {
+        let mut adc_config = AdcConfig::new();
+        let mut pin0 = adc_config.enable_pin(p.GPIO0, Attenuation::_11dB);
+        let mut pin1 = adc_config.enable_pin(p.GPIO1, Attenuation::_11dB);
+        let mut adc = Adc::new(p.ADC1, adc_config);
+
+        for _ in 0..10 {
+            let val0 = nb::block!(adc.read_oneshot(&mut pin0)).unwrap();
+            let val1 = nb::block!(adc.read_oneshot(&mut pin1)).unwrap();
+            // 12 bit ADC
+            assert!(val0 < 4096);
+            assert!(val1 < 4096);
+        }
+    }
+
+    #[test]
+    fn periodic_timer_keeps_period(p: Peripherals) {
+        let timg0 = TimerGroup::new(p.TIMG0);
+        let mut periodic = PeriodicTimer::new(timg0.timer0);
+        periodic.start(Duration::from_millis(10)).unwrap();
+
+        periodic.wait();
+        let started = Instant::now();
+        for _ in 0..10 {
+            periodic.wait();
+        }
+        let elapsed = started.elapsed().as_micros();
+        assert!((99_000..=101_000).contains(&elapsed));
+    }
+}